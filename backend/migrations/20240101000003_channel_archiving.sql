@@ -0,0 +1,13 @@
+-- Channel archiving and announcement channels
+
+-- Archived channels are read-only and hidden from default channel lists
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS is_archived BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+
+-- Announcement channels only accept messages from team admins and designated posters
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS is_announcement BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Designated posters for announcement channels
+ALTER TABLE channel_members ADD COLUMN IF NOT EXISTS can_post BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_channels_archived ON channels(is_archived);
@@ -0,0 +1,16 @@
+-- Per-channel notification preferences
+
+-- One of 'all', 'mentions' or 'nothing'. Members of team channels are notified of
+-- mentions only unless they ask for every message; direct messages notify on every
+-- message. New members get the level for their channel's type when they join.
+ALTER TABLE channel_members ADD COLUMN IF NOT EXISTS notify_level TEXT NOT NULL DEFAULT 'mentions';
+
+UPDATE channel_members cm SET notify_level = 'all'
+FROM channels c
+WHERE c.id = cm.channel_id AND c.channel_type = 'directmessage';
+
+-- Muted channels raise no notifications and show no unread badge until this time
+ALTER TABLE channel_members ADD COLUMN IF NOT EXISTS muted_until TIMESTAMPTZ;
+
+-- Hidden channels are left out of the sidebar
+ALTER TABLE channel_members ADD COLUMN IF NOT EXISTS hidden BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,6 @@
+-- Read receipts
+
+-- Users can opt out of sharing when they have read messages
+ALTER TABLE users ADD COLUMN IF NOT EXISTS read_receipts_enabled BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE INDEX IF NOT EXISTS idx_channel_members_last_read ON channel_members(channel_id, last_read_at);
@@ -0,0 +1,14 @@
+-- Shared channels between teams
+
+-- Additional teams a channel is shared with; channels.team_id remains the host team
+CREATE TABLE IF NOT EXISTS channel_teams (
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    invited_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    invited_at TIMESTAMPTZ NOT NULL,
+    -- Set once an owner or admin of the invited team accepts
+    accepted_at TIMESTAMPTZ,
+    PRIMARY KEY (channel_id, team_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_channel_teams_team ON channel_teams(team_id);
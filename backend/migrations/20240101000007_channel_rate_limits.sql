@@ -0,0 +1,9 @@
+-- Slow mode and per-user message rate limits
+
+-- Minimum number of seconds between two messages from the same member (0 disables slow mode)
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS slow_mode_seconds INTEGER NOT NULL DEFAULT 0;
+
+-- Maximum number of messages a member may send per minute (0 means unlimited)
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS burst_limit INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_messages_channel_sender ON messages(channel_id, sender_id, created_at);
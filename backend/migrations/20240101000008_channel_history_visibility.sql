@@ -0,0 +1,4 @@
+-- History visibility for newly added channel members
+
+-- 'full' shows the whole channel history, 'joined' only messages sent after the member joined
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS history_visibility TEXT NOT NULL DEFAULT 'full';
@@ -0,0 +1,16 @@
+-- Resolved mentions in messages
+
+CREATE TABLE IF NOT EXISTS message_mentions (
+    id UUID PRIMARY KEY NOT NULL,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    -- One of 'user', 'channel' or 'here'
+    mention_type TEXT NOT NULL,
+    -- Set for user mentions only
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    -- Byte offsets of the mention token in the message content
+    start_offset INTEGER NOT NULL,
+    end_offset INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_mentions_message ON message_mentions(message_id);
+CREATE INDEX IF NOT EXISTS idx_message_mentions_user ON message_mentions(user_id);
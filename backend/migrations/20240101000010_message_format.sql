@@ -0,0 +1,3 @@
+-- Rendering format of message content, 'plain' or 'markdown'
+
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS format TEXT NOT NULL DEFAULT 'plain';
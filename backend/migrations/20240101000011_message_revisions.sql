@@ -0,0 +1,17 @@
+-- Message edit history and soft-deleted tombstones
+
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS deleted_by UUID REFERENCES users(id) ON DELETE SET NULL;
+
+-- Every prior version of a message, including the content it had when deleted
+CREATE TABLE IF NOT EXISTS message_revisions (
+    id UUID PRIMARY KEY NOT NULL,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    replaced_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- When this version was written
+    created_at TIMESTAMPTZ NOT NULL,
+    replaced_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_revisions_message ON message_revisions(message_id, replaced_at);
@@ -0,0 +1,18 @@
+-- Moderator edits and removals of other users' messages
+
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS moderated_by UUID REFERENCES users(id) ON DELETE SET NULL;
+
+-- Audit log of moderation actions, kept even if the message is purged
+CREATE TABLE IF NOT EXISTS moderation_actions (
+    id UUID PRIMARY KEY NOT NULL,
+    message_id UUID REFERENCES messages(id) ON DELETE SET NULL,
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    moderator_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- One of 'edit' or 'delete'
+    action TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_moderation_actions_team ON moderation_actions(team_id, created_at DESC);
@@ -0,0 +1,14 @@
+-- Unsent message drafts, synced between a user's devices
+
+CREATE TABLE IF NOT EXISTS drafts (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    -- Set for drafts replying in a thread
+    thread_root_id UUID REFERENCES messages(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+-- One draft per user per channel, and per thread within it
+CREATE UNIQUE INDEX IF NOT EXISTS idx_drafts_user_channel_thread
+    ON drafts(user_id, channel_id, COALESCE(thread_root_id, '00000000-0000-0000-0000-000000000000'::uuid));
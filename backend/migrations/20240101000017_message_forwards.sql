@@ -0,0 +1,16 @@
+-- Forwarded messages keep a snapshot of the message they were shared from
+
+CREATE TABLE IF NOT EXISTS message_forwards (
+    message_id UUID PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    source_message_id UUID REFERENCES messages(id) ON DELETE SET NULL,
+    source_channel_id UUID REFERENCES channels(id) ON DELETE SET NULL,
+    source_sender_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    content TEXT NOT NULL,
+    format TEXT NOT NULL DEFAULT 'plain',
+    source_created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_forwards_source ON message_forwards(source_message_id);
+
+-- Forwarded attachments share the stored file with the original
+CREATE INDEX IF NOT EXISTS idx_files_storage_path ON file_attachments(storage_path);
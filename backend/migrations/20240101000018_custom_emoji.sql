@@ -0,0 +1,12 @@
+-- Custom emoji per team, addressed as :name: in reactions
+
+CREATE TABLE IF NOT EXISTS custom_emoji (
+    id UUID PRIMARY KEY NOT NULL,
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    -- The image, uploaded through the file subsystem
+    file_id UUID NOT NULL REFERENCES file_attachments(id) ON DELETE CASCADE,
+    created_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    UNIQUE(team_id, name)
+);
@@ -0,0 +1,5 @@
+-- Keyset pagination walks lists newest first by (created_at, id)
+
+CREATE INDEX IF NOT EXISTS idx_messages_channel_created_id ON messages(channel_id, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_notifications_user_created_id ON notifications(user_id, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_files_channel_created_id ON file_attachments(channel_id, created_at DESC, id DESC);
@@ -0,0 +1,10 @@
+-- Client generated nonces of sent messages, so a retried send returns the
+-- message stored the first time instead of posting it again
+
+CREATE TABLE IF NOT EXISTS message_nonces (
+    sender_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    client_nonce TEXT NOT NULL,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (sender_id, client_nonce)
+);
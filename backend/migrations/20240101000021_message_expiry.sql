@@ -0,0 +1,6 @@
+-- Self-destructing messages: once expires_at has passed, the purge worker removes
+-- the content, history and attachments, then clears expires_at
+
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_messages_expires_at ON messages(expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Message retention: days to keep messages, set per team and overridden per channel.
+-- NULL inherits (channel -> team -> server default) and 0 keeps messages forever.
+
+ALTER TABLE teams ADD COLUMN IF NOT EXISTS retention_days INTEGER;
+ALTER TABLE channels ADD COLUMN IF NOT EXISTS retention_days INTEGER;
@@ -0,0 +1,36 @@
+-- Per-team content rules, run on every message sent or edited in the team's channels
+
+CREATE TABLE IF NOT EXISTS content_rules (
+    id UUID PRIMARY KEY NOT NULL,
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    -- One of 'regex', 'secrets', 'cards' or 'words'
+    kind TEXT NOT NULL,
+    pattern TEXT,
+    words TEXT[] NOT NULL DEFAULT '{}',
+    -- One of 'block', 'redact' or 'flag'
+    action TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_content_rules_team ON content_rules(team_id);
+
+-- Messages flagged by a rule, queued for the team's moderators until resolved
+CREATE TABLE IF NOT EXISTS flagged_messages (
+    id UUID PRIMARY KEY NOT NULL,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    rule_id UUID REFERENCES content_rules(id) ON DELETE SET NULL,
+    rule_name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    resolved_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_flagged_messages_queue ON flagged_messages(team_id, created_at DESC, id DESC) WHERE resolved_at IS NULL;
+-- A message is queued once per rule, even if it is edited again
+CREATE UNIQUE INDEX IF NOT EXISTS idx_flagged_messages_open ON flagged_messages(message_id, rule_id) WHERE resolved_at IS NULL;
@@ -0,0 +1,8 @@
+-- Keep scheduled messages until they are posted, and keep failed ones for the author to retry
+
+ALTER TABLE scheduled_messages ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'pending';
+ALTER TABLE scheduled_messages ADD COLUMN IF NOT EXISTS error TEXT;
+ALTER TABLE scheduled_messages ADD COLUMN IF NOT EXISTS claimed_at TIMESTAMPTZ;
+
+DROP INDEX IF EXISTS idx_scheduled_messages_send_at;
+CREATE INDEX IF NOT EXISTS idx_scheduled_messages_due ON scheduled_messages(send_at) WHERE status <> 'failed';
@@ -0,0 +1,6 @@
+-- A retried schedule request stores the message once
+
+ALTER TABLE scheduled_messages ADD COLUMN IF NOT EXISTS client_nonce TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_scheduled_messages_client_nonce
+    ON scheduled_messages(sender_id, client_nonce) WHERE client_nonce IS NOT NULL;
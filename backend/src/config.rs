@@ -9,6 +9,7 @@ pub struct AppConfig {
     pub database: DatabaseConfig,
     pub jwt: JwtConfig,
     pub storage: StorageConfig,
+    pub retention: RetentionConfig,
     pub turn: Option<TurnConfig>,
 }
 
@@ -44,6 +45,17 @@ pub struct StorageConfig {
     pub max_file_size: usize, // bytes
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetentionConfig {
+    /// Days to keep messages in channels whose team sets no policy, 0 for forever
+    pub default_days: i32,
+    pub purge_interval: u64, // seconds
+    /// Most messages deleted per batch
+    pub batch_size: i64,
+    /// Only report what the purge would delete
+    pub dry_run: bool,
+}
+
 impl AppConfig {
     pub fn load() -> Result<Self, config::ConfigError> {
         let config = Self {
@@ -81,6 +93,23 @@ impl AppConfig {
                     .parse()
                     .unwrap_or(104857600),
             },
+            retention: RetentionConfig {
+                default_days: env::var("RETENTION_DEFAULT_DAYS")
+                    .unwrap_or_else(|_| "0".to_string()) // keep forever
+                    .parse()
+                    .unwrap_or(0),
+                purge_interval: env::var("RETENTION_PURGE_INTERVAL")
+                    .unwrap_or_else(|_| "3600".to_string()) // 1 hour
+                    .parse()
+                    .unwrap_or(3600),
+                batch_size: env::var("RETENTION_BATCH_SIZE")
+                    .unwrap_or_else(|_| "1000".to_string())
+                    .parse()
+                    .unwrap_or(1000),
+                dry_run: env::var("RETENTION_DRY_RUN")
+                    .map(|v| v == "true" || v == "1")
+                    .unwrap_or(false),
+            },
             turn: env::var("TURN_SERVER_URL").ok().filter(|s| !s.is_empty()).map(|server_url| TurnConfig {
                 server_url,
                 username: env::var("TURN_USERNAME").unwrap_or_default(),
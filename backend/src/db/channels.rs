@@ -1,8 +1,10 @@
 //! Channel database operations
 
 use chrono::{DateTime, Utc};
-use shared::models::{Channel, ChannelMember, ChannelType};
-use sqlx::{FromRow, PgPool};
+use shared::models::{
+    Channel, ChannelMember, ChannelTeam, ChannelType, HistoryVisibility, NotificationLevel,
+};
+use sqlx::{FromRow, PgExecutor, PgPool};
 use uuid::Uuid;
 
 #[derive(Debug, FromRow)]
@@ -13,6 +15,12 @@ pub struct ChannelRow {
     pub description: Option<String>,
     pub channel_type: String,
     pub created_by: Uuid,
+    pub is_archived: bool,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub is_announcement: bool,
+    pub slow_mode_seconds: i32,
+    pub burst_limit: i32,
+    pub history_visibility: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -26,6 +34,12 @@ impl From<ChannelRow> for Channel {
             description: row.description,
             channel_type: serde_json::from_str(&format!("\"{}\"", row.channel_type)).unwrap_or_default(),
             created_by: row.created_by,
+            is_archived: row.is_archived,
+            archived_at: row.archived_at,
+            is_announcement: row.is_announcement,
+            slow_mode_seconds: row.slow_mode_seconds,
+            burst_limit: row.burst_limit,
+            history_visibility: serde_json::from_str(&format!("\"{}\"", row.history_visibility)).unwrap_or_default(),
             created_at: row.created_at,
             updated_at: row.updated_at,
         }
@@ -39,6 +53,10 @@ pub struct ChannelMemberRow {
     pub user_id: Uuid,
     pub joined_at: DateTime<Utc>,
     pub last_read_at: Option<DateTime<Utc>>,
+    pub can_post: bool,
+    pub notify_level: String,
+    pub muted_until: Option<DateTime<Utc>>,
+    pub hidden: bool,
 }
 
 impl From<ChannelMemberRow> for ChannelMember {
@@ -49,10 +67,43 @@ impl From<ChannelMemberRow> for ChannelMember {
             user_id: row.user_id,
             joined_at: row.joined_at,
             last_read_at: row.last_read_at,
+            can_post: row.can_post,
+            notify_level: serde_json::from_str(&format!("\"{}\"", row.notify_level)).unwrap_or_default(),
+            muted_until: row.muted_until,
+            hidden: row.hidden,
         }
     }
 }
 
+#[derive(Debug, FromRow)]
+pub struct ChannelTeamRow {
+    pub channel_id: Uuid,
+    pub team_id: Uuid,
+    pub invited_by: Uuid,
+    pub invited_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+}
+
+impl From<ChannelTeamRow> for ChannelTeam {
+    fn from(row: ChannelTeamRow) -> Self {
+        ChannelTeam {
+            channel_id: row.channel_id,
+            team_id: row.team_id,
+            invited_by: row.invited_by,
+            invited_at: row.invited_at,
+            accepted_at: row.accepted_at,
+        }
+    }
+}
+
+/// Adds user $3 to channel $2, with the notification level of
+/// `NotificationLevel::default_for` the channel's type
+const INSERT_MEMBER: &str = r#"
+    INSERT INTO channel_members (id, channel_id, user_id, joined_at, notify_level)
+    SELECT $1, c.id, $3, $4, CASE WHEN c.channel_type = 'directmessage' THEN 'all' ELSE 'mentions' END
+    FROM channels c WHERE c.id = $2
+"#;
+
 pub struct ChannelRepository;
 
 impl ChannelRepository {
@@ -87,55 +138,200 @@ impl ChannelRepository {
 
         // Add creator as a member
         let member_id = Uuid::new_v4();
+        sqlx::query(INSERT_MEMBER)
+            .bind(&member_id)
+            .bind(&id)
+            .bind(created_by)
+            .bind(&now)
+            .execute(pool)
+            .await?;
+
+        Self::find_by_id(pool, &id).await
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: &Uuid) -> Result<Channel, sqlx::Error> {
+        let row: ChannelRow = sqlx::query_as(
+            r#"SELECT id, team_id, name, description, channel_type, created_by, is_archived, archived_at, is_announcement, slow_mode_seconds, burst_limit, history_visibility, created_at, updated_at FROM channels WHERE id = $1"#,
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    pub async fn find_by_team(
+        pool: &PgPool,
+        team_id: &Uuid,
+        include_archived: bool,
+    ) -> Result<Vec<Channel>, sqlx::Error> {
+        let rows: Vec<ChannelRow> = sqlx::query_as(
+            r#"SELECT id, team_id, name, description, channel_type, created_by, is_archived, archived_at, is_announcement, slow_mode_seconds, burst_limit, history_visibility, created_at, updated_at FROM channels WHERE (team_id = $1 OR id IN (SELECT channel_id FROM channel_teams WHERE team_id = $1 AND accepted_at IS NOT NULL)) AND ($2 OR is_archived = FALSE) ORDER BY name"#,
+        )
+        .bind(team_id)
+        .bind(include_archived)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Public, non-archived channels of a team, optionally filtered by name
+    pub async fn find_public_by_team(
+        pool: &PgPool,
+        team_id: &Uuid,
+        query: Option<&str>,
+    ) -> Result<Vec<Channel>, sqlx::Error> {
+        let search_pattern = format!("%{}%", escape_like(query.unwrap_or("")));
+
+        let rows: Vec<ChannelRow> = sqlx::query_as(
+            r#"
+            SELECT id, team_id, name, description, channel_type, created_by, is_archived, archived_at, is_announcement, slow_mode_seconds, burst_limit, history_visibility, created_at, updated_at
+            FROM channels
+            WHERE team_id = $1
+            AND channel_type = 'public'
+            AND is_archived = FALSE
+            AND (name ILIKE $2 OR description ILIKE $2)
+            ORDER BY name
+            "#,
+        )
+        .bind(team_id)
+        .bind(&search_pattern)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    // Shared channel operations
+    pub async fn add_team(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        team_id: &Uuid,
+        invited_by: &Uuid,
+    ) -> Result<ChannelTeam, sqlx::Error> {
+        let now = Utc::now();
+
         sqlx::query(
             r#"
-            INSERT INTO channel_members (id, channel_id, user_id, joined_at)
+            INSERT INTO channel_teams (channel_id, team_id, invited_by, invited_at)
             VALUES ($1, $2, $3, $4)
             "#,
         )
-        .bind(&member_id)
-        .bind(&id)
-        .bind(created_by)
+        .bind(channel_id)
+        .bind(team_id)
+        .bind(invited_by)
         .bind(&now)
         .execute(pool)
         .await?;
 
-        Self::find_by_id(pool, &id).await
+        Self::find_team(pool, channel_id, team_id).await
     }
 
-    pub async fn find_by_id(pool: &PgPool, id: &Uuid) -> Result<Channel, sqlx::Error> {
-        let row: ChannelRow = sqlx::query_as(
-            r#"SELECT id, team_id, name, description, channel_type, created_by, created_at, updated_at FROM channels WHERE id = $1"#,
+    pub async fn find_team(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        team_id: &Uuid,
+    ) -> Result<ChannelTeam, sqlx::Error> {
+        let row: ChannelTeamRow = sqlx::query_as(
+            r#"SELECT channel_id, team_id, invited_by, invited_at, accepted_at FROM channel_teams WHERE channel_id = $1 AND team_id = $2"#,
         )
-        .bind(id)
+        .bind(channel_id)
+        .bind(team_id)
         .fetch_one(pool)
         .await?;
 
         Ok(row.into())
     }
 
-    pub async fn find_by_team(pool: &PgPool, team_id: &Uuid) -> Result<Vec<Channel>, sqlx::Error> {
-        let rows: Vec<ChannelRow> = sqlx::query_as(
-            r#"SELECT id, team_id, name, description, channel_type, created_by, created_at, updated_at FROM channels WHERE team_id = $1 ORDER BY name"#,
+    pub async fn find_teams(pool: &PgPool, channel_id: &Uuid) -> Result<Vec<ChannelTeam>, sqlx::Error> {
+        let rows: Vec<ChannelTeamRow> = sqlx::query_as(
+            r#"SELECT channel_id, team_id, invited_by, invited_at, accepted_at FROM channel_teams WHERE channel_id = $1 ORDER BY invited_at"#,
         )
-        .bind(team_id)
+        .bind(channel_id)
         .fetch_all(pool)
         .await?;
 
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
-    pub async fn find_by_user(pool: &PgPool, user_id: &Uuid) -> Result<Vec<Channel>, sqlx::Error> {
+    pub async fn accept_team(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        team_id: &Uuid,
+    ) -> Result<ChannelTeam, sqlx::Error> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"UPDATE channel_teams SET accepted_at = $1 WHERE channel_id = $2 AND team_id = $3"#,
+        )
+        .bind(&now)
+        .bind(channel_id)
+        .bind(team_id)
+        .execute(pool)
+        .await?;
+
+        Self::find_team(pool, channel_id, team_id).await
+    }
+
+    /// Detach a team and drop its members who have no other way into the channel
+    pub async fn remove_team(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        team_id: &Uuid,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(r#"DELETE FROM channel_teams WHERE channel_id = $1 AND team_id = $2"#)
+            .bind(channel_id)
+            .bind(team_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM channel_members
+            WHERE channel_id = $1
+            AND user_id IN (SELECT user_id FROM team_members WHERE team_id = $2)
+            AND user_id NOT IN (
+                SELECT tm.user_id FROM team_members tm
+                INNER JOIN channels c ON c.team_id = tm.team_id
+                WHERE c.id = $1
+            )
+            AND user_id NOT IN (
+                SELECT tm.user_id FROM team_members tm
+                INNER JOIN channel_teams ct ON ct.team_id = tm.team_id
+                WHERE ct.channel_id = $1 AND ct.accepted_at IS NOT NULL
+            )
+            "#,
+        )
+        .bind(channel_id)
+        .bind(team_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_user(
+        pool: &PgPool,
+        user_id: &Uuid,
+        include_archived: bool,
+    ) -> Result<Vec<Channel>, sqlx::Error> {
         let rows: Vec<ChannelRow> = sqlx::query_as(
             r#"
-            SELECT c.id, c.team_id, c.name, c.description, c.channel_type, c.created_by, c.created_at, c.updated_at 
+            SELECT c.id, c.team_id, c.name, c.description, c.channel_type, c.created_by, c.is_archived, c.archived_at, c.is_announcement, c.slow_mode_seconds, c.burst_limit, c.history_visibility, c.created_at, c.updated_at 
             FROM channels c
             INNER JOIN channel_members cm ON c.id = cm.channel_id
             WHERE cm.user_id = $1
+            AND ($2 OR c.is_archived = FALSE)
             ORDER BY c.name
             "#,
         )
         .bind(user_id)
+        .bind(include_archived)
         .fetch_all(pool)
         .await?;
 
@@ -149,7 +345,7 @@ impl ChannelRepository {
     ) -> Result<Option<Channel>, sqlx::Error> {
         let row: Option<ChannelRow> = sqlx::query_as(
             r#"
-            SELECT c.id, c.team_id, c.name, c.description, c.channel_type, c.created_by, c.created_at, c.updated_at 
+            SELECT c.id, c.team_id, c.name, c.description, c.channel_type, c.created_by, c.is_archived, c.archived_at, c.is_announcement, c.slow_mode_seconds, c.burst_limit, c.history_visibility, c.created_at, c.updated_at 
             FROM channels c
             INNER JOIN channel_members cm1 ON c.id = cm1.channel_id
             INNER JOIN channel_members cm2 ON c.id = cm2.channel_id
@@ -167,11 +363,11 @@ impl ChannelRepository {
     }
 
     pub async fn update(
-        pool: &PgPool,
+        executor: impl PgExecutor<'_>,
         id: &Uuid,
         name: Option<&str>,
         description: Option<&str>,
-    ) -> Result<Channel, sqlx::Error> {
+    ) -> Result<(), sqlx::Error> {
         let now = Utc::now();
         let mut set_clauses = vec!["updated_at = $1".to_string()];
         let mut param_index = 2;
@@ -202,11 +398,112 @@ impl ChannelRepository {
         }
         
         q = q.bind(id);
-        q.execute(pool).await?;
+        q.execute(executor).await?;
+
+        Ok(())
+    }
+
+    pub async fn set_archived(
+        pool: &PgPool,
+        id: &Uuid,
+        archived: bool,
+    ) -> Result<Channel, sqlx::Error> {
+        let now = Utc::now();
+        let archived_at = if archived { Some(now) } else { None };
+
+        sqlx::query(
+            r#"UPDATE channels SET is_archived = $1, archived_at = $2, updated_at = $3 WHERE id = $4"#,
+        )
+        .bind(archived)
+        .bind(&archived_at)
+        .bind(&now)
+        .bind(id)
+        .execute(pool)
+        .await?;
 
         Self::find_by_id(pool, id).await
     }
 
+    pub async fn set_announcement(
+        executor: impl PgExecutor<'_>,
+        id: &Uuid,
+        is_announcement: bool,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"UPDATE channels SET is_announcement = $1, updated_at = $2 WHERE id = $3"#,
+        )
+        .bind(is_announcement)
+        .bind(&now)
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_rate_limits(
+        executor: impl PgExecutor<'_>,
+        id: &Uuid,
+        slow_mode_seconds: i32,
+        burst_limit: i32,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"UPDATE channels SET slow_mode_seconds = $1, burst_limit = $2, updated_at = $3 WHERE id = $4"#,
+        )
+        .bind(slow_mode_seconds)
+        .bind(burst_limit)
+        .bind(&now)
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_channel_type(
+        executor: impl PgExecutor<'_>,
+        id: &Uuid,
+        channel_type: ChannelType,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let type_str = serde_json::to_string(&channel_type).unwrap().trim_matches('"').to_string();
+
+        sqlx::query(
+            r#"UPDATE channels SET channel_type = $1, updated_at = $2 WHERE id = $3"#,
+        )
+        .bind(&type_str)
+        .bind(&now)
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_history_visibility(
+        executor: impl PgExecutor<'_>,
+        id: &Uuid,
+        history_visibility: HistoryVisibility,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let visibility_str = serde_json::to_string(&history_visibility).unwrap().trim_matches('"').to_string();
+
+        sqlx::query(
+            r#"UPDATE channels SET history_visibility = $1, updated_at = $2 WHERE id = $3"#,
+        )
+        .bind(&visibility_str)
+        .bind(&now)
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn delete(pool: &PgPool, id: &Uuid) -> Result<(), sqlx::Error> {
         sqlx::query(r#"DELETE FROM channels WHERE id = $1"#)
             .bind(id)
@@ -235,29 +532,39 @@ impl ChannelRepository {
         let id = Uuid::new_v4();
         let now = Utc::now();
 
-        sqlx::query(
-            r#"
-            INSERT INTO channel_members (id, channel_id, user_id, joined_at)
-            VALUES ($1, $2, $3, $4)
-            "#,
-        )
-        .bind(&id)
-        .bind(channel_id)
-        .bind(user_id)
-        .bind(&now)
-        .execute(pool)
-        .await?;
+        sqlx::query(INSERT_MEMBER)
+            .bind(&id)
+            .bind(channel_id)
+            .bind(user_id)
+            .bind(&now)
+            .execute(pool)
+            .await?;
 
         Self::find_member(pool, channel_id, user_id).await
     }
 
+    /// Add the user unless they are already a member. Returns whether they were
+    /// added, so concurrent joins can't fail on the membership's unique constraint.
+    pub async fn join(pool: &PgPool, channel_id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let query = format!("{} ON CONFLICT (channel_id, user_id) DO NOTHING", INSERT_MEMBER);
+        let result = sqlx::query(&query)
+            .bind(Uuid::new_v4())
+            .bind(channel_id)
+            .bind(user_id)
+            .bind(Utc::now())
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn find_member(
         pool: &PgPool,
         channel_id: &Uuid,
         user_id: &Uuid,
     ) -> Result<ChannelMember, sqlx::Error> {
         let row: ChannelMemberRow = sqlx::query_as(
-            r#"SELECT id, channel_id, user_id, joined_at, last_read_at FROM channel_members WHERE channel_id = $1 AND user_id = $2"#,
+            r#"SELECT id, channel_id, user_id, joined_at, last_read_at, can_post, notify_level, muted_until, hidden FROM channel_members WHERE channel_id = $1 AND user_id = $2"#,
         )
         .bind(channel_id)
         .bind(user_id)
@@ -269,7 +576,7 @@ impl ChannelRepository {
 
     pub async fn find_members(pool: &PgPool, channel_id: &Uuid) -> Result<Vec<ChannelMember>, sqlx::Error> {
         let rows: Vec<ChannelMemberRow> = sqlx::query_as(
-            r#"SELECT id, channel_id, user_id, joined_at, last_read_at FROM channel_members WHERE channel_id = $1 ORDER BY joined_at"#,
+            r#"SELECT id, channel_id, user_id, joined_at, last_read_at, can_post, notify_level, muted_until, hidden FROM channel_members WHERE channel_id = $1 ORDER BY joined_at"#,
         )
         .bind(channel_id)
         .fetch_all(pool)
@@ -294,6 +601,79 @@ impl ChannelRepository {
         Ok(())
     }
 
+    pub async fn set_can_post(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+        can_post: bool,
+    ) -> Result<ChannelMember, sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE channel_members SET can_post = $1 WHERE channel_id = $2 AND user_id = $3"#,
+        )
+        .bind(can_post)
+        .bind(channel_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Self::find_member(pool, channel_id, user_id).await
+    }
+
+    pub async fn update_settings(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+        notify_level: NotificationLevel,
+        muted_until: Option<DateTime<Utc>>,
+        hidden: bool,
+    ) -> Result<ChannelMember, sqlx::Error> {
+        let level_str = serde_json::to_string(&notify_level).unwrap().trim_matches('"').to_string();
+
+        sqlx::query(
+            r#"UPDATE channel_members SET notify_level = $1, muted_until = $2, hidden = $3 WHERE channel_id = $4 AND user_id = $5"#,
+        )
+        .bind(&level_str)
+        .bind(muted_until)
+        .bind(hidden)
+        .bind(channel_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Self::find_member(pool, channel_id, user_id).await
+    }
+
+    /// For each of the channels the user is a member of, the earliest point in its
+    /// history they may see, or `None` if the channel's full history is visible
+    pub async fn find_member_visibility(
+        pool: &PgPool,
+        user_id: &Uuid,
+        channel_ids: &[Uuid],
+    ) -> Result<Vec<(Uuid, Option<DateTime<Utc>>)>, sqlx::Error> {
+        let rows: Vec<(Uuid, String, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT cm.channel_id, c.history_visibility, cm.joined_at
+            FROM channel_members cm
+            INNER JOIN channels c ON c.id = cm.channel_id
+            WHERE cm.user_id = $1 AND cm.channel_id = ANY($2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(channel_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(channel_id, history_visibility, joined_at)| {
+                let history_visibility: HistoryVisibility =
+                    serde_json::from_str(&format!("\"{}\"", history_visibility)).unwrap_or_default();
+                let visible_since = (history_visibility != HistoryVisibility::Full).then_some(joined_at);
+                (channel_id, visible_since)
+            })
+            .collect())
+    }
+
     pub async fn is_member(
         pool: &PgPool,
         channel_id: &Uuid,
@@ -314,7 +694,7 @@ impl ChannelRepository {
         pool: &PgPool,
         channel_id: &Uuid,
         user_id: &Uuid,
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<DateTime<Utc>, sqlx::Error> {
         let now = Utc::now();
 
         sqlx::query(
@@ -326,9 +706,100 @@ impl ChannelRepository {
         .execute(pool)
         .await?;
 
+        Ok(now)
+    }
+
+    /// Set the member's read pointer to an arbitrary point in time
+    pub async fn set_last_read_at(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+        last_read_at: &DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE channel_members SET last_read_at = $1 WHERE channel_id = $2 AND user_id = $3"#,
+        )
+        .bind(last_read_at)
+        .bind(channel_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
         Ok(())
     }
 
+    /// Unread messages from others that mention the user directly or with @channel, or
+    /// with @here while they were connected
+    pub async fn get_mention_count(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<i64, sqlx::Error> {
+        let result: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM messages m
+            INNER JOIN channel_members cm ON m.channel_id = cm.channel_id
+            INNER JOIN channels c ON c.id = m.channel_id
+            WHERE m.channel_id = $1
+            AND cm.user_id = $2
+            AND m.sender_id <> $2
+            AND cm.notify_level <> 'nothing'
+            AND (cm.muted_until IS NULL OR cm.muted_until <= NOW())
+            AND (cm.last_read_at IS NULL OR m.created_at > cm.last_read_at)
+            AND (c.history_visibility = 'full' OR m.created_at >= cm.joined_at)
+            AND m.deleted_at IS NULL
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND EXISTS (
+                SELECT 1 FROM message_mentions mm
+                WHERE mm.message_id = m.id
+                AND (
+                    mm.user_id = $2
+                    OR mm.mention_type = 'channel'
+                    -- @here only reached the members who were connected, and so were notified
+                    OR (mm.mention_type = 'here' AND EXISTS (
+                        SELECT 1 FROM notifications n
+                        WHERE n.user_id = $2 AND n.reference_id = m.id AND n.notification_type = 'mention'
+                    ))
+                )
+            )
+            "#,
+        )
+        .bind(channel_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(result.0)
+    }
+
+    pub async fn get_first_unread_message_id(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let result: Option<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT m.id FROM messages m
+            INNER JOIN channel_members cm ON m.channel_id = cm.channel_id
+            INNER JOIN channels c ON c.id = m.channel_id
+            WHERE m.channel_id = $1
+            AND cm.user_id = $2
+            AND (cm.last_read_at IS NULL OR m.created_at > cm.last_read_at)
+            AND (c.history_visibility = 'full' OR m.created_at >= cm.joined_at)
+            AND m.deleted_at IS NULL
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            ORDER BY m.created_at, m.id
+            LIMIT 1
+            "#,
+        )
+        .bind(channel_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(result.map(|r| r.0))
+    }
+
     pub async fn get_unread_count(
         pool: &PgPool,
         channel_id: &Uuid,
@@ -338,9 +809,15 @@ impl ChannelRepository {
             r#"
             SELECT COUNT(*) FROM messages m
             INNER JOIN channel_members cm ON m.channel_id = cm.channel_id
+            INNER JOIN channels c ON c.id = m.channel_id
             WHERE m.channel_id = $1
             AND cm.user_id = $2
+            AND cm.notify_level <> 'nothing'
+            AND (cm.muted_until IS NULL OR cm.muted_until <= NOW())
             AND (cm.last_read_at IS NULL OR m.created_at > cm.last_read_at)
+            AND (c.history_visibility = 'full' OR m.created_at >= cm.joined_at)
+            AND m.deleted_at IS NULL
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
             "#,
         )
         .bind(channel_id)
@@ -351,3 +828,8 @@ impl ChannelRepository {
         Ok(result.0)
     }
 }
+
+/// Escape the LIKE wildcards in user input, so it only matches literally
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
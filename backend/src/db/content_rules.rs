@@ -0,0 +1,154 @@
+//! Content rule database operations
+
+use chrono::{DateTime, Utc};
+use shared::models::{ContentRule, ContentRuleAction, ContentRuleKind};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, FromRow)]
+pub struct ContentRuleRow {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub name: String,
+    pub kind: String,
+    pub pattern: Option<String>,
+    pub words: Vec<String>,
+    pub action: String,
+    pub enabled: bool,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<ContentRuleRow> for ContentRule {
+    fn from(row: ContentRuleRow) -> Self {
+        ContentRule {
+            id: row.id,
+            team_id: row.team_id,
+            name: row.name,
+            kind: serde_json::from_str(&format!("\"{}\"", row.kind)).unwrap_or_default(),
+            pattern: row.pattern,
+            words: row.words,
+            action: serde_json::from_str(&format!("\"{}\"", row.action)).unwrap_or_default(),
+            enabled: row.enabled,
+            created_by: row.created_by,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+pub struct ContentRuleRepository;
+
+impl ContentRuleRepository {
+    pub async fn create(
+        pool: &PgPool,
+        team_id: &Uuid,
+        name: &str,
+        kind: ContentRuleKind,
+        pattern: Option<&str>,
+        words: &[String],
+        action: ContentRuleAction,
+        enabled: bool,
+        created_by: &Uuid,
+    ) -> Result<ContentRule, sqlx::Error> {
+        let now = Utc::now();
+        let kind_str = serde_json::to_string(&kind).unwrap().trim_matches('"').to_string();
+        let action_str = serde_json::to_string(&action).unwrap().trim_matches('"').to_string();
+
+        let row: ContentRuleRow = sqlx::query_as(
+            r#"
+            INSERT INTO content_rules (id, team_id, name, kind, pattern, words, action, enabled, created_by, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10)
+            RETURNING id, team_id, name, kind, pattern, words, action, enabled, created_by, created_at, updated_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(team_id)
+        .bind(name)
+        .bind(&kind_str)
+        .bind(pattern)
+        .bind(words)
+        .bind(&action_str)
+        .bind(enabled)
+        .bind(created_by)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: &Uuid) -> Result<ContentRule, sqlx::Error> {
+        let row: ContentRuleRow = sqlx::query_as(
+            r#"SELECT id, team_id, name, kind, pattern, words, action, enabled, created_by, created_at, updated_at FROM content_rules WHERE id = $1"#,
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    /// The team's rules, oldest first
+    pub async fn find_by_team(pool: &PgPool, team_id: &Uuid) -> Result<Vec<ContentRule>, sqlx::Error> {
+        let rows: Vec<ContentRuleRow> = sqlx::query_as(
+            r#"
+            SELECT id, team_id, name, kind, pattern, words, action, enabled, created_by, created_at, updated_at
+            FROM content_rules WHERE team_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(team_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    pub async fn update(
+        pool: &PgPool,
+        id: &Uuid,
+        name: Option<&str>,
+        pattern: Option<&str>,
+        words: Option<&[String]>,
+        action: Option<ContentRuleAction>,
+        enabled: Option<bool>,
+    ) -> Result<ContentRule, sqlx::Error> {
+        let action_str = action.map(|a| serde_json::to_string(&a).unwrap().trim_matches('"').to_string());
+
+        let row: ContentRuleRow = sqlx::query_as(
+            r#"
+            UPDATE content_rules SET
+                name = COALESCE($1, name),
+                pattern = COALESCE($2, pattern),
+                words = COALESCE($3, words),
+                action = COALESCE($4, action),
+                enabled = COALESCE($5, enabled),
+                updated_at = $6
+            WHERE id = $7
+            RETURNING id, team_id, name, kind, pattern, words, action, enabled, created_by, created_at, updated_at
+            "#,
+        )
+        .bind(name)
+        .bind(pattern)
+        .bind(words)
+        .bind(action_str)
+        .bind(enabled)
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    pub async fn delete(pool: &PgPool, id: &Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"DELETE FROM content_rules WHERE id = $1"#)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! Draft database operations
+
+use chrono::{DateTime, Utc};
+use shared::models::Draft;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, FromRow)]
+pub struct DraftRow {
+    pub user_id: Uuid,
+    pub channel_id: Uuid,
+    pub thread_root_id: Option<Uuid>,
+    pub content: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<DraftRow> for Draft {
+    fn from(row: DraftRow) -> Self {
+        Draft {
+            user_id: row.user_id,
+            channel_id: row.channel_id,
+            thread_root_id: row.thread_root_id,
+            content: row.content,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+pub struct DraftRepository;
+
+impl DraftRepository {
+    pub async fn upsert(
+        pool: &PgPool,
+        user_id: &Uuid,
+        channel_id: &Uuid,
+        thread_root_id: Option<&Uuid>,
+        content: &str,
+    ) -> Result<Draft, sqlx::Error> {
+        let row: DraftRow = sqlx::query_as(
+            r#"
+            INSERT INTO drafts (user_id, channel_id, thread_root_id, content, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id, channel_id, COALESCE(thread_root_id, '00000000-0000-0000-0000-000000000000'::uuid))
+            DO UPDATE SET content = EXCLUDED.content, updated_at = EXCLUDED.updated_at
+            RETURNING user_id, channel_id, thread_root_id, content, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(channel_id)
+        .bind(thread_root_id)
+        .bind(content)
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    /// Returns whether there was a draft to delete
+    pub async fn delete(
+        pool: &PgPool,
+        user_id: &Uuid,
+        channel_id: &Uuid,
+        thread_root_id: Option<&Uuid>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM drafts
+            WHERE user_id = $1 AND channel_id = $2 AND thread_root_id IS NOT DISTINCT FROM $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(channel_id)
+        .bind(thread_root_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// All of a user's drafts in channels they still belong to, most recent first
+    pub async fn find_by_user(pool: &PgPool, user_id: &Uuid) -> Result<Vec<Draft>, sqlx::Error> {
+        let rows: Vec<DraftRow> = sqlx::query_as(
+            r#"
+            SELECT d.user_id, d.channel_id, d.thread_root_id, d.content, d.updated_at
+            FROM drafts d
+            INNER JOIN channel_members cm ON cm.channel_id = d.channel_id AND cm.user_id = d.user_id
+            WHERE d.user_id = $1
+            ORDER BY d.updated_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+}
@@ -0,0 +1,144 @@
+//! Custom emoji database operations
+
+use chrono::{DateTime, Utc};
+use shared::models::CustomEmoji;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, FromRow)]
+pub struct CustomEmojiRow {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub name: String,
+    pub file_id: Uuid,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<CustomEmojiRow> for CustomEmoji {
+    fn from(row: CustomEmojiRow) -> Self {
+        CustomEmoji {
+            id: row.id,
+            team_id: row.team_id,
+            name: row.name,
+            file_id: row.file_id,
+            created_by: row.created_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+pub struct CustomEmojiRepository;
+
+impl CustomEmojiRepository {
+    pub async fn create(
+        pool: &PgPool,
+        team_id: &Uuid,
+        name: &str,
+        file_id: &Uuid,
+        created_by: &Uuid,
+    ) -> Result<CustomEmoji, sqlx::Error> {
+        let row: CustomEmojiRow = sqlx::query_as(
+            r#"
+            INSERT INTO custom_emoji (id, team_id, name, file_id, created_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, team_id, name, file_id, created_by, created_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(team_id)
+        .bind(name)
+        .bind(file_id)
+        .bind(created_by)
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: &Uuid) -> Result<CustomEmoji, sqlx::Error> {
+        let row: CustomEmojiRow = sqlx::query_as(
+            r#"SELECT id, team_id, name, file_id, created_by, created_at FROM custom_emoji WHERE id = $1"#,
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    pub async fn find_by_name(
+        pool: &PgPool,
+        team_id: &Uuid,
+        name: &str,
+    ) -> Result<Option<CustomEmoji>, sqlx::Error> {
+        let row: Option<CustomEmojiRow> = sqlx::query_as(
+            r#"SELECT id, team_id, name, file_id, created_by, created_at FROM custom_emoji WHERE team_id = $1 AND name = $2"#,
+        )
+        .bind(team_id)
+        .bind(name)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|r| r.into()))
+    }
+
+    pub async fn find_by_team(pool: &PgPool, team_id: &Uuid) -> Result<Vec<CustomEmoji>, sqlx::Error> {
+        let rows: Vec<CustomEmojiRow> = sqlx::query_as(
+            r#"SELECT id, team_id, name, file_id, created_by, created_at FROM custom_emoji WHERE team_id = $1 ORDER BY name"#,
+        )
+        .bind(team_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    pub async fn rename(pool: &PgPool, id: &Uuid, name: &str) -> Result<CustomEmoji, sqlx::Error> {
+        let row: CustomEmojiRow = sqlx::query_as(
+            r#"
+            UPDATE custom_emoji SET name = $1 WHERE id = $2
+            RETURNING id, team_id, name, file_id, created_by, created_at
+            "#,
+        )
+        .bind(name)
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    pub async fn delete(pool: &PgPool, id: &Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"DELETE FROM custom_emoji WHERE id = $1"#)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Whether the team hosting the channel has a custom emoji with this name
+    pub async fn exists_for_channel(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        name: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result: (bool,) = sqlx::query_as(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM custom_emoji ce
+                INNER JOIN channels c ON c.team_id = ce.team_id
+                WHERE c.id = $1 AND ce.name = $2
+            )
+            "#,
+        )
+        .bind(channel_id)
+        .bind(name)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(result.0)
+    }
+}
@@ -2,7 +2,8 @@
 
 use chrono::{DateTime, Utc};
 use shared::models::FileAttachment;
-use sqlx::{FromRow, PgPool};
+use shared::pagination::Cursor;
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 #[derive(Debug, FromRow)]
@@ -91,18 +92,41 @@ impl FileRepository {
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
+    pub async fn find_by_messages(
+        pool: &PgPool,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<FileAttachment>, sqlx::Error> {
+        let rows: Vec<FileAttachmentRow> = sqlx::query_as(
+            r#"SELECT id, message_id, channel_id, uploader_id, filename, file_size, mime_type, storage_path, created_at FROM file_attachments WHERE message_id = ANY($1) ORDER BY created_at"#,
+        )
+        .bind(message_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Up to `limit` files of the channel, newest first, uploaded before the cursor
     pub async fn find_by_channel(
         pool: &PgPool,
         channel_id: &Uuid,
         limit: i64,
-        offset: i64,
+        before: Option<Cursor>,
     ) -> Result<Vec<FileAttachment>, sqlx::Error> {
         let rows: Vec<FileAttachmentRow> = sqlx::query_as(
-            r#"SELECT id, message_id, channel_id, uploader_id, filename, file_size, mime_type, storage_path, created_at FROM file_attachments WHERE channel_id = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3"#,
+            r#"
+            SELECT id, message_id, channel_id, uploader_id, filename, file_size, mime_type, storage_path, created_at
+            FROM file_attachments
+            WHERE channel_id = $1
+            AND ($2::timestamptz IS NULL OR (created_at, id) < ($2, $3))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4
+            "#,
         )
         .bind(channel_id)
+        .bind(before.map(|c| c.created_at))
+        .bind(before.map(|c| c.id))
         .bind(limit)
-        .bind(offset)
         .fetch_all(pool)
         .await?;
 
@@ -125,6 +149,47 @@ impl FileRepository {
         Ok(())
     }
 
+    /// Attach an existing file to another message, sharing its stored content
+    pub async fn link_to_message(
+        tx: &mut Transaction<'_, Postgres>,
+        file: &FileAttachment,
+        message_id: &Uuid,
+        channel_id: &Uuid,
+        uploader_id: &Uuid,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO file_attachments (id, message_id, channel_id, uploader_id, filename, file_size, mime_type, storage_path, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(message_id)
+        .bind(channel_id)
+        .bind(uploader_id)
+        .bind(&file.filename)
+        .bind(file.file_size)
+        .bind(&file.mime_type)
+        .bind(&file.storage_path)
+        .bind(Utc::now())
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Number of attachments backed by the same stored file
+    pub async fn count_by_storage_path(pool: &PgPool, storage_path: &str) -> Result<i64, sqlx::Error> {
+        let result: (i64,) = sqlx::query_as(
+            r#"SELECT COUNT(*) FROM file_attachments WHERE storage_path = $1"#,
+        )
+        .bind(storage_path)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(result.0)
+    }
+
     pub async fn delete(pool: &PgPool, id: &Uuid) -> Result<FileAttachment, sqlx::Error> {
         let file = Self::find_by_id(pool, id).await?;
 
@@ -0,0 +1,84 @@
+//! Message forward database operations
+
+use chrono::{DateTime, Utc};
+use shared::models::{MessageForward, MessageFormat};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(Debug, FromRow)]
+pub struct MessageForwardRow {
+    pub message_id: Uuid,
+    pub source_message_id: Option<Uuid>,
+    pub source_channel_id: Option<Uuid>,
+    pub source_sender_id: Option<Uuid>,
+    pub content: String,
+    pub format: String,
+    pub source_created_at: DateTime<Utc>,
+}
+
+impl From<MessageForwardRow> for MessageForward {
+    fn from(row: MessageForwardRow) -> Self {
+        MessageForward {
+            message_id: row.message_id,
+            source_message_id: row.source_message_id,
+            source_channel_id: row.source_channel_id,
+            source_sender_id: row.source_sender_id,
+            content: row.content,
+            format: serde_json::from_str(&format!("\"{}\"", row.format)).unwrap_or_default(),
+            source_created_at: row.source_created_at,
+        }
+    }
+}
+
+pub struct ForwardRepository;
+
+impl ForwardRepository {
+    /// Store the snapshot of a forwarded original in the transaction that creates the forward
+    pub async fn create(
+        tx: &mut Transaction<'_, Postgres>,
+        message_id: &Uuid,
+        source_message_id: &Uuid,
+        source_channel_id: &Uuid,
+        source_sender_id: &Uuid,
+        content: &str,
+        format: MessageFormat,
+        source_created_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let format_str = serde_json::to_string(&format).unwrap().trim_matches('"').to_string();
+
+        sqlx::query(
+            r#"
+            INSERT INTO message_forwards (message_id, source_message_id, source_channel_id, source_sender_id, content, format, source_created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(message_id)
+        .bind(source_message_id)
+        .bind(source_channel_id)
+        .bind(source_sender_id)
+        .bind(content)
+        .bind(&format_str)
+        .bind(&source_created_at)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_messages(
+        pool: &PgPool,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<MessageForward>, sqlx::Error> {
+        let rows: Vec<MessageForwardRow> = sqlx::query_as(
+            r#"
+            SELECT message_id, source_message_id, source_channel_id, source_sender_id, content, format, source_created_at
+            FROM message_forwards WHERE message_id = ANY($1)
+            "#,
+        )
+        .bind(message_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+}
@@ -0,0 +1,152 @@
+//! Link preview database operations
+
+use chrono::{DateTime, Utc};
+use shared::models::LinkPreview;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, FromRow)]
+pub struct LinkPreviewRow {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl From<LinkPreviewRow> for LinkPreview {
+    fn from(row: LinkPreviewRow) -> Self {
+        LinkPreview {
+            url: row.url,
+            title: row.title,
+            description: row.description,
+            image_url: row.image_url,
+            site_name: row.site_name,
+            fetched_at: row.fetched_at,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct MessageLinkPreviewRow {
+    pub message_id: Uuid,
+    #[sqlx(flatten)]
+    pub preview: LinkPreviewRow,
+}
+
+pub struct LinkPreviewRepository;
+
+impl LinkPreviewRepository {
+    pub async fn find_by_url(pool: &PgPool, url: &str) -> Result<Option<LinkPreview>, sqlx::Error> {
+        let row: Option<LinkPreviewRow> = sqlx::query_as(
+            r#"
+            SELECT url, title, description, image_url, site_name, fetched_at
+            FROM link_previews WHERE url = $1
+            "#,
+        )
+        .bind(url)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|r| r.into()))
+    }
+
+    pub async fn upsert(pool: &PgPool, preview: &LinkPreview) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO link_previews (url, title, description, image_url, site_name, fetched_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (url) DO UPDATE SET
+                title = EXCLUDED.title,
+                description = EXCLUDED.description,
+                image_url = EXCLUDED.image_url,
+                site_name = EXCLUDED.site_name,
+                fetched_at = EXCLUDED.fetched_at
+            "#,
+        )
+        .bind(&preview.url)
+        .bind(&preview.title)
+        .bind(&preview.description)
+        .bind(&preview.image_url)
+        .bind(&preview.site_name)
+        .bind(&preview.fetched_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Replace the previews attached to a message with the given cached URLs, in order
+    pub async fn replace_for_message(
+        pool: &PgPool,
+        message_id: &Uuid,
+        urls: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(r#"DELETE FROM message_link_previews WHERE message_id = $1"#)
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for (position, url) in urls.iter().enumerate() {
+            sqlx::query(
+                r#"
+                INSERT INTO message_link_previews (message_id, url, position)
+                VALUES ($1, $2, $3)
+                "#,
+            )
+            .bind(message_id)
+            .bind(url)
+            .bind(position as i32)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn find_by_message(
+        pool: &PgPool,
+        message_id: &Uuid,
+    ) -> Result<Vec<LinkPreview>, sqlx::Error> {
+        let rows: Vec<LinkPreviewRow> = sqlx::query_as(
+            r#"
+            SELECT lp.url, lp.title, lp.description, lp.image_url, lp.site_name, lp.fetched_at
+            FROM message_link_previews mlp
+            INNER JOIN link_previews lp ON lp.url = mlp.url
+            WHERE mlp.message_id = $1
+            ORDER BY mlp.position
+            "#,
+        )
+        .bind(message_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Previews of several messages, paired with the message they belong to
+    pub async fn find_by_messages(
+        pool: &PgPool,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<(Uuid, LinkPreview)>, sqlx::Error> {
+        let rows: Vec<MessageLinkPreviewRow> = sqlx::query_as(
+            r#"
+            SELECT mlp.message_id, lp.url, lp.title, lp.description, lp.image_url, lp.site_name, lp.fetched_at
+            FROM message_link_previews mlp
+            INNER JOIN link_previews lp ON lp.url = mlp.url
+            WHERE mlp.message_id = ANY($1)
+            ORDER BY mlp.position
+            "#,
+        )
+        .bind(message_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| (r.message_id, r.preview.into())).collect())
+    }
+}
@@ -1,10 +1,16 @@
 //! Message database operations
 
 use chrono::{DateTime, Utc};
-use shared::models::{Message, MessageType, Reaction};
-use sqlx::{FromRow, PgPool};
+use shared::models::{
+    MentionType, Message, MessageFormat, MessageMention, MessageRevision, MessageType,
+    ModerationAction, Reaction,
+};
+use shared::pagination::Cursor;
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::db::ModerationRepository;
+
 #[derive(Debug, FromRow)]
 pub struct MessageRow {
     pub id: Uuid,
@@ -12,10 +18,15 @@ pub struct MessageRow {
     pub sender_id: Uuid,
     pub content: String,
     pub message_type: String,
+    pub format: String,
     pub reply_to_id: Option<Uuid>,
     pub edited: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<Uuid>,
+    pub moderated_by: Option<Uuid>,
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 impl From<MessageRow> for Message {
@@ -26,10 +37,38 @@ impl From<MessageRow> for Message {
             sender_id: row.sender_id,
             content: row.content,
             message_type: serde_json::from_str(&format!("\"{}\"", row.message_type)).unwrap_or_default(),
+            format: serde_json::from_str(&format!("\"{}\"", row.format)).unwrap_or_default(),
             reply_to_id: row.reply_to_id,
             edited: row.edited,
             created_at: row.created_at,
             updated_at: row.updated_at,
+            deleted_at: row.deleted_at,
+            deleted_by: row.deleted_by,
+            moderated_by: row.moderated_by,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct MessageRevisionRow {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub content: String,
+    pub replaced_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub replaced_at: DateTime<Utc>,
+}
+
+impl From<MessageRevisionRow> for MessageRevision {
+    fn from(row: MessageRevisionRow) -> Self {
+        MessageRevision {
+            id: row.id,
+            message_id: row.message_id,
+            content: row.content,
+            replaced_by: row.replaced_by,
+            created_at: row.created_at,
+            replaced_at: row.replaced_at,
         }
     }
 }
@@ -55,25 +94,52 @@ impl From<ReactionRow> for Reaction {
     }
 }
 
+#[derive(Debug, FromRow)]
+pub struct MessageMentionRow {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub mention_type: String,
+    pub user_id: Option<Uuid>,
+    pub start_offset: i32,
+    pub end_offset: i32,
+}
+
+impl From<MessageMentionRow> for MessageMention {
+    fn from(row: MessageMentionRow) -> Self {
+        MessageMention {
+            id: row.id,
+            message_id: row.message_id,
+            mention_type: serde_json::from_str(&format!("\"{}\"", row.mention_type)).unwrap_or_default(),
+            user_id: row.user_id,
+            start_offset: row.start_offset,
+            end_offset: row.end_offset,
+        }
+    }
+}
+
 pub struct MessageRepository;
 
 impl MessageRepository {
+    /// Insert a message as part of the caller's transaction
     pub async fn create(
-        pool: &PgPool,
+        tx: &mut Transaction<'_, Postgres>,
         channel_id: &Uuid,
         sender_id: &Uuid,
         content: &str,
         message_type: MessageType,
+        format: MessageFormat,
         reply_to_id: Option<&Uuid>,
+        expires_at: Option<DateTime<Utc>>,
     ) -> Result<Message, sqlx::Error> {
         let id = Uuid::new_v4();
         let now = Utc::now();
         let type_str = serde_json::to_string(&message_type).unwrap().trim_matches('"').to_string();
+        let format_str = serde_json::to_string(&format).unwrap().trim_matches('"').to_string();
 
         sqlx::query(
             r#"
-            INSERT INTO messages (id, channel_id, sender_id, content, message_type, reply_to_id, edited, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            INSERT INTO messages (id, channel_id, sender_id, content, message_type, format, reply_to_id, edited, created_at, updated_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             "#,
         )
         .bind(&id)
@@ -81,19 +147,28 @@ impl MessageRepository {
         .bind(sender_id)
         .bind(content)
         .bind(&type_str)
+        .bind(&format_str)
         .bind(reply_to_id)
         .bind(false)
         .bind(&now)
         .bind(&now)
-        .execute(pool)
+        .bind(expires_at)
+        .execute(&mut **tx)
         .await?;
 
-        Self::find_by_id(pool, &id).await
+        let row: MessageRow = sqlx::query_as(
+            r#"SELECT id, channel_id, sender_id, content, message_type, format, reply_to_id, edited, created_at, updated_at, deleted_at, deleted_by, moderated_by, expires_at FROM messages WHERE id = $1"#,
+        )
+        .bind(&id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(row.into())
     }
 
     pub async fn find_by_id(pool: &PgPool, id: &Uuid) -> Result<Message, sqlx::Error> {
         let row: MessageRow = sqlx::query_as(
-            r#"SELECT id, channel_id, sender_id, content, message_type, reply_to_id, edited, created_at, updated_at FROM messages WHERE id = $1"#,
+            r#"SELECT id, channel_id, sender_id, content, message_type, format, reply_to_id, edited, created_at, updated_at, deleted_at, deleted_by, moderated_by, expires_at FROM messages WHERE id = $1"#,
         )
         .bind(id)
         .fetch_one(pool)
@@ -102,73 +177,64 @@ impl MessageRepository {
         Ok(row.into())
     }
 
+    pub async fn find_by_ids(pool: &PgPool, ids: &[Uuid]) -> Result<Vec<Message>, sqlx::Error> {
+        let rows: Vec<MessageRow> = sqlx::query_as(
+            r#"SELECT id, channel_id, sender_id, content, message_type, format, reply_to_id, edited, created_at, updated_at, deleted_at, deleted_by, moderated_by, expires_at FROM messages WHERE id = ANY($1)"#,
+        )
+        .bind(ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Up to `limit` messages of the channel, newest first, sent before the cursor
+    /// and no earlier than `visible_since`
     pub async fn find_by_channel(
         pool: &PgPool,
         channel_id: &Uuid,
         limit: i64,
-        before: Option<DateTime<Utc>>,
-        after: Option<DateTime<Utc>>,
+        before: Option<Cursor>,
+        visible_since: Option<DateTime<Utc>>,
     ) -> Result<Vec<Message>, sqlx::Error> {
-        let rows: Vec<MessageRow> = match (before, after) {
-            (Some(b), None) => {
-                sqlx::query_as(
-                    r#"SELECT id, channel_id, sender_id, content, message_type, reply_to_id, edited, created_at, updated_at 
-                    FROM messages WHERE channel_id = $1 AND created_at < $2 
-                    ORDER BY created_at DESC LIMIT $3"#,
-                )
-                .bind(channel_id)
-                .bind(b)
-                .bind(limit)
-                .fetch_all(pool)
-                .await?
-            }
-            (None, Some(a)) => {
-                sqlx::query_as(
-                    r#"SELECT id, channel_id, sender_id, content, message_type, reply_to_id, edited, created_at, updated_at 
-                    FROM messages WHERE channel_id = $1 AND created_at > $2 
-                    ORDER BY created_at DESC LIMIT $3"#,
-                )
-                .bind(channel_id)
-                .bind(a)
-                .bind(limit)
-                .fetch_all(pool)
-                .await?
-            }
-            (Some(b), Some(a)) => {
-                sqlx::query_as(
-                    r#"SELECT id, channel_id, sender_id, content, message_type, reply_to_id, edited, created_at, updated_at 
-                    FROM messages WHERE channel_id = $1 AND created_at < $2 AND created_at > $3 
-                    ORDER BY created_at DESC LIMIT $4"#,
-                )
-                .bind(channel_id)
-                .bind(b)
-                .bind(a)
-                .bind(limit)
-                .fetch_all(pool)
-                .await?
-            }
-            (None, None) => {
-                sqlx::query_as(
-                    r#"SELECT id, channel_id, sender_id, content, message_type, reply_to_id, edited, created_at, updated_at 
-                    FROM messages WHERE channel_id = $1 
-                    ORDER BY created_at DESC LIMIT $2"#,
-                )
-                .bind(channel_id)
-                .bind(limit)
-                .fetch_all(pool)
-                .await?
-            }
-        };
+        let rows: Vec<MessageRow> = sqlx::query_as(
+            r#"
+            SELECT id, channel_id, sender_id, content, message_type, format, reply_to_id, edited, created_at, updated_at, deleted_at, deleted_by, moderated_by, expires_at
+            FROM messages
+            WHERE channel_id = $1
+            AND ($2::timestamptz IS NULL OR (created_at, id) < ($2, $3))
+            AND ($4::timestamptz IS NULL OR created_at >= $4)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $5
+            "#,
+        )
+        .bind(channel_id)
+        .bind(before.map(|c| c.created_at))
+        .bind(before.map(|c| c.id))
+        .bind(visible_since)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
 
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
+    /// Replace the content, keeping the previous version as a revision.
+    /// A moderation reason records the edit in the moderation log.
     pub async fn update(
         pool: &PgPool,
         id: &Uuid,
         content: &str,
+        edited_by: &Uuid,
+        moderation_reason: Option<&str>,
     ) -> Result<Message, sqlx::Error> {
         let now = Utc::now();
+        let mut tx = pool.begin().await?;
+
+        Self::insert_revision(&mut tx, id, edited_by, now).await?;
+        if let Some(reason) = moderation_reason {
+            ModerationRepository::record(&mut tx, id, edited_by, ModerationAction::Edit, reason, now).await?;
+        }
 
         sqlx::query(
             r#"UPDATE messages SET content = $1, edited = $2, updated_at = $3 WHERE id = $4"#,
@@ -177,53 +243,237 @@ impl MessageRepository {
         .bind(true)
         .bind(&now)
         .bind(id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Self::find_by_id(pool, id).await
     }
 
-    pub async fn delete(pool: &PgPool, id: &Uuid) -> Result<(), sqlx::Error> {
-        sqlx::query(r#"DELETE FROM messages WHERE id = $1"#)
+    /// Turn the message into a tombstone. The deleted content is kept as a revision
+    /// for auditing, and the row stays so replies keep their context.
+    /// A moderation reason records the removal in the moderation log.
+    pub async fn soft_delete(
+        pool: &PgPool,
+        id: &Uuid,
+        deleted_by: &Uuid,
+        moderation_reason: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let mut tx = pool.begin().await?;
+
+        Self::insert_revision(&mut tx, id, deleted_by, now).await?;
+        if let Some(reason) = moderation_reason {
+            ModerationRepository::record(&mut tx, id, deleted_by, ModerationAction::Delete, reason, now).await?;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE messages SET content = '', deleted_at = $1, deleted_by = $2, updated_at = $1
+            WHERE id = $3
+            "#,
+        )
+        .bind(&now)
+        .bind(deleted_by)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(r#"DELETE FROM message_mentions WHERE message_id = $1"#)
             .bind(id)
-            .execute(pool)
+            .execute(&mut *tx)
             .await?;
 
+        sqlx::query(r#"DELETE FROM message_link_previews WHERE message_id = $1"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 
+    async fn insert_revision(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        message_id: &Uuid,
+        replaced_by: &Uuid,
+        replaced_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO message_revisions (id, message_id, content, replaced_by, created_at, replaced_at)
+            SELECT $1, id, content, $2, updated_at, $3 FROM messages WHERE id = $4
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(replaced_by)
+        .bind(&replaced_at)
+        .bind(message_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Ids of messages whose time to live has run out, oldest expiry first
+    pub async fn find_expired(
+        pool: &PgPool,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<Uuid>, sqlx::Error> {
+        let rows: Vec<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT id FROM messages
+            WHERE expires_at IS NOT NULL AND expires_at <= $1
+            ORDER BY expires_at
+            LIMIT $2
+            "#,
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.0).collect())
+    }
+
+    /// Remove everything an expired message held: its content and earlier versions,
+    /// mentions, previews, reactions and attachments. The row stays as a tombstone.
+    /// Returns the message as it was, or `None` if it was purged already, and the
+    /// storage paths of the removed attachments.
+    pub async fn purge_expired(
+        pool: &PgPool,
+        id: &Uuid,
+    ) -> Result<(Option<Message>, Vec<String>), sqlx::Error> {
+        let now = Utc::now();
+        let mut tx = pool.begin().await?;
+
+        let row: Option<MessageRow> = sqlx::query_as(
+            r#"
+            SELECT id, channel_id, sender_id, content, message_type, format, reply_to_id, edited, created_at, updated_at, deleted_at, deleted_by, moderated_by, expires_at
+            FROM messages WHERE id = $1 AND expires_at IS NOT NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok((None, vec![]));
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE messages SET content = '', expires_at = NULL,
+                deleted_at = COALESCE(deleted_at, $1), updated_at = $1
+            WHERE id = $2
+            "#,
+        )
+        .bind(&now)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(r#"DELETE FROM message_revisions WHERE message_id = $1"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(r#"DELETE FROM message_mentions WHERE message_id = $1"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(r#"DELETE FROM message_link_previews WHERE message_id = $1"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(r#"DELETE FROM reactions WHERE message_id = $1"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        let storage_paths: Vec<(String,)> = sqlx::query_as(
+            r#"DELETE FROM file_attachments WHERE message_id = $1 RETURNING storage_path"#,
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok((Some(row.into()), storage_paths.into_iter().map(|p| p.0).collect()))
+    }
+
+    /// Previous versions of a message, oldest first
+    pub async fn find_revisions(
+        pool: &PgPool,
+        message_id: &Uuid,
+    ) -> Result<Vec<MessageRevision>, sqlx::Error> {
+        let rows: Vec<MessageRevisionRow> = sqlx::query_as(
+            r#"
+            SELECT id, message_id, content, replaced_by, created_at, replaced_at
+            FROM message_revisions WHERE message_id = $1
+            ORDER BY replaced_at
+            "#,
+        )
+        .bind(message_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Search messages in the channels the user belongs to, honoring each channel's history visibility
     pub async fn search(
         pool: &PgPool,
+        user_id: &Uuid,
         query: &str,
         channel_id: Option<&Uuid>,
         from_user_id: Option<&Uuid>,
         from_date: Option<DateTime<Utc>>,
         to_date: Option<DateTime<Utc>>,
         limit: i64,
-        offset: i64,
-    ) -> Result<(Vec<Message>, i64), sqlx::Error> {
+        before: Option<Cursor>,
+    ) -> Result<Vec<Message>, sqlx::Error> {
         let search_pattern = format!("%{}%", query);
-        
-        // Build query dynamically based on filters
-        let base_query = "SELECT id, channel_id, sender_id, content, message_type, reply_to_id, edited, created_at, updated_at FROM messages WHERE content ILIKE $1";
-        let count_base = "SELECT COUNT(*) FROM messages WHERE content ILIKE $1";
-        
-        // For simplicity, we'll use a basic query without dynamic filters for now
+
         let rows: Vec<MessageRow> = sqlx::query_as(
-            &format!("{} ORDER BY created_at DESC LIMIT $2 OFFSET $3", base_query),
+            r#"
+            SELECT m.id, m.channel_id, m.sender_id, m.content, m.message_type, m.format, m.reply_to_id, m.edited, m.created_at, m.updated_at, m.deleted_at, m.deleted_by, m.moderated_by, m.expires_at
+            FROM messages m
+            INNER JOIN channel_members cm ON cm.channel_id = m.channel_id AND cm.user_id = $2
+            INNER JOIN channels c ON c.id = m.channel_id
+            WHERE m.content ILIKE $1
+            AND m.deleted_at IS NULL
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND (c.history_visibility = 'full' OR m.created_at >= cm.joined_at)
+            AND ($3::uuid IS NULL OR m.channel_id = $3)
+            AND ($4::uuid IS NULL OR m.sender_id = $4)
+            AND ($5::timestamptz IS NULL OR m.created_at >= $5)
+            AND ($6::timestamptz IS NULL OR m.created_at <= $6)
+            AND ($7::timestamptz IS NULL OR (m.created_at, m.id) < ($7, $8))
+            ORDER BY m.created_at DESC, m.id DESC
+            LIMIT $9
+            "#,
         )
         .bind(&search_pattern)
+        .bind(user_id)
+        .bind(channel_id)
+        .bind(from_user_id)
+        .bind(from_date)
+        .bind(to_date)
+        .bind(before.map(|c| c.created_at))
+        .bind(before.map(|c| c.id))
         .bind(limit)
-        .bind(offset)
         .fetch_all(pool)
         .await?;
 
-        let (total_count,): (i64,) = sqlx::query_as(count_base)
-            .bind(&search_pattern)
-            .fetch_one(pool)
-            .await?;
-
-        Ok((rows.into_iter().map(|r| r.into()).collect(), total_count))
+        Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
     pub async fn get_last_message(
@@ -231,7 +481,7 @@ impl MessageRepository {
         channel_id: &Uuid,
     ) -> Result<Option<Message>, sqlx::Error> {
         let row: Option<MessageRow> = sqlx::query_as(
-            r#"SELECT id, channel_id, sender_id, content, message_type, reply_to_id, edited, created_at, updated_at 
+            r#"SELECT id, channel_id, sender_id, content, message_type, format, reply_to_id, edited, created_at, updated_at, deleted_at, deleted_by, moderated_by, expires_at 
             FROM messages WHERE channel_id = $1 ORDER BY created_at DESC LIMIT 1"#,
         )
         .bind(channel_id)
@@ -241,6 +491,169 @@ impl MessageRepository {
         Ok(row.map(|r| r.into()))
     }
 
+    /// Serialize the sender's posts in a channel until the transaction ends, so each
+    /// rate limit check sees the messages of every send before it
+    pub async fn lock_sender(
+        tx: &mut Transaction<'_, Postgres>,
+        channel_id: &Uuid,
+        sender_id: &Uuid,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"SELECT pg_advisory_xact_lock(hashtextextended($1::text || $2::text, 0))"#)
+            .bind(channel_id)
+            .bind(sender_id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// When the sender last posted in the channel
+    pub async fn get_last_sent_at(
+        tx: &mut Transaction<'_, Postgres>,
+        channel_id: &Uuid,
+        sender_id: &Uuid,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let result: (Option<DateTime<Utc>>,) = sqlx::query_as(
+            r#"SELECT MAX(created_at) FROM messages WHERE channel_id = $1 AND sender_id = $2"#,
+        )
+        .bind(channel_id)
+        .bind(sender_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(result.0)
+    }
+
+    /// Number of messages the sender posted in the channel since the given time, and the oldest of them
+    pub async fn count_sent_since(
+        tx: &mut Transaction<'_, Postgres>,
+        channel_id: &Uuid,
+        sender_id: &Uuid,
+        since: &DateTime<Utc>,
+    ) -> Result<(i64, Option<DateTime<Utc>>), sqlx::Error> {
+        let result: (i64, Option<DateTime<Utc>>) = sqlx::query_as(
+            r#"SELECT COUNT(*), MIN(created_at) FROM messages WHERE channel_id = $1 AND sender_id = $2 AND created_at > $3"#,
+        )
+        .bind(channel_id)
+        .bind(sender_id)
+        .bind(since)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(result)
+    }
+
+    // Mention operations
+    /// Replace the stored mentions of a message with `(type, user, start, end)` entries
+    pub async fn replace_mentions(
+        pool: &PgPool,
+        message_id: &Uuid,
+        mentions: &[(MentionType, Option<Uuid>, i32, i32)],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(r#"DELETE FROM message_mentions WHERE message_id = $1"#)
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for (mention_type, user_id, start_offset, end_offset) in mentions {
+            let type_str = serde_json::to_string(mention_type).unwrap().trim_matches('"').to_string();
+
+            sqlx::query(
+                r#"
+                INSERT INTO message_mentions (id, message_id, mention_type, user_id, start_offset, end_offset)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(message_id)
+            .bind(&type_str)
+            .bind(user_id)
+            .bind(start_offset)
+            .bind(end_offset)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn get_mentions_for_messages(
+        pool: &PgPool,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<MessageMention>, sqlx::Error> {
+        let rows: Vec<MessageMentionRow> = sqlx::query_as(
+            r#"SELECT id, message_id, mention_type, user_id, start_offset, end_offset FROM message_mentions WHERE message_id = ANY($1) ORDER BY start_offset"#,
+        )
+        .bind(message_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// The message a sender posted with the nonce since `since`, if any
+    pub async fn find_by_client_nonce(
+        pool: &PgPool,
+        sender_id: &Uuid,
+        client_nonce: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<Message>, sqlx::Error> {
+        let row: Option<MessageRow> = sqlx::query_as(
+            r#"
+            SELECT m.id, m.channel_id, m.sender_id, m.content, m.message_type, m.format, m.reply_to_id, m.edited, m.created_at, m.updated_at, m.deleted_at, m.deleted_by, m.moderated_by, m.expires_at
+            FROM message_nonces n
+            INNER JOIN messages m ON m.id = n.message_id
+            WHERE n.sender_id = $1 AND n.client_nonce = $2 AND n.created_at > $3
+            "#,
+        )
+        .bind(sender_id)
+        .bind(client_nonce)
+        .bind(since)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|r| r.into()))
+    }
+
+    /// Record the nonce a message is sent with, as part of the transaction creating
+    /// it, and forget the sender's nonces from before `expired_before`. Returns false
+    /// if the nonce is taken, in which case the transaction should be rolled back.
+    /// A concurrent send with the same nonce waits here until the first one ends.
+    pub async fn reserve_client_nonce(
+        tx: &mut Transaction<'_, Postgres>,
+        sender_id: &Uuid,
+        client_nonce: &str,
+        message_id: &Uuid,
+        expired_before: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query(r#"DELETE FROM message_nonces WHERE sender_id = $1 AND created_at <= $2"#)
+            .bind(sender_id)
+            .bind(expired_before)
+            .execute(&mut **tx)
+            .await?;
+
+        let reserved: Option<(Uuid,)> = sqlx::query_as(
+            r#"
+            INSERT INTO message_nonces (sender_id, client_nonce, message_id, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (sender_id, client_nonce) DO NOTHING
+            RETURNING message_id
+            "#,
+        )
+        .bind(sender_id)
+        .bind(client_nonce)
+        .bind(message_id)
+        .bind(Utc::now())
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(reserved.is_some())
+    }
+
     // Reaction operations
     pub async fn add_reaction(
         pool: &PgPool,
@@ -280,8 +693,8 @@ impl MessageRepository {
         message_id: &Uuid,
         user_id: &Uuid,
         emoji: &str,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query(
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
             r#"DELETE FROM reactions WHERE message_id = $1 AND user_id = $2 AND emoji = $3"#,
         )
         .bind(message_id)
@@ -290,17 +703,17 @@ impl MessageRepository {
         .execute(pool)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 
-    pub async fn get_reactions(
+    pub async fn get_reactions_for_messages(
         pool: &PgPool,
-        message_id: &Uuid,
+        message_ids: &[Uuid],
     ) -> Result<Vec<Reaction>, sqlx::Error> {
         let rows: Vec<ReactionRow> = sqlx::query_as(
-            r#"SELECT id, message_id, user_id, emoji, created_at FROM reactions WHERE message_id = $1 ORDER BY created_at"#,
+            r#"SELECT id, message_id, user_id, emoji, created_at FROM reactions WHERE message_id = ANY($1) ORDER BY created_at"#,
         )
-        .bind(message_id)
+        .bind(message_ids)
         .fetch_all(pool)
         .await?;
 
@@ -3,17 +3,38 @@
 pub mod users;
 pub mod teams;
 pub mod channels;
+pub mod content_rules;
 pub mod messages;
+pub mod drafts;
+pub mod emoji;
+pub mod forwards;
+pub mod link_previews;
 pub mod files;
 pub mod calls;
 pub mod notifications;
 pub mod meetings;
+pub mod moderation;
+pub mod polls;
+pub mod retention;
+pub mod scheduled_messages;
+
+#[cfg(test)]
+pub mod testing;
 
 pub use users::*;
 pub use teams::*;
 pub use channels::*;
+pub use content_rules::*;
 pub use messages::*;
+pub use drafts::*;
+pub use emoji::*;
+pub use forwards::*;
+pub use link_previews::*;
 pub use files::*;
 pub use calls::*;
 pub use notifications::*;
 pub use meetings::*;
+pub use moderation::*;
+pub use polls::*;
+pub use retention::*;
+pub use scheduled_messages::*;
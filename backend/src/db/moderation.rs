@@ -0,0 +1,212 @@
+//! Moderation audit database operations
+
+use chrono::{DateTime, Utc};
+use shared::models::{FlaggedMessage, ModerationAction, ModerationRecord};
+use shared::pagination::Cursor;
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(Debug, FromRow)]
+pub struct ModerationRecordRow {
+    pub id: Uuid,
+    pub message_id: Option<Uuid>,
+    pub channel_id: Uuid,
+    pub team_id: Uuid,
+    pub moderator_id: Uuid,
+    pub action: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ModerationRecordRow> for ModerationRecord {
+    fn from(row: ModerationRecordRow) -> Self {
+        ModerationRecord {
+            id: row.id,
+            message_id: row.message_id,
+            channel_id: row.channel_id,
+            team_id: row.team_id,
+            moderator_id: row.moderator_id,
+            action: serde_json::from_str(&format!("\"{}\"", row.action)).unwrap_or_default(),
+            reason: row.reason,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct FlaggedMessageRow {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub channel_id: Uuid,
+    pub team_id: Uuid,
+    pub rule_id: Option<Uuid>,
+    pub rule_name: String,
+    pub created_at: DateTime<Utc>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl From<FlaggedMessageRow> for FlaggedMessage {
+    fn from(row: FlaggedMessageRow) -> Self {
+        FlaggedMessage {
+            id: row.id,
+            message_id: row.message_id,
+            channel_id: row.channel_id,
+            team_id: row.team_id,
+            rule_id: row.rule_id,
+            rule_name: row.rule_name,
+            created_at: row.created_at,
+            resolved_by: row.resolved_by,
+            resolved_at: row.resolved_at,
+        }
+    }
+}
+
+pub struct ModerationRepository;
+
+impl ModerationRepository {
+    /// Record a moderation action on a message, as part of the transaction applying it
+    pub async fn record(
+        tx: &mut Transaction<'_, Postgres>,
+        message_id: &Uuid,
+        moderator_id: &Uuid,
+        action: ModerationAction,
+        reason: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let action_str = serde_json::to_string(&action).unwrap().trim_matches('"').to_string();
+
+        sqlx::query(
+            r#"
+            INSERT INTO moderation_actions (id, message_id, channel_id, team_id, moderator_id, action, reason, created_at)
+            SELECT $1, m.id, m.channel_id, c.team_id, $2, $3, $4, $5
+            FROM messages m
+            INNER JOIN channels c ON c.id = m.channel_id
+            WHERE m.id = $6
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(moderator_id)
+        .bind(&action_str)
+        .bind(reason)
+        .bind(&now)
+        .bind(message_id)
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query(r#"UPDATE messages SET moderated_by = $1 WHERE id = $2"#)
+            .bind(moderator_id)
+            .bind(message_id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Up to `limit` moderation actions in a team, newest first, taken before the cursor
+    pub async fn find_by_team(
+        pool: &PgPool,
+        team_id: &Uuid,
+        limit: i64,
+        before: Option<Cursor>,
+    ) -> Result<Vec<ModerationRecord>, sqlx::Error> {
+        let rows: Vec<ModerationRecordRow> = sqlx::query_as(
+            r#"
+            SELECT id, message_id, channel_id, team_id, moderator_id, action, reason, created_at
+            FROM moderation_actions WHERE team_id = $1
+            AND ($2::timestamptz IS NULL OR (created_at, id) < ($2, $3))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(team_id)
+        .bind(before.map(|c| c.created_at))
+        .bind(before.map(|c| c.id))
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Queue a message for the team's moderators. A message already waiting for
+    /// review under the same rule is not queued twice.
+    pub async fn flag(
+        pool: &PgPool,
+        message_id: &Uuid,
+        rule_id: &Uuid,
+        rule_name: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO flagged_messages (id, message_id, channel_id, team_id, rule_id, rule_name, created_at)
+            SELECT $1, m.id, m.channel_id, c.team_id, $2, $3, $4
+            FROM messages m
+            INNER JOIN channels c ON c.id = m.channel_id
+            WHERE m.id = $5 AND c.team_id IS NOT NULL
+            ON CONFLICT (message_id, rule_id) WHERE resolved_at IS NULL DO NOTHING
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(rule_id)
+        .bind(rule_name)
+        .bind(Utc::now())
+        .bind(message_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Up to `limit` unresolved flags in the team, newest first, before the cursor.
+    /// Flags on messages deleted since are left out.
+    pub async fn find_flagged(
+        pool: &PgPool,
+        team_id: &Uuid,
+        limit: i64,
+        before: Option<Cursor>,
+    ) -> Result<Vec<FlaggedMessage>, sqlx::Error> {
+        let rows: Vec<FlaggedMessageRow> = sqlx::query_as(
+            r#"
+            SELECT f.id, f.message_id, f.channel_id, f.team_id, f.rule_id, f.rule_name, f.created_at, f.resolved_by, f.resolved_at
+            FROM flagged_messages f
+            INNER JOIN messages m ON m.id = f.message_id
+            WHERE f.team_id = $1 AND f.resolved_at IS NULL AND m.deleted_at IS NULL
+            AND ($2::timestamptz IS NULL OR (f.created_at, f.id) < ($2, $3))
+            ORDER BY f.created_at DESC, f.id DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(team_id)
+        .bind(before.map(|c| c.created_at))
+        .bind(before.map(|c| c.id))
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Take a flag off the queue. Returns whether it was waiting for review.
+    pub async fn resolve_flag(
+        pool: &PgPool,
+        id: &Uuid,
+        team_id: &Uuid,
+        resolved_by: &Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE flagged_messages SET resolved_by = $1, resolved_at = $2
+            WHERE id = $3 AND team_id = $4 AND resolved_at IS NULL
+            "#,
+        )
+        .bind(resolved_by)
+        .bind(Utc::now())
+        .bind(id)
+        .bind(team_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -2,6 +2,7 @@
 
 use chrono::{DateTime, Utc};
 use shared::models::{Notification, NotificationType};
+use shared::pagination::Cursor;
 use sqlx::{FromRow, PgPool};
 use uuid::Uuid;
 
@@ -12,7 +13,7 @@ pub struct NotificationRow {
     pub title: String,
     pub body: String,
     pub notification_type: String,
-    pub reference_id: Option<String>,
+    pub reference_id: Option<Uuid>,
     pub read: bool,
     pub created_at: DateTime<Utc>,
 }
@@ -25,7 +26,7 @@ impl From<NotificationRow> for Notification {
             title: row.title,
             body: row.body,
             notification_type: row.notification_type.parse().unwrap_or(NotificationType::System),
-            reference_id: row.reference_id,
+            reference_id: row.reference_id.map(|id| id.to_string()),
             read: row.read,
             created_at: row.created_at,
         }
@@ -57,7 +58,7 @@ impl NotificationRepository {
         .bind(title)
         .bind(body)
         .bind(notification_type)
-        .bind(reference_id.map(|r| r.to_string()))
+        .bind(reference_id)
         .bind(false)
         .bind(&now)
         .execute(pool)
@@ -66,6 +67,45 @@ impl NotificationRepository {
         Self::find_by_id(pool, &id).await
     }
 
+    /// Create one notification per recipient in a single statement. Each entry is
+    /// the recipient, title, body and notification type.
+    pub async fn create_many(
+        pool: &PgPool,
+        notifications: &[(Uuid, String, String, &str)],
+        reference_id: Option<&Uuid>,
+    ) -> Result<Vec<Notification>, sqlx::Error> {
+        if notifications.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let ids: Vec<Uuid> = notifications.iter().map(|_| Uuid::new_v4()).collect();
+        let user_ids: Vec<Uuid> = notifications.iter().map(|n| n.0).collect();
+        let titles: Vec<&str> = notifications.iter().map(|n| n.1.as_str()).collect();
+        let bodies: Vec<&str> = notifications.iter().map(|n| n.2.as_str()).collect();
+        let types: Vec<&str> = notifications.iter().map(|n| n.3).collect();
+
+        let rows: Vec<NotificationRow> = sqlx::query_as(
+            r#"
+            INSERT INTO notifications (id, user_id, title, body, notification_type, reference_id, read, created_at)
+            SELECT n.id, n.user_id, n.title, n.body, n.notification_type, $6, FALSE, $7
+            FROM UNNEST($1::uuid[], $2::uuid[], $3::text[], $4::text[], $5::text[])
+                AS n(id, user_id, title, body, notification_type)
+            RETURNING id, user_id, title, body, notification_type, reference_id, read, created_at
+            "#,
+        )
+        .bind(&ids)
+        .bind(&user_ids)
+        .bind(&titles)
+        .bind(&bodies)
+        .bind(&types)
+        .bind(reference_id)
+        .bind(Utc::now())
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
     pub async fn find_by_id(pool: &PgPool, id: &Uuid) -> Result<Notification, sqlx::Error> {
         let row: NotificationRow = sqlx::query_as(
             r#"SELECT id, user_id, title, body, notification_type, reference_id, read, created_at FROM notifications WHERE id = $1"#,
@@ -77,33 +117,32 @@ impl NotificationRepository {
         Ok(row.into())
     }
 
+    /// Up to `limit` of the user's notifications, newest first, created before the cursor
     pub async fn find_by_user(
         pool: &PgPool,
         user_id: &Uuid,
         unread_only: bool,
         limit: i64,
-        offset: i64,
+        before: Option<Cursor>,
     ) -> Result<Vec<Notification>, sqlx::Error> {
-        let rows: Vec<NotificationRow> = if unread_only {
-            sqlx::query_as(
-                r#"SELECT id, user_id, title, body, notification_type, reference_id, read, created_at FROM notifications WHERE user_id = $1 AND read = $2 ORDER BY created_at DESC LIMIT $3 OFFSET $4"#
-            )
-            .bind(user_id)
-            .bind(false)
-            .bind(limit)
-            .bind(offset)
-            .fetch_all(pool)
-            .await?
-        } else {
-            sqlx::query_as(
-                r#"SELECT id, user_id, title, body, notification_type, reference_id, read, created_at FROM notifications WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3"#
-            )
-            .bind(user_id)
-            .bind(limit)
-            .bind(offset)
-            .fetch_all(pool)
-            .await?
-        };
+        let rows: Vec<NotificationRow> = sqlx::query_as(
+            r#"
+            SELECT id, user_id, title, body, notification_type, reference_id, read, created_at
+            FROM notifications
+            WHERE user_id = $1
+            AND (NOT $2 OR read = FALSE)
+            AND ($3::timestamptz IS NULL OR (created_at, id) < ($3, $4))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $5
+            "#,
+        )
+        .bind(user_id)
+        .bind(unread_only)
+        .bind(before.map(|c| c.created_at))
+        .bind(before.map(|c| c.id))
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
 
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
@@ -0,0 +1,271 @@
+//! Poll database operations
+
+use chrono::{DateTime, Utc};
+use shared::models::{Poll, PollOption, PollVote};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(Debug, FromRow)]
+pub struct PollRow {
+    pub message_id: Uuid,
+    pub question: String,
+    pub allow_multiple: bool,
+    pub anonymous: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<PollRow> for Poll {
+    fn from(row: PollRow) -> Self {
+        Poll {
+            message_id: row.message_id,
+            question: row.question,
+            allow_multiple: row.allow_multiple,
+            anonymous: row.anonymous,
+            closes_at: row.closes_at,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct PollOptionRow {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub position: i32,
+    pub text: String,
+}
+
+impl From<PollOptionRow> for PollOption {
+    fn from(row: PollOptionRow) -> Self {
+        PollOption {
+            id: row.id,
+            message_id: row.message_id,
+            position: row.position,
+            text: row.text,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct PollVoteRow {
+    pub message_id: Uuid,
+    pub option_id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<PollVoteRow> for PollVote {
+    fn from(row: PollVoteRow) -> Self {
+        PollVote {
+            message_id: row.message_id,
+            option_id: row.option_id,
+            user_id: row.user_id,
+            created_at: row.created_at,
+        }
+    }
+}
+
+pub struct PollRepository;
+
+impl PollRepository {
+    /// Store a poll and its options in the transaction that creates its message
+    pub async fn create(
+        tx: &mut Transaction<'_, Postgres>,
+        message_id: &Uuid,
+        question: &str,
+        options: &[String],
+        allow_multiple: bool,
+        anonymous: bool,
+        closes_at: Option<DateTime<Utc>>,
+    ) -> Result<Poll, sqlx::Error> {
+        let row: PollRow = sqlx::query_as(
+            r#"
+            INSERT INTO polls (message_id, question, allow_multiple, anonymous, closes_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING message_id, question, allow_multiple, anonymous, closes_at, created_at
+            "#,
+        )
+        .bind(message_id)
+        .bind(question)
+        .bind(allow_multiple)
+        .bind(anonymous)
+        .bind(closes_at)
+        .bind(Utc::now())
+        .fetch_one(&mut **tx)
+        .await?;
+
+        for (position, text) in options.iter().enumerate() {
+            sqlx::query(
+                r#"
+                INSERT INTO poll_options (id, message_id, position, text)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(message_id)
+            .bind(position as i32)
+            .bind(text)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(row.into())
+    }
+
+    pub async fn find_by_message(pool: &PgPool, message_id: &Uuid) -> Result<Option<Poll>, sqlx::Error> {
+        let row: Option<PollRow> = sqlx::query_as(
+            r#"
+            SELECT message_id, question, allow_multiple, anonymous, closes_at, created_at
+            FROM polls WHERE message_id = $1
+            "#,
+        )
+        .bind(message_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|r| r.into()))
+    }
+
+    pub async fn find_options(pool: &PgPool, message_id: &Uuid) -> Result<Vec<PollOption>, sqlx::Error> {
+        let rows: Vec<PollOptionRow> = sqlx::query_as(
+            r#"
+            SELECT id, message_id, position, text
+            FROM poll_options WHERE message_id = $1
+            ORDER BY position
+            "#,
+        )
+        .bind(message_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    pub async fn find_votes(pool: &PgPool, message_id: &Uuid) -> Result<Vec<PollVote>, sqlx::Error> {
+        let rows: Vec<PollVoteRow> = sqlx::query_as(
+            r#"
+            SELECT message_id, option_id, user_id, created_at
+            FROM poll_votes WHERE message_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(message_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    pub async fn find_by_messages(pool: &PgPool, message_ids: &[Uuid]) -> Result<Vec<Poll>, sqlx::Error> {
+        let rows: Vec<PollRow> = sqlx::query_as(
+            r#"
+            SELECT message_id, question, allow_multiple, anonymous, closes_at, created_at
+            FROM polls WHERE message_id = ANY($1)
+            "#,
+        )
+        .bind(message_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    pub async fn find_options_for_messages(
+        pool: &PgPool,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<PollOption>, sqlx::Error> {
+        let rows: Vec<PollOptionRow> = sqlx::query_as(
+            r#"
+            SELECT id, message_id, position, text
+            FROM poll_options WHERE message_id = ANY($1)
+            ORDER BY position
+            "#,
+        )
+        .bind(message_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    pub async fn find_votes_for_messages(
+        pool: &PgPool,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<PollVote>, sqlx::Error> {
+        let rows: Vec<PollVoteRow> = sqlx::query_as(
+            r#"
+            SELECT message_id, option_id, user_id, created_at
+            FROM poll_votes WHERE message_id = ANY($1)
+            ORDER BY created_at
+            "#,
+        )
+        .bind(message_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Record a vote. With `exclusive`, the user's other votes in the poll are
+    /// replaced. Votes on a poll are serialized so a single choice poll never
+    /// ends up with two votes from one user.
+    pub async fn vote(
+        pool: &PgPool,
+        message_id: &Uuid,
+        option_id: &Uuid,
+        user_id: &Uuid,
+        exclusive: bool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(r#"SELECT message_id FROM polls WHERE message_id = $1 FOR UPDATE"#)
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await?;
+
+        if exclusive {
+            sqlx::query(r#"DELETE FROM poll_votes WHERE message_id = $1 AND user_id = $2 AND option_id <> $3"#)
+                .bind(message_id)
+                .bind(user_id)
+                .bind(option_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO poll_votes (message_id, option_id, user_id, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (option_id, user_id) DO NOTHING
+            "#,
+        )
+        .bind(message_id)
+        .bind(option_id)
+        .bind(user_id)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Returns whether there was a vote to retract in the poll
+    pub async fn retract_vote(
+        pool: &PgPool,
+        message_id: &Uuid,
+        option_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(r#"DELETE FROM poll_votes WHERE message_id = $1 AND option_id = $2 AND user_id = $3"#)
+            .bind(message_id)
+            .bind(option_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
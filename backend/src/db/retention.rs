@@ -0,0 +1,157 @@
+//! Message retention database operations
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub struct RetentionRepository;
+
+impl RetentionRepository {
+    pub async fn get_team_policy(pool: &PgPool, team_id: &Uuid) -> Result<Option<i32>, sqlx::Error> {
+        let result: (Option<i32>,) = sqlx::query_as(r#"SELECT retention_days FROM teams WHERE id = $1"#)
+            .bind(team_id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(result.0)
+    }
+
+    pub async fn set_team_policy(
+        pool: &PgPool,
+        team_id: &Uuid,
+        retention_days: Option<i32>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE teams SET retention_days = $1, updated_at = $2 WHERE id = $3"#)
+            .bind(retention_days)
+            .bind(Utc::now())
+            .bind(team_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// The channel's own policy and its team's
+    pub async fn get_channel_policy(
+        pool: &PgPool,
+        channel_id: &Uuid,
+    ) -> Result<(Option<i32>, Option<i32>), sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT c.retention_days, t.retention_days
+            FROM channels c
+            LEFT JOIN teams t ON t.id = c.team_id
+            WHERE c.id = $1
+            "#,
+        )
+        .bind(channel_id)
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn set_channel_policy(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        retention_days: Option<i32>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE channels SET retention_days = $1, updated_at = $2 WHERE id = $3"#)
+            .bind(retention_days)
+            .bind(Utc::now())
+            .bind(channel_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Channels whose messages expire, with the days they are kept for
+    pub async fn find_expiring_channels(
+        pool: &PgPool,
+        default_days: i32,
+    ) -> Result<Vec<(Uuid, i32)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT c.id, COALESCE(c.retention_days, t.retention_days, $1) AS retention_days
+            FROM channels c
+            LEFT JOIN teams t ON t.id = c.team_id
+            WHERE COALESCE(c.retention_days, t.retention_days, $1) > 0
+            "#,
+        )
+        .bind(default_days)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Number of messages in the channel sent before `cutoff`, and of their attachments
+    pub async fn count_expired(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        cutoff: DateTime<Utc>,
+    ) -> Result<(i64, i64), sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM messages WHERE channel_id = $1 AND created_at < $2),
+                (SELECT COUNT(*) FROM file_attachments f
+                 INNER JOIN messages m ON m.id = f.message_id
+                 WHERE m.channel_id = $1 AND m.created_at < $2)
+            "#,
+        )
+        .bind(channel_id)
+        .bind(cutoff)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Delete up to `limit` of the channel's messages sent before `cutoff`, oldest
+    /// first, with their attachments and the snapshots forwards took of them. Returns
+    /// the number of deleted messages and the storage paths of the deleted attachments.
+    pub async fn purge_expired(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        cutoff: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<(u64, Vec<String>), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let ids: Vec<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT id FROM messages
+            WHERE channel_id = $1 AND created_at < $2
+            ORDER BY created_at
+            LIMIT $3
+            FOR UPDATE
+            "#,
+        )
+        .bind(channel_id)
+        .bind(cutoff)
+        .bind(limit)
+        .fetch_all(&mut *tx)
+        .await?;
+        let ids: Vec<Uuid> = ids.into_iter().map(|r| r.0).collect();
+
+        // Attachments outlive their message otherwise
+        let storage_paths: Vec<(String,)> = sqlx::query_as(
+            r#"DELETE FROM file_attachments WHERE message_id = ANY($1) RETURNING storage_path"#,
+        )
+        .bind(&ids)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // Forwards elsewhere keep a copy of the content, which must not outlive it either
+        sqlx::query(r#"UPDATE message_forwards SET content = '' WHERE source_message_id = ANY($1)"#)
+            .bind(&ids)
+            .execute(&mut *tx)
+            .await?;
+
+        // Mentions, reactions, revisions, previews, polls and nonces go with the message
+        let result = sqlx::query(r#"DELETE FROM messages WHERE id = ANY($1)"#)
+            .bind(&ids)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok((result.rows_affected(), storage_paths.into_iter().map(|p| p.0).collect()))
+    }
+}
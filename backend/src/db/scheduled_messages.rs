@@ -0,0 +1,254 @@
+//! Scheduled message database operations
+
+use chrono::{DateTime, Utc};
+use shared::models::{MessageFormat, MessageType, ScheduledMessage};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, FromRow)]
+pub struct ScheduledMessageRow {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub sender_id: Uuid,
+    pub content: String,
+    pub message_type: String,
+    pub format: String,
+    pub reply_to_id: Option<Uuid>,
+    pub attachment_ids: Vec<Uuid>,
+    pub send_at: DateTime<Utc>,
+    pub status: String,
+    pub error: Option<String>,
+    pub client_nonce: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<ScheduledMessageRow> for ScheduledMessage {
+    fn from(row: ScheduledMessageRow) -> Self {
+        ScheduledMessage {
+            id: row.id,
+            channel_id: row.channel_id,
+            sender_id: row.sender_id,
+            content: row.content,
+            message_type: serde_json::from_str(&format!("\"{}\"", row.message_type)).unwrap_or_default(),
+            format: serde_json::from_str(&format!("\"{}\"", row.format)).unwrap_or_default(),
+            reply_to_id: row.reply_to_id,
+            attachment_ids: row.attachment_ids,
+            send_at: row.send_at,
+            status: serde_json::from_str(&format!("\"{}\"", row.status)).unwrap_or_default(),
+            error: row.error,
+            client_nonce: row.client_nonce,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+pub struct ScheduledMessageRepository;
+
+impl ScheduledMessageRepository {
+    /// Store a message to send later. A request retried with the same client nonce
+    /// gets the message stored the first time.
+    pub async fn create(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        sender_id: &Uuid,
+        content: &str,
+        message_type: MessageType,
+        format: MessageFormat,
+        reply_to_id: Option<&Uuid>,
+        attachment_ids: &[Uuid],
+        send_at: DateTime<Utc>,
+        client_nonce: Option<&str>,
+    ) -> Result<ScheduledMessage, sqlx::Error> {
+        let now = Utc::now();
+        let type_str = serde_json::to_string(&message_type).unwrap().trim_matches('"').to_string();
+        let format_str = serde_json::to_string(&format).unwrap().trim_matches('"').to_string();
+
+        let created: Option<(Uuid,)> = sqlx::query_as(
+            r#"
+            INSERT INTO scheduled_messages (id, channel_id, sender_id, content, message_type, format, reply_to_id, attachment_ids, send_at, client_nonce, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            ON CONFLICT (sender_id, client_nonce) WHERE client_nonce IS NOT NULL DO NOTHING
+            RETURNING id
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(channel_id)
+        .bind(sender_id)
+        .bind(content)
+        .bind(&type_str)
+        .bind(&format_str)
+        .bind(reply_to_id)
+        .bind(attachment_ids)
+        .bind(&send_at)
+        .bind(client_nonce)
+        .bind(&now)
+        .bind(&now)
+        .fetch_optional(pool)
+        .await?;
+
+        match (created, client_nonce) {
+            (Some((id,)), _) => Self::find_by_id(pool, &id).await,
+            (None, Some(client_nonce)) => {
+                let row: ScheduledMessageRow = sqlx::query_as(
+                    r#"
+                    SELECT id, channel_id, sender_id, content, message_type, format, reply_to_id, attachment_ids, send_at, status, error, client_nonce, created_at, updated_at
+                    FROM scheduled_messages WHERE sender_id = $1 AND client_nonce = $2
+                    "#,
+                )
+                .bind(sender_id)
+                .bind(client_nonce)
+                .fetch_one(pool)
+                .await?;
+
+                Ok(row.into())
+            }
+            (None, None) => Err(sqlx::Error::RowNotFound),
+        }
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: &Uuid) -> Result<ScheduledMessage, sqlx::Error> {
+        let row: ScheduledMessageRow = sqlx::query_as(
+            r#"
+            SELECT id, channel_id, sender_id, content, message_type, format, reply_to_id, attachment_ids, send_at, status, error, client_nonce, created_at, updated_at
+            FROM scheduled_messages WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    /// Pending messages of a user, soonest first
+    pub async fn find_by_sender(
+        pool: &PgPool,
+        sender_id: &Uuid,
+    ) -> Result<Vec<ScheduledMessage>, sqlx::Error> {
+        let rows: Vec<ScheduledMessageRow> = sqlx::query_as(
+            r#"
+            SELECT id, channel_id, sender_id, content, message_type, format, reply_to_id, attachment_ids, send_at, status, error, client_nonce, created_at, updated_at
+            FROM scheduled_messages WHERE sender_id = $1
+            ORDER BY send_at
+            "#,
+        )
+        .bind(sender_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Edit a message the dispatcher hasn't taken. A new send time queues a failed
+    /// message again.
+    pub async fn update(
+        pool: &PgPool,
+        id: &Uuid,
+        content: Option<&str>,
+        send_at: Option<DateTime<Utc>>,
+    ) -> Result<ScheduledMessage, sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE scheduled_messages
+            SET content = COALESCE($1, content), send_at = COALESCE($2, send_at), updated_at = $3,
+                status = CASE WHEN $2::timestamptz IS NULL THEN status ELSE 'pending' END,
+                error = CASE WHEN $2::timestamptz IS NULL THEN error END
+            WHERE id = $4 AND status <> 'sending'
+            "#,
+        )
+        .bind(content)
+        .bind(send_at)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Self::find_by_id(pool, id).await
+    }
+
+    /// Returns whether the message was still there and not taken by the dispatcher
+    pub async fn delete(pool: &PgPool, id: &Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(r#"DELETE FROM scheduled_messages WHERE id = $1 AND status <> 'sending'"#)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mark messages that are due as being sent and return them. Rows locked by another
+    /// dispatcher are skipped, so each message is taken once. Messages taken before
+    /// `stale_before` whose dispatcher never finished are taken again.
+    pub async fn claim_due(
+        pool: &PgPool,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ScheduledMessage>, sqlx::Error> {
+        let rows: Vec<ScheduledMessageRow> = sqlx::query_as(
+            r#"
+            UPDATE scheduled_messages SET status = 'sending', claimed_at = $1
+            WHERE id IN (
+                SELECT id FROM scheduled_messages
+                WHERE send_at <= $1
+                AND (status = 'pending' OR (status = 'sending' AND claimed_at < $2))
+                ORDER BY send_at
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, channel_id, sender_id, content, message_type, format, reply_to_id, attachment_ids, send_at, status, error, client_nonce, created_at, updated_at
+            "#,
+        )
+        .bind(&now)
+        .bind(&stale_before)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Remove a claimed message once it has been posted
+    pub async fn remove_sent(pool: &PgPool, id: &Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"DELETE FROM scheduled_messages WHERE id = $1"#)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Put a claimed or failed message back in the queue
+    pub async fn requeue(pool: &PgPool, id: &Uuid, send_at: DateTime<Utc>) -> Result<ScheduledMessage, sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE scheduled_messages
+            SET status = 'pending', send_at = $1, error = NULL, claimed_at = NULL, updated_at = $2
+            WHERE id = $3
+            "#,
+        )
+        .bind(&send_at)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Self::find_by_id(pool, id).await
+    }
+
+    /// Keep a claimed message that could not be posted, with the reason, for its author
+    pub async fn mark_failed(pool: &PgPool, id: &Uuid, error: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"UPDATE scheduled_messages SET status = 'failed', error = $1, claimed_at = NULL, updated_at = $2 WHERE id = $3"#,
+        )
+        .bind(error)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
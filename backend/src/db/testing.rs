@@ -0,0 +1,88 @@
+//! Database fixtures for tests
+//!
+//! Tests that need Postgres connect to the server at `TEST_DATABASE_URL` and are
+//! skipped when it isn't set. Each call to [`pool`] creates a fresh database with
+//! the migrations applied, so tests can run in parallel.
+
+use shared::models::{Channel, ChannelType, Team, TeamRole, User};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::{ChannelRepository, TeamRepository, UserRepository};
+
+/// A migrated database of its own, or `None` when no test server is configured
+pub async fn pool() -> Option<PgPool> {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        eprintln!("TEST_DATABASE_URL is not set, skipping database test");
+        return None;
+    };
+
+    let options: PgConnectOptions = url.parse().expect("invalid TEST_DATABASE_URL");
+    let admin = PgPool::connect_with(options.clone())
+        .await
+        .expect("failed to connect to the test database server");
+
+    let name = format!("rust_teams_test_{}", Uuid::new_v4().simple());
+    sqlx::query(&format!("CREATE DATABASE {}", name))
+        .execute(&admin)
+        .await
+        .expect("failed to create the test database");
+    admin.close().await;
+
+    let pool = PgPoolOptions::new()
+        .max_connections(10)
+        .connect_with(options.database(&name))
+        .await
+        .expect("failed to connect to the test database");
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("failed to run migrations");
+
+    Some(pool)
+}
+
+pub async fn user(pool: &PgPool, username: &str) -> User {
+    UserRepository::create(pool, &format!("{}@example.com", username), username, username, "hash")
+        .await
+        .unwrap()
+}
+
+/// A team owned by `owner`
+pub async fn team(pool: &PgPool, owner: &User) -> Team {
+    TeamRepository::create(pool, "Team", None, None, &owner.id).await.unwrap()
+}
+
+/// A new user who belongs to the team with the given role
+pub async fn team_member(pool: &PgPool, team: &Team, username: &str, role: TeamRole) -> User {
+    let user = user(pool, username).await;
+    TeamRepository::add_member(pool, &team.id, &user.id, role).await.unwrap();
+    user
+}
+
+/// A channel created by `creator`, with `members` joined
+pub async fn channel(
+    pool: &PgPool,
+    team: Option<&Team>,
+    channel_type: ChannelType,
+    creator: &User,
+    members: &[&User],
+) -> Channel {
+    let channel = ChannelRepository::create(
+        pool,
+        team.map(|t| &t.id),
+        "channel",
+        None,
+        channel_type,
+        &creator.id,
+    )
+    .await
+    .unwrap();
+
+    for member in members {
+        ChannelRepository::add_member(pool, &channel.id, &member.id).await.unwrap();
+    }
+
+    channel
+}
@@ -83,6 +83,17 @@ impl UserRepository {
         Ok(row.into())
     }
 
+    pub async fn find_by_ids(pool: &PgPool, ids: &[Uuid]) -> Result<Vec<User>, sqlx::Error> {
+        let rows: Vec<UserRow> = sqlx::query_as(
+            r#"SELECT id, email, username, display_name, password_hash, avatar_url, status, status_message, last_seen, created_at, updated_at FROM users WHERE id = ANY($1)"#,
+        )
+        .bind(ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
     pub async fn find_by_email(pool: &PgPool, email: &str) -> Result<User, sqlx::Error> {
         let row: UserRow = sqlx::query_as(
             r#"SELECT id, email, username, display_name, password_hash, avatar_url, status, status_message, last_seen, created_at, updated_at FROM users WHERE email = $1"#,
@@ -200,6 +211,88 @@ impl UserRepository {
         Ok(())
     }
 
+    pub async fn get_read_receipts_enabled(pool: &PgPool, id: &Uuid) -> Result<bool, sqlx::Error> {
+        let result: (bool,) = sqlx::query_as(
+            r#"SELECT read_receipts_enabled FROM users WHERE id = $1"#,
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(result.0)
+    }
+
+    pub async fn set_read_receipts_enabled(
+        pool: &PgPool,
+        id: &Uuid,
+        enabled: bool,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"UPDATE users SET read_receipts_enabled = $1, updated_at = $2 WHERE id = $3"#,
+        )
+        .bind(enabled)
+        .bind(&now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Channel members sharing read receipts who have read up to the given time
+    pub async fn find_channel_readers(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        read_since: &DateTime<Utc>,
+        exclude_user_id: &Uuid,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let rows: Vec<UserRow> = sqlx::query_as(
+            r#"
+            SELECT u.id, u.email, u.username, u.display_name, u.password_hash, u.avatar_url, u.status, u.status_message, u.last_seen, u.created_at, u.updated_at
+            FROM users u
+            INNER JOIN channel_members cm ON cm.user_id = u.id
+            WHERE cm.channel_id = $1
+            AND cm.last_read_at >= $2
+            AND u.id <> $3
+            AND u.read_receipts_enabled = TRUE
+            ORDER BY cm.last_read_at
+            "#,
+        )
+        .bind(channel_id)
+        .bind(read_since)
+        .bind(exclude_user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Ids of the channel's members who share read receipts
+    pub async fn find_receipt_sharing_members(
+        pool: &PgPool,
+        channel_id: &Uuid,
+        exclude_user_id: &Uuid,
+    ) -> Result<Vec<Uuid>, sqlx::Error> {
+        let rows: Vec<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT u.id
+            FROM users u
+            INNER JOIN channel_members cm ON cm.user_id = u.id
+            WHERE cm.channel_id = $1
+            AND u.id <> $2
+            AND u.read_receipts_enabled = TRUE
+            "#,
+        )
+        .bind(channel_id)
+        .bind(exclude_user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.0).collect())
+    }
+
     pub async fn search(
         pool: &PgPool,
         query: &str,
@@ -1,6 +1,6 @@
 //! Error handling for the backend
 
-use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use actix_web::{http::header, http::StatusCode, HttpResponse, ResponseError};
 use shared::error::{AppError, ErrorResponse};
 use std::fmt;
 
@@ -68,7 +68,7 @@ impl ResponseError for ApiError {
             AppError::ValidationError(_) => StatusCode::BAD_REQUEST,
             AppError::ConflictError(_) => StatusCode::CONFLICT,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
-            AppError::RateLimitExceeded => StatusCode::TOO_MANY_REQUESTS,
+            AppError::RateLimitExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
             AppError::FileUploadError(_) => StatusCode::BAD_REQUEST,
             AppError::WebSocketError(_) => StatusCode::BAD_REQUEST,
             AppError::DatabaseError(_) | AppError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -77,7 +77,11 @@ impl ResponseError for ApiError {
 
     fn error_response(&self) -> HttpResponse {
         let error_response: ErrorResponse = self.0.to_error_response();
-        HttpResponse::build(self.status_code()).json(error_response)
+        let mut builder = HttpResponse::build(self.status_code());
+        if let Some(retry_after) = self.0.retry_after() {
+            builder.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        builder.json(error_response)
     }
 }
 
@@ -0,0 +1,299 @@
+//! Fetching of external pages for link previews
+//!
+//! Page fetching sits behind [`PageFetcher`] so the preview logic does not depend on
+//! the network; [`HttpPageFetcher`] is the implementation used by the server.
+
+use futures_util::future::BoxFuture;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE, LOCATION};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// Largest response body read, in bytes. Longer pages are cut off, which still
+/// leaves the `<head>` where the metadata lives.
+pub const MAX_BODY_BYTES: usize = 512 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REDIRECTS: usize = 3;
+const USER_AGENT: &str = "RustTeamsBot/1.0 (+link preview)";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FetchError {
+    #[error("URL is not allowed: {0}")]
+    Blocked(String),
+    #[error("Request failed: {0}")]
+    Request(String),
+    #[error("Unsupported content type: {0}")]
+    UnsupportedContent(String),
+}
+
+/// A fetched page. `url` is the final URL after redirects.
+#[derive(Debug, Clone)]
+pub struct FetchedPage {
+    pub url: String,
+    pub content_type: String,
+    pub body: String,
+}
+
+pub trait PageFetcher: Send + Sync {
+    /// Fetch an HTML page or JSON document
+    fn fetch<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<FetchedPage, FetchError>>;
+}
+
+/// Fetches pages over HTTP(S), refusing anything that resolves to a non-public address
+pub struct HttpPageFetcher;
+
+impl HttpPageFetcher {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<FetchedPage, FetchError> {
+        let mut url = Url::parse(url).map_err(|e| FetchError::Blocked(e.to_string()))?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            let addr = resolve_public_addr(&url).await?;
+            let host = url.host_str().unwrap_or_default().to_string();
+
+            // Connect to the address that was checked, so a second DNS lookup
+            // cannot point the request somewhere else
+            let client = reqwest::Client::builder()
+                .redirect(Policy::none())
+                .timeout(FETCH_TIMEOUT)
+                .user_agent(USER_AGENT)
+                .resolve(&host, addr)
+                .build()
+                .map_err(|e| FetchError::Request(e.to_string()))?;
+
+            let mut response = client
+                .get(url.clone())
+                .header("Accept", "text/html, application/xhtml+xml, application/json")
+                .send()
+                .await
+                .map_err(|e| FetchError::Request(e.to_string()))?;
+
+            let status = response.status();
+            if status.is_redirection() {
+                let location = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .ok_or_else(|| FetchError::Request("Redirect without location".to_string()))?;
+                url = url.join(location).map_err(|e| FetchError::Blocked(e.to_string()))?;
+                continue;
+            }
+            if !status.is_success() {
+                return Err(FetchError::Request(format!("Unexpected status {}", status)));
+            }
+
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            let supported = ["text/html", "application/xhtml+xml", "application/json", "application/json+oembed"]
+                .iter()
+                .any(|t| content_type.starts_with(t));
+            if !supported {
+                return Err(FetchError::UnsupportedContent(content_type));
+            }
+
+            let declared_length = response
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<usize>().ok());
+            let mut body = Vec::with_capacity(declared_length.unwrap_or(0).min(MAX_BODY_BYTES));
+            while let Some(chunk) = response
+                .chunk()
+                .await
+                .map_err(|e| FetchError::Request(e.to_string()))?
+            {
+                if !append_capped(&mut body, &chunk) {
+                    break;
+                }
+            }
+
+            return Ok(FetchedPage {
+                url: url.to_string(),
+                content_type,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+
+        Err(FetchError::Request("Too many redirects".to_string()))
+    }
+}
+
+impl Default for HttpPageFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PageFetcher for HttpPageFetcher {
+    fn fetch<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<FetchedPage, FetchError>> {
+        Box::pin(self.fetch_page(url))
+    }
+}
+
+/// Append a chunk of the body, up to [`MAX_BODY_BYTES`] in all.
+/// Returns false once the body is full.
+fn append_capped(body: &mut Vec<u8>, chunk: &[u8]) -> bool {
+    let remaining = MAX_BODY_BYTES - body.len();
+    if chunk.len() >= remaining {
+        body.extend_from_slice(&chunk[..remaining]);
+        return false;
+    }
+    body.extend_from_slice(chunk);
+    true
+}
+
+/// Resolve the URL's host, rejecting it unless every address it resolves to is public
+async fn resolve_public_addr(url: &Url) -> Result<SocketAddr, FetchError> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(FetchError::Blocked(format!("Scheme {} is not allowed", url.scheme())));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| FetchError::Blocked("Missing host".to_string()))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    // IPv6 literals come back in brackets
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| FetchError::Request(e.to_string()))?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(FetchError::Request(format!("{} did not resolve", host)));
+    }
+    if let Some(addr) = addrs.iter().find(|a| !is_public_ip(a.ip())) {
+        return Err(FetchError::Blocked(format!("{} resolves to {}", host, addr.ip())));
+    }
+
+    Ok(addrs[0])
+}
+
+/// Whether an address is reachable on the public internet, i.e. not loopback,
+/// private, link-local, carrier-grade NAT, multicast or otherwise reserved
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b == 18 || b == 19))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+
+    // 6to4 addresses route to the IPv4 address they embed
+    if segments[0] == 0x2002 {
+        let [a, b] = segments[1].to_be_bytes();
+        let [c, d] = segments[2].to_be_bytes();
+        return is_public_ipv4(Ipv4Addr::new(a, b, c, d));
+    }
+
+    let first = segments[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || first == 0x2001 && segments[1] == 0x0db8
+        // Teredo tunnels to an obfuscated IPv4 address, which could be a private one
+        || first == 0x2001 && segments[1] == 0x0000
+        || first == 0x0064 && segments[1] == 0xff9b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_public(ip: &str) -> bool {
+        is_public_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_public_addresses() {
+        assert!(is_public("93.184.216.34"));
+        assert!(is_public("2606:2800:220:1:248:1893:25c8:1946"));
+        assert!(is_public("::ffff:93.184.216.34"));
+        // 6to4 for 93.184.216.34
+        assert!(is_public("2002:5db8:d822::1"));
+    }
+
+    #[test]
+    fn test_non_public_addresses() {
+        let addresses = [
+            "0.0.0.0",
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "255.255.255.255",
+            "224.0.0.1",
+            "240.0.0.1",
+            "::",
+            "::1",
+            "fc00::1",
+            "fe80::1",
+            "ff02::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::a00:1",
+            // 6to4 for 127.0.0.1 and 169.254.169.254
+            "2002:7f00:1::1",
+            "2002:a9fe:a9fe::1",
+            "2001:0:4136:e378:8000:63bf:3fff:fdd2",
+        ];
+        for address in addresses {
+            assert!(!is_public(address), "{} should not be public", address);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_urls_to_private_hosts_are_blocked() {
+        for url in ["http://127.0.0.1/", "http://[::1]:8080/", "http://localhost/admin", "ftp://example.com/"] {
+            let result = resolve_public_addr(&Url::parse(url).unwrap()).await;
+            assert!(matches!(result, Err(FetchError::Blocked(_))), "{} should be blocked", url);
+        }
+
+        // The fetcher checks every hop, including the first, before connecting
+        let result = HttpPageFetcher::new().fetch("http://169.254.169.254/latest/meta-data").await;
+        assert!(matches!(result, Err(FetchError::Blocked(_))));
+    }
+
+    #[test]
+    fn test_body_is_capped() {
+        let mut body = Vec::new();
+        assert!(append_capped(&mut body, &[b'a'; 1024]));
+        assert!(!append_capped(&mut body, &vec![b'b'; MAX_BODY_BYTES]));
+        assert_eq!(body.len(), MAX_BODY_BYTES);
+        assert!(!append_capped(&mut body, b"more"));
+        assert_eq!(body.len(), MAX_BODY_BYTES);
+    }
+}
@@ -1,7 +1,11 @@
 //! Channel handlers
 
 use actix_web::{web, HttpRequest, HttpResponse};
-use shared::dto::{CreateChannelRequest, UpdateChannelRequest};
+use chrono::{DateTime, Utc};
+use shared::dto::{
+    CreateChannelRequest, ShareChannelRequest, UpdateChannelMemberSettingsRequest,
+    UpdateChannelRequest, WebSocketMessage,
+};
 use std::sync::Arc;
 use uuid::Uuid;
 use validator::Validate;
@@ -9,13 +13,23 @@ use validator::Validate;
 use crate::error::{ApiError, ApiResult};
 use crate::middleware::get_user_id_from_request;
 use crate::services::Services;
+use crate::websocket::WebSocketServer;
+
+#[derive(serde::Deserialize)]
+pub struct ListChannelsQuery {
+    include_archived: Option<bool>,
+}
 
 pub async fn list_channels(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
+    query: web::Query<ListChannelsQuery>,
 ) -> ApiResult<HttpResponse> {
     let user_id = get_user_id_from_request(&req, &services)?;
-    let channels = services.channels.list_user_channels(&user_id).await?;
+    let channels = services
+        .channels
+        .list_user_channels(&user_id, query.include_archived.unwrap_or(false))
+        .await?;
     Ok(HttpResponse::Ok().json(channels))
 }
 
@@ -23,11 +37,36 @@ pub async fn list_team_channels(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
     path: web::Path<Uuid>,
+    query: web::Query<ListChannelsQuery>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let team_id = path.into_inner();
+
+    let channels = services
+        .channels
+        .list_team_channels(&team_id, &user_id, query.include_archived.unwrap_or(false))
+        .await?;
+    Ok(HttpResponse::Ok().json(channels))
+}
+
+#[derive(serde::Deserialize)]
+pub struct BrowseChannelsQuery {
+    q: Option<String>,
+}
+
+pub async fn browse_team_channels(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    query: web::Query<BrowseChannelsQuery>,
 ) -> ApiResult<HttpResponse> {
     let user_id = get_user_id_from_request(&req, &services)?;
     let team_id = path.into_inner();
 
-    let channels = services.channels.list_team_channels(&team_id, &user_id).await?;
+    let channels = services
+        .channels
+        .browse_team_channels(&team_id, &user_id, query.q.as_deref())
+        .await?;
     Ok(HttpResponse::Ok().json(channels))
 }
 
@@ -61,15 +100,72 @@ pub async fn create_dm_channel(
 pub async fn get_channel(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
     path: web::Path<Uuid>,
 ) -> ApiResult<HttpResponse> {
     let user_id = get_user_id_from_request(&req, &services)?;
     let channel_id = path.into_inner();
 
+    // Opening a public channel joins it
+    if services.channels.auto_join(&channel_id, &user_id).await? {
+        broadcast_user_joined(&services, &ws_server, &channel_id, &user_id).await;
+    }
+
     let channel = services.channels.get_channel(&channel_id, &user_id).await?;
     Ok(HttpResponse::Ok().json(channel))
 }
 
+pub async fn join_channel(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let channel = services.channels.join_channel(&channel_id, &user_id).await?;
+    broadcast_user_joined(&services, &ws_server, &channel_id, &user_id).await;
+
+    Ok(HttpResponse::Ok().json(channel))
+}
+
+pub async fn leave_channel(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    services.channels.leave_channel(&channel_id, &user_id).await?;
+
+    ws_server.remove_from_channel(&channel_id, &user_id);
+    let ws_msg = WebSocketMessage::UserLeftChannel {
+        channel_id,
+        user_id,
+    };
+    ws_server.broadcast_to_channel(&channel_id, &ws_msg, Some(&user_id));
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn broadcast_user_joined(
+    services: &Services,
+    ws_server: &WebSocketServer,
+    channel_id: &Uuid,
+    user_id: &Uuid,
+) {
+    if let Ok(user) = services.users.get_user(user_id).await {
+        let ws_msg = WebSocketMessage::UserJoinedChannel {
+            channel_id: *channel_id,
+            user,
+        };
+        ws_server.broadcast_to_channel(channel_id, &ws_msg, Some(user_id));
+    }
+}
+
 pub async fn update_channel(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
@@ -100,6 +196,94 @@ pub async fn delete_channel(
     Ok(HttpResponse::NoContent().finish())
 }
 
+pub async fn archive_channel(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let channel = services.channels.archive_channel(&channel_id, &user_id).await?;
+    Ok(HttpResponse::Ok().json(channel))
+}
+
+pub async fn unarchive_channel(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let channel = services.channels.unarchive_channel(&channel_id, &user_id).await?;
+    Ok(HttpResponse::Ok().json(channel))
+}
+
+pub async fn list_shared_teams(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let teams = services.channels.list_shared_teams(&channel_id, &user_id).await?;
+    Ok(HttpResponse::Ok().json(teams))
+}
+
+pub async fn share_channel(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    body: web::Json<ShareChannelRequest>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let channel_team = services
+        .channels
+        .share_with_team(&channel_id, &user_id, &body.team_id)
+        .await?;
+    Ok(HttpResponse::Created().json(channel_team))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ChannelTeamPath {
+    channel_id: Uuid,
+    team_id: Uuid,
+}
+
+pub async fn accept_shared_channel(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<ChannelTeamPath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let channel_team = services
+        .channels
+        .accept_shared_channel(&params.channel_id, &user_id, &params.team_id)
+        .await?;
+    Ok(HttpResponse::Ok().json(channel_team))
+}
+
+pub async fn unshare_channel(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<ChannelTeamPath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    services
+        .channels
+        .unshare_with_team(&params.channel_id, &user_id, &params.team_id)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
 pub async fn list_channel_members(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
@@ -154,16 +338,92 @@ pub async fn remove_channel_member(
     Ok(HttpResponse::NoContent().finish())
 }
 
+pub async fn update_my_channel_settings(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateChannelMemberSettingsRequest>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let channel = services
+        .channels
+        .update_member_settings(&channel_id, &user_id, body.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(channel))
+}
+
+pub async fn add_channel_poster(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    body: web::Json<AddMemberRequest>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let member = services
+        .channels
+        .set_poster(&channel_id, &user_id, &body.user_id, true)
+        .await?;
+    Ok(HttpResponse::Ok().json(member))
+}
+
+pub async fn remove_channel_poster(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<ChannelMemberPath>,
+) -> ApiResult<HttpResponse> {
+    let requester_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    services
+        .channels
+        .set_poster(&params.channel_id, &requester_id, &params.user_id, false)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
 pub async fn mark_as_read(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
     path: web::Path<Uuid>,
 ) -> ApiResult<HttpResponse> {
     let user_id = get_user_id_from_request(&req, &services)?;
     let channel_id = path.into_inner();
 
-    services.channels.mark_as_read(&channel_id, &user_id).await?;
+    let last_read_at = services.channels.mark_as_read(&channel_id, &user_id).await?;
+    broadcast_read_receipt(&services, &ws_server, &channel_id, &user_id, last_read_at).await?;
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Channel marked as read"
     })))
 }
+
+/// Share a member's new read position with the subscribers who may see it
+pub async fn broadcast_read_receipt(
+    services: &Services,
+    ws_server: &WebSocketServer,
+    channel_id: &Uuid,
+    user_id: &Uuid,
+    last_read_at: DateTime<Utc>,
+) -> ApiResult<()> {
+    let recipients = services.messages.read_receipt_recipients(channel_id, user_id).await?;
+    if recipients.is_empty() {
+        return Ok(());
+    }
+
+    let ws_msg = WebSocketMessage::ReadReceipt {
+        channel_id: *channel_id,
+        user_id: *user_id,
+        last_read_at,
+    };
+    let subscribers = ws_server.get_online_users_in_channel(channel_id);
+    for recipient in recipients.iter().filter(|id| subscribers.contains(id)) {
+        ws_server.send_to_user(recipient, &ws_msg);
+    }
+
+    Ok(())
+}
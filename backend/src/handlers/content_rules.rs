@@ -0,0 +1,119 @@
+//! Content rule and moderation queue handlers
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use shared::dto::{CreateContentRuleRequest, PaginationParams, UpdateContentRuleRequest};
+use shared::pagination::{page_size, Cursor};
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::error::{ApiError, ApiResult};
+use crate::middleware::get_user_id_from_request;
+use crate::services::Services;
+
+#[derive(serde::Deserialize)]
+pub struct ContentRulePath {
+    team_id: Uuid,
+    rule_id: Uuid,
+}
+
+#[derive(serde::Deserialize)]
+pub struct FlaggedMessagePath {
+    team_id: Uuid,
+    flag_id: Uuid,
+}
+
+pub async fn list_content_rules(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let team_id = path.into_inner();
+
+    let rules = services.content_rules.list_rules(&team_id, &user_id).await?;
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+pub async fn create_content_rule(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateContentRuleRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let team_id = path.into_inner();
+
+    let rule = services
+        .content_rules
+        .create_rule(&team_id, &user_id, body.into_inner())
+        .await?;
+    Ok(HttpResponse::Created().json(rule))
+}
+
+pub async fn update_content_rule(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<ContentRulePath>,
+    body: web::Json<UpdateContentRuleRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let rule = services
+        .content_rules
+        .update_rule(&params.team_id, &params.rule_id, &user_id, body.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+pub async fn delete_content_rule(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<ContentRulePath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    services
+        .content_rules
+        .delete_rule(&params.team_id, &params.rule_id, &user_id)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn list_moderation_queue(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    query: web::Query<PaginationParams>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let team_id = path.into_inner();
+    let cursor = Cursor::parse(query.cursor.as_deref())?;
+
+    let flagged = services
+        .messages
+        .list_flagged_messages(&team_id, &user_id, page_size(query.limit, 50), cursor)
+        .await?;
+    Ok(HttpResponse::Ok().json(flagged))
+}
+
+pub async fn resolve_flagged_message(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<FlaggedMessagePath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    services
+        .messages
+        .resolve_flagged_message(&params.team_id, &params.flag_id, &user_id)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,120 @@
+//! Draft handlers
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use shared::dto::{SaveDraftRequest, WebSocketMessage};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::error::{ApiError, ApiResult};
+use crate::middleware::get_user_id_from_request;
+use crate::services::Services;
+use crate::websocket::WebSocketServer;
+
+pub async fn list_drafts(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let drafts = services.drafts.list_drafts(&user_id).await?;
+    Ok(HttpResponse::Ok().json(drafts))
+}
+
+pub async fn save_draft(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<Uuid>,
+    body: web::Json<SaveDraftRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let draft = services
+        .drafts
+        .save_draft(&channel_id, &user_id, body.into_inner())
+        .await?;
+
+    // Sync to the user's other devices
+    let ws_msg = WebSocketMessage::DraftUpdated { draft: draft.clone() };
+    ws_server.send_to_user(&user_id, &ws_msg);
+
+    Ok(HttpResponse::Ok().json(draft))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ClearDraftQuery {
+    thread_root_id: Option<Uuid>,
+}
+
+pub async fn clear_draft(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<Uuid>,
+    query: web::Query<ClearDraftQuery>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let draft = services
+        .drafts
+        .clear_draft(&channel_id, &user_id, query.thread_root_id.as_ref())
+        .await?;
+
+    let ws_msg = WebSocketMessage::DraftUpdated { draft };
+    ws_server.send_to_user(&user_id, &ws_msg);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Sending a message clears the sender's draft for that channel or thread on all of their devices
+pub async fn clear_sent_draft(
+    services: &Services,
+    ws_server: &WebSocketServer,
+    user_id: &Uuid,
+    channel_id: &Uuid,
+    thread_root_id: Option<&Uuid>,
+) {
+    match services.drafts.clear_draft(channel_id, user_id, thread_root_id).await {
+        Ok(draft) => ws_server.send_to_user(user_id, &WebSocketMessage::DraftUpdated { draft }),
+        Err(e) => warn!("Failed to clear draft for user {} in channel {}: {}", user_id, channel_id, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::db::testing;
+    use shared::models::ChannelType;
+
+    #[tokio::test]
+    async fn test_sending_clears_the_draft_on_every_device() {
+        let Some(pool) = testing::pool().await else { return };
+        let alice = testing::user(&pool, "alice").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &alice, &[]).await;
+        let services = Services::new(pool, AppConfig::load().unwrap());
+        let ws_server = WebSocketServer::new();
+        let mut rx = ws_server.register(alice.id);
+
+        let request = SaveDraftRequest {
+            content: "almost done".to_string(),
+            thread_root_id: None,
+        };
+        services.drafts.save_draft(&channel.id, &alice.id, request).await.unwrap();
+
+        clear_sent_draft(&services, &ws_server, &alice.id, &channel.id, None).await;
+        assert!(services.drafts.list_drafts(&alice.id).await.unwrap().is_empty());
+        match serde_json::from_str(&rx.try_recv().unwrap()).unwrap() {
+            WebSocketMessage::DraftUpdated { draft } => {
+                assert_eq!(draft.channel_id, channel.id);
+                assert!(draft.content.is_empty());
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+}
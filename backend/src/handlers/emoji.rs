@@ -0,0 +1,103 @@
+//! Custom emoji handlers
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use shared::dto::{CreateCustomEmojiRequest, UpdateCustomEmojiRequest};
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::error::{ApiError, ApiResult};
+use crate::middleware::get_user_id_from_request;
+use crate::services::Services;
+
+#[derive(serde::Deserialize)]
+pub struct EmojiPath {
+    team_id: Uuid,
+    emoji_id: Uuid,
+}
+
+pub async fn list_emoji(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let team_id = path.into_inner();
+
+    let emoji = services.emoji.list_emoji(&team_id, &user_id).await?;
+    Ok(HttpResponse::Ok().json(emoji))
+}
+
+pub async fn create_emoji(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreateCustomEmojiRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let team_id = path.into_inner();
+
+    let emoji = services
+        .emoji
+        .create_emoji(&team_id, &user_id, body.into_inner())
+        .await?;
+    Ok(HttpResponse::Created().json(emoji))
+}
+
+pub async fn update_emoji(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<EmojiPath>,
+    body: web::Json<UpdateCustomEmojiRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let emoji = services
+        .emoji
+        .rename_emoji(&params.team_id, &params.emoji_id, &user_id, body.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(emoji))
+}
+
+pub async fn delete_emoji(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<EmojiPath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    services
+        .emoji
+        .delete_emoji(&params.team_id, &params.emoji_id, &user_id)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_emoji_image(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<EmojiPath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let (file_path, mime_type) = services
+        .emoji
+        .get_emoji_image(&params.team_id, &params.emoji_id, &user_id)
+        .await?;
+
+    let file_content = tokio::fs::read(&file_path).await.map_err(|e| {
+        crate::error::ApiError(shared::error::AppError::InternalError(e.to_string()))
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(mime_type)
+        .insert_header(("Cache-Control", "private, max-age=86400"))
+        .body(file_content))
+}
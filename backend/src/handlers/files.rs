@@ -3,6 +3,8 @@
 use actix_multipart::Multipart;
 use actix_web::{web, HttpRequest, HttpResponse};
 use futures_util::StreamExt;
+use shared::dto::PaginationParams;
+use shared::pagination::{page_size, Cursor};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -118,6 +120,23 @@ pub async fn download_file(
         .body(file_content))
 }
 
+pub async fn list_channel_files(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    query: web::Query<PaginationParams>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+    let cursor = Cursor::parse(query.cursor.as_deref())?;
+
+    let files = services
+        .files
+        .list_channel_files(&channel_id, &user_id, page_size(query.limit, 50), cursor)
+        .await?;
+    Ok(HttpResponse::Ok().json(files))
+}
+
 pub async fn delete_file(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
@@ -1,17 +1,23 @@
 //! Health check handler
 
-use actix_web::{HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder};
 use serde::Serialize;
+use std::sync::Arc;
+
+use crate::services::retention::RetentionMetrics;
+use crate::services::Services;
 
 #[derive(Serialize)]
 struct HealthResponse {
     status: String,
     version: String,
+    retention: RetentionMetrics,
 }
 
-pub async fn health_check() -> impl Responder {
+pub async fn health_check(services: web::Data<Arc<Services>>) -> impl Responder {
     HttpResponse::Ok().json(HealthResponse {
         status: "healthy".to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
+        retention: services.retention.metrics(),
     })
 }
@@ -1,42 +1,38 @@
 //! Message handlers
 
 use actix_web::{web, HttpRequest, HttpResponse};
-use chrono::{DateTime, Utc};
-use shared::dto::{AddReactionRequest, SendMessageRequest, UpdateMessageRequest, WebSocketMessage};
+use chrono::Utc;
+use shared::dto::{
+    AddReactionRequest, CreatePollRequest, EphemeralMessageResponse, ForwardMessageRequest, MessageResponse,
+    PaginationParams, PollResponse, PollVoteRequest, SendEphemeralMessageRequest, SendMessageRequest,
+    UpdateMessageRequest, UpdateScheduledMessageRequest, WebSocketMessage,
+};
+use shared::models::MessageFormat;
+use shared::pagination::{page_size, Cursor};
 use std::sync::Arc;
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::error::{ApiError, ApiResult};
+use crate::handlers::channels::broadcast_read_receipt;
+use crate::handlers::drafts::clear_sent_draft;
 use crate::middleware::get_user_id_from_request;
 use crate::services::Services;
 use crate::websocket::WebSocketServer;
 
-#[derive(serde::Deserialize)]
-pub struct ListMessagesQuery {
-    limit: Option<i64>,
-    before: Option<DateTime<Utc>>,
-    after: Option<DateTime<Utc>>,
-}
-
 pub async fn list_messages(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
     path: web::Path<Uuid>,
-    query: web::Query<ListMessagesQuery>,
+    query: web::Query<PaginationParams>,
 ) -> ApiResult<HttpResponse> {
     let user_id = get_user_id_from_request(&req, &services)?;
     let channel_id = path.into_inner();
+    let cursor = Cursor::parse(query.cursor.as_deref())?;
 
     let messages = services
         .messages
-        .list_messages(
-            &channel_id,
-            &user_id,
-            query.limit.unwrap_or(50),
-            query.before,
-            query.after,
-        )
+        .list_messages(&channel_id, &user_id, page_size(query.limit, 50), cursor)
         .await?;
 
     Ok(HttpResponse::Ok().json(messages))
@@ -54,25 +50,278 @@ pub async fn send_message(
     let user_id = get_user_id_from_request(&req, &services)?;
     let channel_id = path.into_inner();
 
-    let message = services
+    let reply_to_id = body.reply_to_id;
+    let client_nonce = body.client_nonce.clone();
+
+    if let Some(send_at) = body.send_at.filter(|send_at| *send_at > Utc::now()) {
+        // A schedule request retried after the message went out gets the posted message
+        if let Some(nonce) = &client_nonce {
+            if let Some(message) = services.messages.find_sent_message(&channel_id, &user_id, nonce).await? {
+                return Ok(HttpResponse::Ok().json(message));
+            }
+        }
+
+        let scheduled = services
+            .messages
+            .schedule_message(&channel_id, &user_id, body.into_inner(), send_at)
+            .await?;
+        clear_sent_draft(&services, &ws_server, &user_id, &channel_id, reply_to_id.as_ref()).await;
+        return Ok(HttpResponse::Accepted().json(scheduled));
+    }
+
+    let (message, posted) = services
         .messages
-        .send_message(&channel_id, &user_id, body.into_inner())
+        .send_message_once(&channel_id, &user_id, body.into_inner())
         .await?;
 
+    // A retried send gets the message stored the first time
+    if !posted {
+        return Ok(HttpResponse::Ok().json(message));
+    }
+
     // Broadcast new message to all channel subscribers except sender
     tracing::info!("Broadcasting NewMessage to channel {} (excluding sender {})", channel_id, user_id);
-    let ws_msg = WebSocketMessage::NewMessage { message: message.clone() };
-    ws_server.broadcast_to_channel(&channel_id, &ws_msg, Some(&user_id));
+    broadcast_new_message(&ws_server, &channel_id, &message, client_nonce, Some(&user_id));
+
+    clear_sent_draft(&services, &ws_server, &user_id, &channel_id, reply_to_id.as_ref()).await;
+    spawn_message_notifications(services.get_ref().clone(), ws_server.get_ref().clone(), message.clone());
+    spawn_link_previews(services.get_ref().clone(), ws_server.get_ref().clone(), message.clone());
+
+    Ok(HttpResponse::Created().json(message))
+}
+
+/// Push a new message to the channel's subscribers in the view any member may see.
+/// The sender's own view can embed a forwarded original others cannot access.
+pub fn broadcast_new_message(
+    ws_server: &WebSocketServer,
+    channel_id: &Uuid,
+    message: &MessageResponse,
+    client_nonce: Option<String>,
+    exclude_user: Option<&Uuid>,
+) {
+    let ws_msg = WebSocketMessage::NewMessage { message: message.clone().for_broadcast(), client_nonce };
+    ws_server.broadcast_to_channel(channel_id, &ws_msg, exclude_user);
+}
+
+/// Push a changed message to the channel's subscribers in the view any member may see
+pub fn broadcast_message_updated(ws_server: &WebSocketServer, message: &MessageResponse) {
+    let ws_msg = WebSocketMessage::MessageUpdated { message: message.clone().for_broadcast() };
+    ws_server.broadcast_to_channel(&message.channel_id, &ws_msg, None);
+}
+
+/// Create notifications for a new message in the background and push them to recipients
+pub fn spawn_message_notifications(
+    services: Arc<Services>,
+    ws_server: Arc<WebSocketServer>,
+    message: MessageResponse,
+) {
+    // @here only reaches members who are connected right now
+    let online_user_ids = ws_server.get_online_users_in_channel(&message.channel_id);
+
+    actix_rt::spawn(async move {
+        match services
+            .notifications
+            .notify_channel_message(&message, &online_user_ids)
+            .await
+        {
+            Ok(notifications) => {
+                for (user_id, notification) in notifications {
+                    let ws_msg = WebSocketMessage::Notification { notification };
+                    ws_server.send_to_user(&user_id, &ws_msg);
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Failed to create notifications for message {}: {}", message.id, e);
+            }
+        }
+    });
+}
+
+/// Send an "only visible to you" message to one member of the channel, for moderators'
+/// bots and slash commands
+pub async fn post_ephemeral_message(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<Uuid>,
+    body: web::Json<SendEphemeralMessageRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+    let recipient_id = body.user_id;
+
+    let message = services
+        .messages
+        .create_ephemeral_message(&channel_id, &user_id, body.into_inner())
+        .await?;
+
+    let ws_msg = WebSocketMessage::EphemeralMessage { message: message.clone() };
+    ws_server.send_to_user(&recipient_id, &ws_msg);
 
     Ok(HttpResponse::Created().json(message))
 }
 
+/// Show a system message to a single user in a channel, without storing it in history
+pub fn send_ephemeral_message(ws_server: &WebSocketServer, channel_id: &Uuid, user_id: &Uuid, content: String) {
+    let ws_msg = WebSocketMessage::EphemeralMessage {
+        message: EphemeralMessageResponse {
+            id: Uuid::new_v4(),
+            channel_id: *channel_id,
+            sender_id: None,
+            content,
+            format: MessageFormat::Plain,
+            created_at: Utc::now(),
+        },
+    };
+    ws_server.send_to_user(user_id, &ws_msg);
+}
+
+/// Fetch previews for the links in a message in the background, and push the
+/// updated message to the channel once they are attached
+pub fn spawn_link_previews(
+    services: Arc<Services>,
+    ws_server: Arc<WebSocketServer>,
+    message: MessageResponse,
+) {
+    // Links in self-destructing messages are not fetched, so they don't leave the server
+    if message.expires_at.is_some() {
+        return;
+    }
+
+    actix_rt::spawn(async move {
+        match services
+            .link_previews
+            .unfurl_message(&message.id, &message.content)
+            .await
+        {
+            Ok(true) => match services.messages.get_message(&message.id, &message.sender.id).await {
+                Ok(updated) => broadcast_message_updated(&ws_server, &updated),
+                Err(e) => {
+                    tracing::warn!("Failed to load message {} after adding previews: {}", message.id, e);
+                }
+            },
+            Ok(false) => {}
+            Err(e) => {
+                tracing::warn!("Failed to create link previews for message {}: {}", message.id, e);
+            }
+        }
+    });
+}
+
+pub async fn list_scheduled_messages(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let scheduled = services.messages.list_scheduled_messages(&user_id).await?;
+    Ok(HttpResponse::Ok().json(scheduled))
+}
+
+pub async fn update_scheduled_message(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateScheduledMessageRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let scheduled_id = path.into_inner();
+
+    let scheduled = services
+        .messages
+        .update_scheduled_message(&scheduled_id, &user_id, body.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(scheduled))
+}
+
+pub async fn cancel_scheduled_message(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let scheduled_id = path.into_inner();
+
+    services
+        .messages
+        .cancel_scheduled_message(&scheduled_id, &user_id)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn retry_scheduled_message(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let scheduled_id = path.into_inner();
+
+    let scheduled = services
+        .messages
+        .retry_scheduled_message(&scheduled_id, &user_id)
+        .await?;
+    Ok(HttpResponse::Ok().json(scheduled))
+}
+
 #[derive(serde::Deserialize)]
 pub struct MessagePath {
     channel_id: Uuid,
     message_id: Uuid,
 }
 
+pub async fn get_seen_by(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<MessagePath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let users = services
+        .messages
+        .get_seen_by(&params.channel_id, &params.message_id, &user_id)
+        .await?;
+    Ok(HttpResponse::Ok().json(users))
+}
+
+pub async fn get_message_history(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<MessagePath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let history = services
+        .messages
+        .get_message_history(&params.channel_id, &params.message_id, &user_id)
+        .await?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+pub async fn mark_as_unread(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<MessagePath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let last_read_at = services
+        .channels
+        .mark_as_unread(&params.channel_id, &params.message_id, &user_id)
+        .await?;
+    broadcast_read_receipt(&services, &ws_server, &params.channel_id, &user_id, last_read_at).await?;
+
+    let channel = services.channels.get_channel(&params.channel_id, &user_id).await?;
+    Ok(HttpResponse::Ok().json(channel))
+}
+
 pub async fn update_message(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
@@ -87,34 +336,44 @@ pub async fn update_message(
 
     let message = services
         .messages
-        .update_message(&params.message_id, &user_id, body.into_inner())
+        .update_message(&params.channel_id, &params.message_id, &user_id, body.into_inner())
         .await?;
 
     // Broadcast updated message to all channel subscribers
-    let ws_msg = WebSocketMessage::MessageUpdated { message: message.clone() };
-    ws_server.broadcast_to_channel(&params.channel_id, &ws_msg, None);
+    broadcast_message_updated(&ws_server, &message);
+
+    // Links may have been added or removed by the edit
+    spawn_link_previews(services.get_ref().clone(), ws_server.get_ref().clone(), message.clone());
 
     Ok(HttpResponse::Ok().json(message))
 }
 
+#[derive(serde::Deserialize)]
+pub struct DeleteMessageQuery {
+    /// Required when a moderator removes someone else's message
+    reason: Option<String>,
+}
+
 pub async fn delete_message(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
     ws_server: web::Data<Arc<WebSocketServer>>,
     path: web::Path<MessagePath>,
+    query: web::Query<DeleteMessageQuery>,
 ) -> ApiResult<HttpResponse> {
     let user_id = get_user_id_from_request(&req, &services)?;
     let params = path.into_inner();
 
-    services
+    let moderated = services
         .messages
-        .delete_message(&params.message_id, &user_id)
+        .delete_message(&params.channel_id, &params.message_id, &user_id, query.reason.as_deref())
         .await?;
 
     // Broadcast message deletion to all channel subscribers
     let ws_msg = WebSocketMessage::MessageDeleted {
         channel_id: params.channel_id,
         message_id: params.message_id,
+        moderated,
     };
     ws_server.broadcast_to_channel(&params.channel_id, &ws_msg, None);
 
@@ -124,17 +383,28 @@ pub async fn delete_message(
 pub async fn add_reaction(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
     path: web::Path<MessagePath>,
     body: web::Json<AddReactionRequest>,
 ) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
     let user_id = get_user_id_from_request(&req, &services)?;
     let params = path.into_inner();
 
     services
         .messages
-        .add_reaction(&params.message_id, &user_id, &body.emoji)
+        .add_reaction(&params.channel_id, &params.message_id, &user_id, &body.emoji)
         .await?;
 
+    let ws_msg = WebSocketMessage::ReactionAdded {
+        channel_id: params.channel_id,
+        message_id: params.message_id,
+        emoji: body.emoji.clone(),
+        user_id,
+    };
+    ws_server.broadcast_to_channel(&params.channel_id, &ws_msg, None);
+
     Ok(HttpResponse::Created().json(serde_json::json!({
         "message": "Reaction added"
     })))
@@ -150,15 +420,224 @@ pub struct ReactionPath {
 pub async fn remove_reaction(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
     path: web::Path<ReactionPath>,
 ) -> ApiResult<HttpResponse> {
     let user_id = get_user_id_from_request(&req, &services)?;
     let params = path.into_inner();
 
-    services
+    let removed = services
         .messages
-        .remove_reaction(&params.message_id, &user_id, &params.emoji)
+        .remove_reaction(&params.channel_id, &params.message_id, &user_id, &params.emoji)
         .await?;
 
+    if removed {
+        let ws_msg = WebSocketMessage::ReactionRemoved {
+            channel_id: params.channel_id,
+            message_id: params.message_id,
+            emoji: params.emoji,
+            user_id,
+        };
+        ws_server.broadcast_to_channel(&params.channel_id, &ws_msg, None);
+    }
+
     Ok(HttpResponse::NoContent().finish())
 }
+
+pub async fn forward_message(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<MessagePath>,
+    body: web::Json<ForwardMessageRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let message = services
+        .messages
+        .forward_message(&params.channel_id, &params.message_id, &user_id, body.into_inner())
+        .await?;
+
+    broadcast_new_message(&ws_server, &message.channel_id, &message, None, Some(&user_id));
+
+    spawn_message_notifications(services.get_ref().clone(), ws_server.get_ref().clone(), message.clone());
+    spawn_link_previews(services.get_ref().clone(), ws_server.get_ref().clone(), message.clone());
+
+    Ok(HttpResponse::Created().json(message))
+}
+
+pub async fn create_poll(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<Uuid>,
+    body: web::Json<CreatePollRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let message = services
+        .messages
+        .create_poll(&channel_id, &user_id, body.into_inner())
+        .await?;
+
+    broadcast_new_message(&ws_server, &channel_id, &message, None, Some(&user_id));
+
+    spawn_message_notifications(services.get_ref().clone(), ws_server.get_ref().clone(), message.clone());
+
+    Ok(HttpResponse::Created().json(message))
+}
+
+pub async fn vote_poll(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<MessagePath>,
+    body: web::Json<PollVoteRequest>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let poll = services
+        .messages
+        .vote_poll(&params.channel_id, &params.message_id, &user_id, &body.option_id)
+        .await?;
+
+    broadcast_poll_update(&ws_server, &params.channel_id, &params.message_id, &poll);
+
+    Ok(HttpResponse::Ok().json(poll))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PollVotePath {
+    channel_id: Uuid,
+    message_id: Uuid,
+    option_id: Uuid,
+}
+
+pub async fn retract_poll_vote(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    ws_server: web::Data<Arc<WebSocketServer>>,
+    path: web::Path<PollVotePath>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let params = path.into_inner();
+
+    let poll = services
+        .messages
+        .retract_poll_vote(&params.channel_id, &params.message_id, &user_id, &params.option_id)
+        .await?;
+
+    broadcast_poll_update(&ws_server, &params.channel_id, &params.message_id, &poll);
+
+    Ok(HttpResponse::Ok().json(poll))
+}
+
+/// Push new tallies to the channel. The voter's own choices stay out of the broadcast.
+fn broadcast_poll_update(ws_server: &WebSocketServer, channel_id: &Uuid, message_id: &Uuid, poll: &PollResponse) {
+    let ws_msg = WebSocketMessage::PollUpdated {
+        channel_id: *channel_id,
+        message_id: *message_id,
+        options: poll.options.clone(),
+        total_voters: poll.total_voters,
+    };
+    ws_server.broadcast_to_channel(channel_id, &ws_msg, None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::services::messages::MessageService;
+    use shared::dto::ForwardedMessageResponse;
+    use shared::models::{ChannelType, TeamRole};
+
+    #[tokio::test]
+    async fn test_forward_broadcasts_hide_the_original() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let source = testing::channel(&pool, Some(&team), ChannelType::Private, &owner, &[]).await;
+        let target = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&member]).await;
+        let service = MessageService::new(Arc::new(pool));
+
+        let request = SendMessageRequest {
+            content: "the secret plan".to_string(),
+            ..Default::default()
+        };
+        let original = service.send_message(&source.id, &owner.id, request).await.unwrap();
+        let request = ForwardMessageRequest {
+            target_channel_id: target.id,
+            comment: None,
+            format: None,
+        };
+        let message = service
+            .forward_message(&source.id, &original.id, &owner.id, request)
+            .await
+            .unwrap();
+        assert!(message.forwarded.as_ref().unwrap().accessible);
+
+        // The member can't access the source channel, so the broadcasts leave the original out
+        let ws_server = WebSocketServer::new();
+        let mut rx = ws_server.register(member.id);
+        ws_server.subscribe_to_channel(target.id, member.id, Uuid::new_v4());
+        broadcast_new_message(&ws_server, &target.id, &message, None, Some(&owner.id));
+        broadcast_message_updated(&ws_server, &message);
+
+        for _ in 0..2 {
+            let json = rx.try_recv().unwrap();
+            assert!(!json.contains("the secret plan"));
+            let received = match serde_json::from_str(&json).unwrap() {
+                WebSocketMessage::NewMessage { message, .. } | WebSocketMessage::MessageUpdated { message } => message,
+                other => panic!("unexpected broadcast {:?}", other),
+            };
+            assert_eq!(received.forwarded, Some(ForwardedMessageResponse::collapsed()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_broadcasts_leave_out_the_actors_votes_and_reactions() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let member = testing::user(&pool, "member").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &owner, &[&member]).await;
+        let service = MessageService::new(Arc::new(pool));
+
+        let request = CreatePollRequest {
+            question: "Lunch?".to_string(),
+            options: vec!["yes".to_string(), "no".to_string()],
+            ..Default::default()
+        };
+        let poll = service.create_poll(&channel.id, &owner.id, request).await.unwrap();
+        let option_id = poll.poll.as_ref().unwrap().options[0].id;
+        service.vote_poll(&channel.id, &poll.id, &owner.id, &option_id).await.unwrap();
+        service.add_reaction(&channel.id, &poll.id, &owner.id, "👍").await.unwrap();
+
+        let listed = service.list_messages(&channel.id, &owner.id, 50, None).await.unwrap();
+        let message = listed.items.into_iter().next().unwrap();
+        assert!(message.reactions[0].reacted_by_me);
+        assert_eq!(message.poll.as_ref().unwrap().my_votes, vec![option_id]);
+
+        let ws_server = WebSocketServer::new();
+        let mut rx = ws_server.register(member.id);
+        ws_server.subscribe_to_channel(channel.id, member.id, Uuid::new_v4());
+        broadcast_message_updated(&ws_server, &message);
+
+        let received = match serde_json::from_str(&rx.try_recv().unwrap()).unwrap() {
+            WebSocketMessage::MessageUpdated { message } => message,
+            other => panic!("unexpected broadcast {:?}", other),
+        };
+        assert_eq!(received.reactions[0].count, 1);
+        assert!(!received.reactions[0].reacted_by_me);
+        let poll = received.poll.unwrap();
+        assert_eq!(poll.total_voters, 1);
+        assert!(poll.my_votes.is_empty());
+    }
+}
@@ -3,11 +3,15 @@
 pub mod auth;
 pub mod calls;
 pub mod channels;
+pub mod content_rules;
+pub mod drafts;
+pub mod emoji;
 pub mod files;
 pub mod health;
 pub mod meetings;
 pub mod messages;
 pub mod notifications;
+pub mod retention;
 pub mod search;
 pub mod teams;
 pub mod users;
@@ -1,6 +1,7 @@
 //! Notification handlers
 
 use actix_web::{web, HttpRequest, HttpResponse};
+use shared::pagination::{page_size, Cursor};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -12,7 +13,7 @@ use crate::services::Services;
 pub struct ListNotificationsQuery {
     unread_only: Option<bool>,
     limit: Option<i64>,
-    offset: Option<i64>,
+    cursor: Option<String>,
 }
 
 pub async fn list_notifications(
@@ -21,14 +22,15 @@ pub async fn list_notifications(
     query: web::Query<ListNotificationsQuery>,
 ) -> ApiResult<HttpResponse> {
     let user_id = get_user_id_from_request(&req, &services)?;
+    let cursor = Cursor::parse(query.cursor.as_deref())?;
 
     let notifications = services
         .notifications
         .list_notifications(
             &user_id,
             query.unread_only.unwrap_or(false),
-            query.limit.unwrap_or(50),
-            query.offset.unwrap_or(0),
+            page_size(query.limit, 50),
+            cursor,
         )
         .await?;
 
@@ -0,0 +1,71 @@
+//! Message retention handlers
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use shared::dto::UpdateRetentionPolicyRequest;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::error::{ApiError, ApiResult};
+use crate::middleware::get_user_id_from_request;
+use crate::services::Services;
+
+pub async fn get_team_retention(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let team_id = path.into_inner();
+
+    let policy = services.retention.get_team_policy(&team_id, &user_id).await?;
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+pub async fn update_team_retention(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateRetentionPolicyRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let team_id = path.into_inner();
+
+    let policy = services
+        .retention
+        .update_team_policy(&team_id, &user_id, body.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+pub async fn get_channel_retention(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let policy = services.retention.get_channel_policy(&channel_id, &user_id).await?;
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+pub async fn update_channel_retention(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateRetentionPolicyRequest>,
+) -> ApiResult<HttpResponse> {
+    body.validate().map_err(ApiError::from)?;
+
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let channel_id = path.into_inner();
+
+    let policy = services
+        .retention
+        .update_channel_policy(&channel_id, &user_id, body.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(policy))
+}
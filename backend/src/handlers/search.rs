@@ -2,7 +2,7 @@
 
 use actix_web::{web, HttpRequest, HttpResponse};
 use chrono::{DateTime, Utc};
-use shared::dto::SearchResponse;
+use shared::pagination::{page_size, Cursor};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -18,7 +18,7 @@ pub struct SearchQuery {
     from_date: Option<DateTime<Utc>>,
     to_date: Option<DateTime<Utc>>,
     limit: Option<i64>,
-    offset: Option<i64>,
+    cursor: Option<String>,
 }
 
 pub async fn search_messages(
@@ -27,8 +27,9 @@ pub async fn search_messages(
     query: web::Query<SearchQuery>,
 ) -> ApiResult<HttpResponse> {
     let user_id = get_user_id_from_request(&req, &services)?;
+    let cursor = Cursor::parse(query.cursor.as_deref())?;
 
-    let (messages, total_count) = services
+    let messages = services
         .messages
         .search_messages(
             &user_id,
@@ -37,13 +38,10 @@ pub async fn search_messages(
             query.from_user_id.as_ref(),
             query.from_date,
             query.to_date,
-            query.limit.unwrap_or(20),
-            query.offset.unwrap_or(0),
+            page_size(query.limit, 20),
+            cursor,
         )
         .await?;
 
-    Ok(HttpResponse::Ok().json(SearchResponse {
-        messages,
-        total_count,
-    }))
+    Ok(HttpResponse::Ok().json(messages))
 }
@@ -1,7 +1,10 @@
 //! Team handlers
 
 use actix_web::{web, HttpRequest, HttpResponse};
-use shared::dto::{AddTeamMemberRequest, CreateTeamRequest, UpdateTeamMemberRequest, UpdateTeamRequest};
+use shared::dto::{
+    AddTeamMemberRequest, CreateTeamRequest, PaginationParams, UpdateTeamMemberRequest, UpdateTeamRequest,
+};
+use shared::pagination::{page_size, Cursor};
 use std::sync::Arc;
 use uuid::Uuid;
 use validator::Validate;
@@ -151,3 +154,20 @@ pub async fn remove_team_member(
         .await?;
     Ok(HttpResponse::NoContent().finish())
 }
+
+pub async fn get_moderation_log(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    path: web::Path<Uuid>,
+    query: web::Query<PaginationParams>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let team_id = path.into_inner();
+    let cursor = Cursor::parse(query.cursor.as_deref())?;
+
+    let records = services
+        .teams
+        .get_moderation_log(&team_id, &user_id, page_size(query.limit, 50), cursor)
+        .await?;
+    Ok(HttpResponse::Ok().json(records))
+}
@@ -1,7 +1,7 @@
 //! User handlers
 
 use actix_web::{web, HttpRequest, HttpResponse};
-use shared::dto::{ChangePasswordRequest, UpdateUserRequest};
+use shared::dto::{ChangePasswordRequest, UpdatePrivacySettingsRequest, UpdateUserRequest};
 use std::sync::Arc;
 use uuid::Uuid;
 use validator::Validate;
@@ -49,6 +49,28 @@ pub async fn change_password(
     })))
 }
 
+pub async fn get_privacy_settings(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let settings = services.users.get_privacy_settings(&user_id).await?;
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+pub async fn update_privacy_settings(
+    req: HttpRequest,
+    services: web::Data<Arc<Services>>,
+    body: web::Json<UpdatePrivacySettingsRequest>,
+) -> ApiResult<HttpResponse> {
+    let user_id = get_user_id_from_request(&req, &services)?;
+    let settings = services
+        .users
+        .update_privacy_settings(&user_id, body.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(settings))
+}
+
 pub async fn get_user(
     req: HttpRequest,
     services: web::Data<Arc<Services>>,
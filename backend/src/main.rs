@@ -13,9 +13,11 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 mod config;
 mod db;
 mod error;
+mod fetcher;
 mod handlers;
 pub mod middleware;
 mod services;
+mod tasks;
 mod websocket;
 
 use crate::config::AppConfig;
@@ -77,6 +79,11 @@ async fn main() -> std::io::Result<()> {
     // Initialize WebSocket server
     let ws_server = Arc::new(WebSocketServer::new());
 
+    // Start background tasks
+    tasks::spawn_scheduled_message_dispatcher(services.clone(), ws_server.clone());
+    tasks::spawn_expired_message_purger(services.clone(), ws_server.clone());
+    tasks::spawn_retention_purger(services.clone(), config.retention.purge_interval);
+
     // Create upload directory if it doesn't exist
     tokio::fs::create_dir_all(&config.storage.upload_path)
         .await
@@ -121,6 +128,13 @@ async fn main() -> std::io::Result<()> {
                             .route("/me", web::get().to(handlers::users::get_current_user))
                             .route("/me", web::patch().to(handlers::users::update_current_user))
                             .route("/me/password", web::put().to(handlers::users::change_password))
+                            .route("/me/privacy", web::get().to(handlers::users::get_privacy_settings))
+                            .route("/me/privacy", web::patch().to(handlers::users::update_privacy_settings))
+                            .route("/me/drafts", web::get().to(handlers::drafts::list_drafts))
+                            .route("/me/scheduled-messages", web::get().to(handlers::messages::list_scheduled_messages))
+                            .route("/me/scheduled-messages/{scheduled_id}", web::patch().to(handlers::messages::update_scheduled_message))
+                            .route("/me/scheduled-messages/{scheduled_id}", web::delete().to(handlers::messages::cancel_scheduled_message))
+                            .route("/me/scheduled-messages/{scheduled_id}/retry", web::post().to(handlers::messages::retry_scheduled_message))
                             .route("/search", web::get().to(handlers::users::search_users))
                             .route("/{user_id}", web::get().to(handlers::users::get_user))
                     )
@@ -136,7 +150,22 @@ async fn main() -> std::io::Result<()> {
                             .route("/{team_id}/members", web::post().to(handlers::teams::add_team_member))
                             .route("/{team_id}/members/{user_id}", web::patch().to(handlers::teams::update_team_member))
                             .route("/{team_id}/members/{user_id}", web::delete().to(handlers::teams::remove_team_member))
+                            .route("/{team_id}/moderation-log", web::get().to(handlers::teams::get_moderation_log))
+                            .route("/{team_id}/retention", web::get().to(handlers::retention::get_team_retention))
+                            .route("/{team_id}/retention", web::put().to(handlers::retention::update_team_retention))
+                            .route("/{team_id}/content-rules", web::get().to(handlers::content_rules::list_content_rules))
+                            .route("/{team_id}/content-rules", web::post().to(handlers::content_rules::create_content_rule))
+                            .route("/{team_id}/content-rules/{rule_id}", web::patch().to(handlers::content_rules::update_content_rule))
+                            .route("/{team_id}/content-rules/{rule_id}", web::delete().to(handlers::content_rules::delete_content_rule))
+                            .route("/{team_id}/moderation-queue", web::get().to(handlers::content_rules::list_moderation_queue))
+                            .route("/{team_id}/moderation-queue/{flag_id}/resolve", web::post().to(handlers::content_rules::resolve_flagged_message))
+                            .route("/{team_id}/emoji", web::get().to(handlers::emoji::list_emoji))
+                            .route("/{team_id}/emoji", web::post().to(handlers::emoji::create_emoji))
+                            .route("/{team_id}/emoji/{emoji_id}", web::patch().to(handlers::emoji::update_emoji))
+                            .route("/{team_id}/emoji/{emoji_id}", web::delete().to(handlers::emoji::delete_emoji))
+                            .route("/{team_id}/emoji/{emoji_id}/image", web::get().to(handlers::emoji::get_emoji_image))
                             .route("/{team_id}/channels", web::get().to(handlers::channels::list_team_channels))
+                            .route("/{team_id}/channels/browse", web::get().to(handlers::channels::browse_team_channels))
                     )
                     // Channels
                     .service(
@@ -147,15 +176,39 @@ async fn main() -> std::io::Result<()> {
                             .route("/{channel_id}", web::get().to(handlers::channels::get_channel))
                             .route("/{channel_id}", web::patch().to(handlers::channels::update_channel))
                             .route("/{channel_id}", web::delete().to(handlers::channels::delete_channel))
+                            .route("/{channel_id}/archive", web::post().to(handlers::channels::archive_channel))
+                            .route("/{channel_id}/unarchive", web::post().to(handlers::channels::unarchive_channel))
+                            .route("/{channel_id}/teams", web::get().to(handlers::channels::list_shared_teams))
+                            .route("/{channel_id}/teams", web::post().to(handlers::channels::share_channel))
+                            .route("/{channel_id}/teams/{team_id}", web::delete().to(handlers::channels::unshare_channel))
+                            .route("/{channel_id}/teams/{team_id}/accept", web::post().to(handlers::channels::accept_shared_channel))
+                            .route("/{channel_id}/join", web::post().to(handlers::channels::join_channel))
+                            .route("/{channel_id}/leave", web::post().to(handlers::channels::leave_channel))
                             .route("/{channel_id}/members", web::get().to(handlers::channels::list_channel_members))
                             .route("/{channel_id}/members", web::post().to(handlers::channels::add_channel_member))
+                            .route("/{channel_id}/members/me", web::patch().to(handlers::channels::update_my_channel_settings))
                             .route("/{channel_id}/members/{user_id}", web::delete().to(handlers::channels::remove_channel_member))
+                            .route("/{channel_id}/posters", web::post().to(handlers::channels::add_channel_poster))
+                            .route("/{channel_id}/posters/{user_id}", web::delete().to(handlers::channels::remove_channel_poster))
+                            .route("/{channel_id}/files", web::get().to(handlers::files::list_channel_files))
+                            .route("/{channel_id}/retention", web::get().to(handlers::retention::get_channel_retention))
+                            .route("/{channel_id}/retention", web::put().to(handlers::retention::update_channel_retention))
+                            .route("/{channel_id}/draft", web::put().to(handlers::drafts::save_draft))
+                            .route("/{channel_id}/draft", web::delete().to(handlers::drafts::clear_draft))
                             .route("/{channel_id}/messages", web::get().to(handlers::messages::list_messages))
                             .route("/{channel_id}/messages", web::post().to(handlers::messages::send_message))
+                            .route("/{channel_id}/ephemeral-messages", web::post().to(handlers::messages::post_ephemeral_message))
                             .route("/{channel_id}/messages/{message_id}", web::patch().to(handlers::messages::update_message))
                             .route("/{channel_id}/messages/{message_id}", web::delete().to(handlers::messages::delete_message))
+                            .route("/{channel_id}/messages/{message_id}/seen-by", web::get().to(handlers::messages::get_seen_by))
+                            .route("/{channel_id}/messages/{message_id}/history", web::get().to(handlers::messages::get_message_history))
+                            .route("/{channel_id}/messages/{message_id}/unread", web::post().to(handlers::messages::mark_as_unread))
+                            .route("/{channel_id}/messages/{message_id}/forward", web::post().to(handlers::messages::forward_message))
                             .route("/{channel_id}/messages/{message_id}/reactions", web::post().to(handlers::messages::add_reaction))
                             .route("/{channel_id}/messages/{message_id}/reactions/{emoji}", web::delete().to(handlers::messages::remove_reaction))
+                            .route("/{channel_id}/messages/{message_id}/poll/votes", web::post().to(handlers::messages::vote_poll))
+                            .route("/{channel_id}/messages/{message_id}/poll/votes/{option_id}", web::delete().to(handlers::messages::retract_poll_vote))
+                            .route("/{channel_id}/polls", web::post().to(handlers::messages::create_poll))
                             .route("/{channel_id}/read", web::post().to(handlers::channels::mark_as_read))
                     )
                     // Files
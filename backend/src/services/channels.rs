@@ -1,16 +1,18 @@
 //! Channel service
 
+use chrono::{DateTime, Utc};
 use shared::dto::{
-    ChannelMemberResponse, ChannelResponse, CreateChannelRequest, MessageResponse,
-    UpdateChannelRequest, UserResponse,
+    ChannelMemberResponse, ChannelResponse, ChannelTeamResponse, CreateChannelRequest,
+    MessageResponse, UpdateChannelMemberSettingsRequest, UpdateChannelRequest, UserResponse,
 };
 use shared::error::AppError;
-use shared::models::ChannelType;
+use shared::models::{Channel, ChannelTeam, ChannelType, NotificationLevel, TeamRole};
 use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::db::{ChannelRepository, MessageRepository, TeamRepository, UserRepository};
+use crate::services::hydration;
 
 pub struct ChannelService {
     pool: Arc<PgPool>,
@@ -110,8 +112,12 @@ impl ChannelService {
         self.get_channel_response(channel_id, user_id).await
     }
 
-    pub async fn list_user_channels(&self, user_id: &Uuid) -> Result<Vec<ChannelResponse>, AppError> {
-        let channels = ChannelRepository::find_by_user(&self.pool, user_id)
+    pub async fn list_user_channels(
+        &self,
+        user_id: &Uuid,
+        include_archived: bool,
+    ) -> Result<Vec<ChannelResponse>, AppError> {
+        let channels = ChannelRepository::find_by_user(&self.pool, user_id, include_archived)
             .await
             .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
@@ -129,6 +135,7 @@ impl ChannelService {
         &self,
         team_id: &Uuid,
         user_id: &Uuid,
+        include_archived: bool,
     ) -> Result<Vec<ChannelResponse>, AppError> {
         // Verify user is a team member
         if !TeamRepository::is_member(&self.pool, team_id, user_id)
@@ -140,7 +147,7 @@ impl ChannelService {
             ));
         }
 
-        let channels = ChannelRepository::find_by_team(&self.pool, team_id)
+        let channels = ChannelRepository::find_by_team(&self.pool, team_id, include_archived)
             .await
             .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
@@ -154,6 +161,107 @@ impl ChannelService {
         Ok(responses)
     }
 
+    /// Public channels of a team that the user can join, optionally filtered by name
+    pub async fn browse_team_channels(
+        &self,
+        team_id: &Uuid,
+        user_id: &Uuid,
+        query: Option<&str>,
+    ) -> Result<Vec<ChannelResponse>, AppError> {
+        if !TeamRepository::is_member(&self.pool, team_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You are not a member of this team".to_string(),
+            ));
+        }
+
+        let channels = ChannelRepository::find_public_by_team(&self.pool, team_id, query)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let mut responses = Vec::new();
+        for channel in channels {
+            if let Ok(response) = self.get_channel_response(&channel.id, user_id).await {
+                responses.push(response);
+            }
+        }
+
+        Ok(responses)
+    }
+
+    /// Join a public team channel
+    pub async fn join_channel(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<ChannelResponse, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        if ChannelRepository::is_member(&self.pool, channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::ConflictError(
+                "You are already a member of this channel".to_string(),
+            ));
+        }
+
+        self.check_can_join(&channel, user_id).await?;
+
+        // A concurrent join may have added the user since
+        let joined = ChannelRepository::join(&self.pool, channel_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if !joined {
+            return Err(AppError::ConflictError(
+                "You are already a member of this channel".to_string(),
+            ));
+        }
+
+        self.get_channel_response(channel_id, user_id).await
+    }
+
+    /// Join a public team channel when the user opens it without being a member.
+    /// Returns `true` if the user was added to the channel.
+    pub async fn auto_join(&self, channel_id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
+        if ChannelRepository::is_member(&self.pool, channel_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        {
+            return Ok(false);
+        }
+
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        if self.check_can_join(&channel, user_id).await.is_err() {
+            return Ok(false);
+        }
+
+        ChannelRepository::join(&self.pool, channel_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    /// Leave a channel the user is a member of
+    pub async fn leave_channel(&self, channel_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
+        if !ChannelRepository::is_member(&self.pool, channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::NotFoundError(
+                "You are not a member of this channel".to_string(),
+            ));
+        }
+
+        self.remove_member(channel_id, user_id, user_id).await
+    }
+
     pub async fn update_channel(
         &self,
         channel_id: &Uuid,
@@ -166,15 +274,42 @@ impl ChannelService {
             .await
             .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
 
-        // Only creator can update channel
-        if channel.created_by != *user_id {
-            return Err(AppError::AuthorizationError(
-                "Only the channel creator can update it".to_string(),
+        // Only the creator or a team admin can update the channel
+        self.check_manage_permission(&channel, user_id).await?;
+
+        if let Some(channel_type) = request.channel_type {
+            if channel.channel_type == ChannelType::DirectMessage
+                || channel_type == ChannelType::DirectMessage
+            {
+                return Err(AppError::BadRequest(
+                    "Only public and private channels can be converted".to_string(),
+                ));
+            }
+        }
+
+        // Changing who can see the channel or its history is reserved for team owners and admins
+        if request.channel_type.is_some() || request.history_visibility.is_some() {
+            let is_admin = match &channel.team_id {
+                Some(team_id) => self.is_team_admin(team_id, user_id).await?,
+                None => false,
+            };
+            if !is_admin {
+                return Err(AppError::AuthorizationError(
+                    "Only team owners and admins can change who sees the channel".to_string(),
+                ));
+            }
+        }
+
+        if request.is_announcement.is_some() && channel.channel_type == ChannelType::DirectMessage {
+            return Err(AppError::BadRequest(
+                "Direct message channels cannot be announcement channels".to_string(),
             ));
         }
 
+        // Every field is checked above, and the changes are written together
+        let mut tx = self.pool.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
         ChannelRepository::update(
-            &self.pool,
+            &mut *tx,
             channel_id,
             request.name.as_deref(),
             request.description.as_deref(),
@@ -182,6 +317,85 @@ impl ChannelService {
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
+        if let Some(is_announcement) = request.is_announcement {
+            ChannelRepository::set_announcement(&mut *tx, channel_id, is_announcement)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        }
+
+        if let Some(channel_type) = request.channel_type {
+            if channel_type != channel.channel_type {
+                ChannelRepository::set_channel_type(&mut *tx, channel_id, channel_type)
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            }
+        }
+
+        if let Some(history_visibility) = request.history_visibility {
+            ChannelRepository::set_history_visibility(&mut *tx, channel_id, history_visibility)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        }
+
+        if request.slow_mode_seconds.is_some() || request.burst_limit.is_some() {
+            ChannelRepository::set_rate_limits(
+                &mut *tx,
+                channel_id,
+                request.slow_mode_seconds.unwrap_or(channel.slow_mode_seconds),
+                request.burst_limit.unwrap_or(channel.burst_limit),
+            )
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.get_channel_response(channel_id, user_id).await
+    }
+
+    pub async fn archive_channel(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<ChannelResponse, AppError> {
+        self.set_archived(channel_id, user_id, true).await
+    }
+
+    pub async fn unarchive_channel(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<ChannelResponse, AppError> {
+        self.set_archived(channel_id, user_id, false).await
+    }
+
+    async fn set_archived(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+        archived: bool,
+    ) -> Result<ChannelResponse, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        if channel.channel_type == ChannelType::DirectMessage {
+            return Err(AppError::BadRequest(
+                "Direct message channels cannot be archived".to_string(),
+            ));
+        }
+
+        self.check_manage_permission(&channel, user_id).await?;
+
+        if channel.is_archived == archived {
+            let state = if archived { "archived" } else { "not archived" };
+            return Err(AppError::ConflictError(format!("Channel is already {}", state)));
+        }
+
+        ChannelRepository::set_archived(&self.pool, channel_id, archived)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
         self.get_channel_response(channel_id, user_id).await
     }
 
@@ -242,6 +456,7 @@ impl ChannelService {
                 },
                 joined_at: member.joined_at,
                 last_read_at: member.last_read_at,
+                can_post: member.can_post,
             });
         }
 
@@ -275,6 +490,8 @@ impl ChannelService {
             return Err(AppError::ConflictError("User is already a channel member".to_string()));
         }
 
+        self.check_shared_member_permission(&channel, requester_id, user_id).await?;
+
         let member = ChannelRepository::add_member(&self.pool, channel_id, user_id)
             .await
             .map_err(|e| AppError::DatabaseError(e.to_string()))?;
@@ -298,6 +515,7 @@ impl ChannelService {
             },
             joined_at: member.joined_at,
             last_read_at: member.last_read_at,
+            can_post: member.can_post,
         })
     }
 
@@ -318,8 +536,13 @@ impl ChannelService {
             ));
         }
 
-        // Can remove self or if you're the creator
-        if requester_id != user_id && channel.created_by != *requester_id {
+        // Can remove self, if you're the creator, or if you manage the user's team in a shared channel
+        if requester_id != user_id
+            && channel.created_by != *requester_id
+            && !self
+                .manages_shared_member(&self.find_accepted_teams(channel_id).await?, requester_id, user_id)
+                .await?
+        {
             return Err(AppError::AuthorizationError(
                 "You don't have permission to remove this member".to_string(),
             ));
@@ -330,7 +553,206 @@ impl ChannelService {
             .map_err(|e| AppError::DatabaseError(e.to_string()))
     }
 
-    pub async fn mark_as_read(&self, channel_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
+    /// Grant or revoke a member's permission to post in an announcement channel
+    pub async fn set_poster(
+        &self,
+        channel_id: &Uuid,
+        requester_id: &Uuid,
+        user_id: &Uuid,
+        can_post: bool,
+    ) -> Result<ChannelMemberResponse, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        self.check_manage_permission(&channel, requester_id).await?;
+
+        if !ChannelRepository::is_member(&self.pool, channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::NotFoundError("User is not a channel member".to_string()));
+        }
+
+        let member = ChannelRepository::set_can_post(&self.pool, channel_id, user_id, can_post)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let user = UserRepository::find_by_id(&self.pool, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(ChannelMemberResponse {
+            id: member.id,
+            user: UserResponse {
+                id: user.id,
+                email: user.email,
+                username: user.username,
+                display_name: user.display_name,
+                avatar_url: user.avatar_url,
+                status: user.status,
+                status_message: user.status_message,
+                last_seen: user.last_seen,
+                created_at: user.created_at,
+            },
+            joined_at: member.joined_at,
+            last_read_at: member.last_read_at,
+            can_post: member.can_post,
+        })
+    }
+
+    /// Teams the channel is shared with, including pending invitations
+    pub async fn list_shared_teams(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<Vec<ChannelTeamResponse>, AppError> {
+        self.check_channel_access(channel_id, user_id).await?;
+
+        let teams = ChannelRepository::find_teams(&self.pool, channel_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let mut responses = Vec::new();
+        for channel_team in teams {
+            if let Ok(response) = self.get_channel_team_response(channel_team).await {
+                responses.push(response);
+            }
+        }
+
+        Ok(responses)
+    }
+
+    /// Invite another team into a channel; the host team keeps moderation
+    pub async fn share_with_team(
+        &self,
+        channel_id: &Uuid,
+        requester_id: &Uuid,
+        team_id: &Uuid,
+    ) -> Result<ChannelTeamResponse, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        let host_team_id = match channel.team_id {
+            Some(host_team_id) if channel.channel_type != ChannelType::DirectMessage => host_team_id,
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Only team channels can be shared".to_string(),
+                ))
+            }
+        };
+
+        self.check_manage_permission(&channel, requester_id).await?;
+
+        if host_team_id == *team_id {
+            return Err(AppError::BadRequest(
+                "A channel can't be shared with its own team".to_string(),
+            ));
+        }
+
+        TeamRepository::find_by_id(&self.pool, team_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Team not found".to_string()))?;
+
+        if ChannelRepository::find_team(&self.pool, channel_id, team_id).await.is_ok() {
+            return Err(AppError::ConflictError(
+                "The channel is already shared with this team".to_string(),
+            ));
+        }
+
+        let channel_team = ChannelRepository::add_team(&self.pool, channel_id, team_id, requester_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.get_channel_team_response(channel_team).await
+    }
+
+    /// Accept an invitation to a shared channel on behalf of a team
+    pub async fn accept_shared_channel(
+        &self,
+        channel_id: &Uuid,
+        requester_id: &Uuid,
+        team_id: &Uuid,
+    ) -> Result<ChannelTeamResponse, AppError> {
+        let channel_team = ChannelRepository::find_team(&self.pool, channel_id, team_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Shared channel invitation not found".to_string()))?;
+
+        if !self.is_team_admin(team_id, requester_id).await? {
+            return Err(AppError::AuthorizationError(
+                "Only team owners and admins can accept shared channels".to_string(),
+            ));
+        }
+
+        if channel_team.accepted_at.is_some() {
+            return Err(AppError::ConflictError(
+                "The shared channel was already accepted".to_string(),
+            ));
+        }
+
+        let channel_team = ChannelRepository::accept_team(&self.pool, channel_id, team_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.get_channel_team_response(channel_team).await
+    }
+
+    /// Stop sharing a channel with a team, either from the host side or the invited team
+    pub async fn unshare_with_team(
+        &self,
+        channel_id: &Uuid,
+        requester_id: &Uuid,
+        team_id: &Uuid,
+    ) -> Result<(), AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        ChannelRepository::find_team(&self.pool, channel_id, team_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("The channel is not shared with this team".to_string()))?;
+
+        if !self.is_team_admin(team_id, requester_id).await? {
+            self.check_manage_permission(&channel, requester_id).await?;
+        }
+
+        ChannelRepository::remove_team(&self.pool, channel_id, team_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    /// Update the requesting member's notification preferences for a channel
+    pub async fn update_member_settings(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+        request: UpdateChannelMemberSettingsRequest,
+    ) -> Result<ChannelResponse, AppError> {
+        let member = ChannelRepository::find_member(&self.pool, channel_id, user_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("You are not a member of this channel".to_string()))?;
+
+        ChannelRepository::update_settings(
+            &self.pool,
+            channel_id,
+            user_id,
+            request.notify_level.unwrap_or(member.notify_level),
+            request.muted_until.unwrap_or(member.muted_until),
+            request.hidden.unwrap_or(member.hidden),
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.get_channel_response(channel_id, user_id).await
+    }
+
+    /// Move the user's read pointer to now and return it
+    pub async fn mark_as_read(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<DateTime<Utc>, AppError> {
         self.check_channel_access(channel_id, user_id).await?;
 
         ChannelRepository::mark_as_read(&self.pool, channel_id, user_id)
@@ -338,12 +760,183 @@ impl ChannelService {
             .map_err(|e| AppError::DatabaseError(e.to_string()))
     }
 
+    /// Move the user's read pointer back so the given message and everything after it is unread
+    pub async fn mark_as_unread(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<DateTime<Utc>, AppError> {
+        let message = MessageRepository::find_by_id(&self.pool, message_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+        if message.channel_id != *channel_id {
+            return Err(AppError::NotFoundError("Message not found".to_string()));
+        }
+
+        if !ChannelRepository::is_member(&self.pool, channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You are not a member of this channel".to_string(),
+            ));
+        }
+
+        let last_read_at = message.created_at - chrono::Duration::microseconds(1);
+
+        ChannelRepository::set_last_read_at(&self.pool, channel_id, user_id, &last_read_at)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(last_read_at)
+    }
+
     pub async fn is_member(&self, channel_id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
         ChannelRepository::is_member(&self.pool, channel_id, user_id)
             .await
             .map_err(|e| AppError::DatabaseError(e.to_string()))
     }
 
+    /// The channel creator and the owning team's owners and admins can manage a channel
+    async fn check_manage_permission(&self, channel: &Channel, user_id: &Uuid) -> Result<(), AppError> {
+        if channel.created_by == *user_id {
+            return Ok(());
+        }
+
+        if let Some(team_id) = &channel.team_id {
+            let role = TeamRepository::get_user_role(&self.pool, team_id, user_id)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+            if matches!(role, Some(TeamRole::Owner) | Some(TeamRole::Admin)) {
+                return Ok(());
+            }
+        }
+
+        Err(AppError::AuthorizationError(
+            "You don't have permission to manage this channel".to_string(),
+        ))
+    }
+
+    /// In shared channels, each team decides which of its members participate. Members of
+    /// the host team follow the regular rules; anyone else must belong to an attached team
+    /// whose owners or admins manage them.
+    async fn check_shared_member_permission(
+        &self,
+        channel: &Channel,
+        requester_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<(), AppError> {
+        let host_team_id = match channel.team_id {
+            Some(host_team_id) => host_team_id,
+            None => return Ok(()),
+        };
+
+        let teams = self.find_accepted_teams(&channel.id).await?;
+        if teams.is_empty()
+            || TeamRepository::is_member(&self.pool, &host_team_id, user_id)
+                .await
+                .unwrap_or(false)
+        {
+            return Ok(());
+        }
+
+        if self.manages_shared_member(&teams, requester_id, user_id).await? {
+            return Ok(());
+        }
+
+        Err(AppError::AuthorizationError(
+            "Only the owners and admins of a team this channel is shared with can manage its members"
+                .to_string(),
+        ))
+    }
+
+    /// Whether the requester is an owner or admin of an attached team the user belongs to
+    async fn manages_shared_member(
+        &self,
+        teams: &[ChannelTeam],
+        requester_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<bool, AppError> {
+        for channel_team in teams {
+            if TeamRepository::is_member(&self.pool, &channel_team.team_id, user_id)
+                .await
+                .unwrap_or(false)
+                && self.is_team_admin(&channel_team.team_id, requester_id).await?
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn find_accepted_teams(&self, channel_id: &Uuid) -> Result<Vec<ChannelTeam>, AppError> {
+        Ok(ChannelRepository::find_teams(&self.pool, channel_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .into_iter()
+            .filter(|t| t.accepted_at.is_some())
+            .collect())
+    }
+
+    async fn is_team_admin(&self, team_id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
+        let role = TeamRepository::get_user_role(&self.pool, team_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(matches!(role, Some(TeamRole::Owner) | Some(TeamRole::Admin)))
+    }
+
+    async fn get_channel_team_response(
+        &self,
+        channel_team: ChannelTeam,
+    ) -> Result<ChannelTeamResponse, AppError> {
+        let team = TeamRepository::find_by_id(&self.pool, &channel_team.team_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Team not found".to_string()))?;
+
+        Ok(ChannelTeamResponse {
+            team_id: team.id,
+            team_name: team.name,
+            invited_by: channel_team.invited_by,
+            invited_at: channel_team.invited_at,
+            accepted_at: channel_team.accepted_at,
+        })
+    }
+
+    /// Only team members may join public, non-archived team channels on their own
+    async fn check_can_join(&self, channel: &Channel, user_id: &Uuid) -> Result<(), AppError> {
+        if channel.channel_type != ChannelType::Public {
+            return Err(AppError::AuthorizationError(
+                "Only public channels can be joined".to_string(),
+            ));
+        }
+
+        if channel.is_archived {
+            return Err(AppError::AuthorizationError(
+                "This channel is archived and read-only".to_string(),
+            ));
+        }
+
+        let is_team_member = match channel.team_id {
+            Some(team_id) => TeamRepository::is_member(&self.pool, &team_id, user_id)
+                .await
+                .unwrap_or(false),
+            None => false,
+        };
+
+        if !is_team_member {
+            return Err(AppError::AuthorizationError(
+                "You are not a member of this team".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
     async fn check_channel_access(&self, channel_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
         let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
             .await
@@ -387,14 +980,35 @@ impl ChannelService {
             .await
             .unwrap_or(0);
 
+        let member = ChannelRepository::find_member(&self.pool, channel_id, user_id)
+            .await
+            .ok();
+
         let unread_count = ChannelRepository::get_unread_count(&self.pool, channel_id, user_id)
             .await
             .unwrap_or(0);
 
+        let is_shared = self
+            .find_accepted_teams(channel_id)
+            .await
+            .map(|teams| !teams.is_empty())
+            .unwrap_or(false);
+
+        let mention_count = ChannelRepository::get_mention_count(&self.pool, channel_id, user_id)
+            .await
+            .unwrap_or(0);
+
+        let first_unread_message_id =
+            ChannelRepository::get_first_unread_message_id(&self.pool, channel_id, user_id)
+                .await
+                .ok()
+                .flatten();
+
         let last_message = MessageRepository::get_last_message(&self.pool, channel_id)
             .await
             .ok()
-            .flatten();
+            .flatten()
+            .map(|m| hydration::expire(m, Utc::now()));
 
         let last_message_response = if let Some(msg) = last_message {
             let sender = UserRepository::find_by_id(&self.pool, &msg.sender_id)
@@ -417,12 +1031,20 @@ impl ChannelService {
                 },
                 content: msg.content,
                 message_type: msg.message_type,
+                format: msg.format,
                 reply_to: None,
                 reactions: vec![],
                 attachments: vec![],
+                mentions: vec![],
+                link_previews: vec![],
+                poll: None,
+                forwarded: None,
                 edited: msg.edited,
                 created_at: msg.created_at,
                 updated_at: msg.updated_at,
+                deleted_at: msg.deleted_at,
+                moderated: msg.moderated_by.is_some(),
+                expires_at: msg.expires_at,
             })
         } else {
             None
@@ -434,10 +1056,172 @@ impl ChannelService {
             name: channel.name,
             description: channel.description,
             channel_type: channel.channel_type,
+            is_archived: channel.is_archived,
+            archived_at: channel.archived_at,
+            is_announcement: channel.is_announcement,
+            slow_mode_seconds: channel.slow_mode_seconds,
+            burst_limit: channel.burst_limit,
+            history_visibility: channel.history_visibility,
+            is_member: member.is_some(),
+            is_shared,
+            notify_level: member
+                .as_ref()
+                .map(|m| m.notify_level)
+                .unwrap_or_else(|| NotificationLevel::default_for(channel.channel_type)),
+            muted_until: member.as_ref().and_then(|m| m.muted_until),
+            hidden: member.as_ref().is_some_and(|m| m.hidden),
             member_count,
             unread_count,
+            mention_count,
+            first_unread_message_id,
             last_message: last_message_response,
             created_at: channel.created_at,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use shared::models::HistoryVisibility;
+
+    fn update_request() -> UpdateChannelRequest {
+        UpdateChannelRequest {
+            name: None,
+            description: None,
+            is_announcement: None,
+            slow_mode_seconds: None,
+            burst_limit: None,
+            channel_type: None,
+            history_visibility: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_update_changes_nothing() {
+        let Some(pool) = testing::pool().await else { return };
+        let alice = testing::user(&pool, "alice").await;
+        let bob = testing::user(&pool, "bob").await;
+        let dm = testing::channel(&pool, None, ChannelType::DirectMessage, &alice, &[&bob]).await;
+        let service = ChannelService::new(Arc::new(pool.clone()));
+
+        let request = UpdateChannelRequest {
+            name: Some("renamed".to_string()),
+            is_announcement: Some(true),
+            ..update_request()
+        };
+        let result = service.update_channel(&dm.id, &alice.id, request).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+        let channel = ChannelRepository::find_by_id(&pool, &dm.id).await.unwrap();
+        assert_eq!(channel.name, dm.name);
+        assert!(!channel.is_announcement);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_auto_joins() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        let service = ChannelService::new(Arc::new(pool));
+
+        let (first, second) = tokio::join!(
+            service.auto_join(&channel.id, &member.id),
+            service.auto_join(&channel.id, &member.id),
+        );
+        let joined = [first.unwrap(), second.unwrap()];
+        assert_eq!(joined.iter().filter(|j| **j).count(), 1);
+
+        let again = service.join_channel(&channel.id, &member.id).await;
+        assert!(matches!(again, Err(AppError::ConflictError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_browse_matches_wildcards_literally() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        for name in ["100%-done", "general", "dev_ops", "devops"] {
+            let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+            ChannelRepository::update(&pool, &channel.id, Some(name), None).await.unwrap();
+        }
+        let service = ChannelService::new(Arc::new(pool));
+
+        let names = |channels: Vec<ChannelResponse>| channels.into_iter().map(|c| c.name).collect::<Vec<_>>();
+        let found = service.browse_team_channels(&team.id, &owner.id, Some("%")).await.unwrap();
+        assert_eq!(names(found), vec!["100%-done"]);
+        let found = service.browse_team_channels(&team.id, &owner.id, Some("v_o")).await.unwrap();
+        assert_eq!(names(found), vec!["dev_ops"]);
+    }
+
+    #[tokio::test]
+    async fn test_shared_channel_membership() {
+        let Some(pool) = testing::pool().await else { return };
+        let host = testing::user(&pool, "host").await;
+        let host_team = testing::team(&pool, &host).await;
+        let partner = testing::user(&pool, "partner").await;
+        let partner_team = testing::team(&pool, &partner).await;
+        let guest = testing::team_member(&pool, &partner_team, "guest", TeamRole::Member).await;
+        let channel =
+            testing::channel(&pool, Some(&host_team), ChannelType::Public, &host, &[&partner]).await;
+        let service = ChannelService::new(Arc::new(pool.clone()));
+
+        let result = service.share_with_team(&channel.id, &host.id, &host_team.id).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        service.share_with_team(&channel.id, &host.id, &partner_team.id).await.unwrap();
+        let result = service.accept_shared_channel(&channel.id, &guest.id, &partner_team.id).await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+
+        let listed = service.list_team_channels(&partner_team.id, &partner.id, false).await.unwrap();
+        assert!(listed.iter().all(|c| c.id != channel.id));
+
+        service.accept_shared_channel(&channel.id, &partner.id, &partner_team.id).await.unwrap();
+        let listed = service.list_team_channels(&partner_team.id, &partner.id, false).await.unwrap();
+        assert!(listed.iter().any(|c| c.id == channel.id));
+
+        // Only the partner team's admins decide which of its members join
+        let result = service.add_member(&channel.id, &host.id, &guest.id).await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+        service.add_member(&channel.id, &partner.id, &guest.id).await.unwrap();
+        assert!(ChannelRepository::is_member(&pool, &channel.id, &guest.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_change_who_sees_the_channel() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let creator = testing::team_member(&pool, &team, "creator", TeamRole::Member).await;
+        let admin = testing::team_member(&pool, &team, "admin", TeamRole::Admin).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &creator, &[&admin]).await;
+        let service = ChannelService::new(Arc::new(pool.clone()));
+
+        let to_private = || UpdateChannelRequest {
+            channel_type: Some(ChannelType::Private),
+            ..update_request()
+        };
+        let result = service.update_channel(&channel.id, &creator.id, to_private()).await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+        let updated = service.update_channel(&channel.id, &admin.id, to_private()).await.unwrap();
+        assert_eq!(updated.channel_type, ChannelType::Private);
+
+        let joined_history = || UpdateChannelRequest {
+            history_visibility: Some(HistoryVisibility::Joined),
+            ..update_request()
+        };
+        let result = service.update_channel(&channel.id, &creator.id, joined_history()).await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+        let updated = service.update_channel(&channel.id, &admin.id, joined_history()).await.unwrap();
+        assert_eq!(updated.history_visibility, HistoryVisibility::Joined);
+
+        let to_dm = UpdateChannelRequest {
+            channel_type: Some(ChannelType::DirectMessage),
+            ..update_request()
+        };
+        let result = service.update_channel(&channel.id, &admin.id, to_dm).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+}
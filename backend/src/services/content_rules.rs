@@ -0,0 +1,183 @@
+//! Content rule service
+
+use shared::dto::{ContentRuleResponse, CreateContentRuleRequest, UpdateContentRuleRequest};
+use shared::error::AppError;
+use shared::filters;
+use shared::models::{ContentRule, ContentRuleKind, TeamRole};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::{ContentRuleRepository, TeamRepository};
+
+pub struct ContentRuleService {
+    pool: Arc<PgPool>,
+}
+
+impl ContentRuleService {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Moderators can see the rules behind the flags they review
+    pub async fn list_rules(&self, team_id: &Uuid, user_id: &Uuid) -> Result<Vec<ContentRuleResponse>, AppError> {
+        let role = TeamRepository::get_user_role(&self.pool, team_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        match role {
+            Some(role) if role.can_moderate() => {}
+            Some(_) => {
+                return Err(AppError::AuthorizationError(
+                    "Only team moderators can view content rules".to_string(),
+                ))
+            }
+            None => {
+                return Err(AppError::AuthorizationError(
+                    "You are not a member of this team".to_string(),
+                ))
+            }
+        }
+
+        let rules = ContentRuleRepository::find_by_team(&self.pool, team_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(rules.into_iter().map(Self::rule_response).collect())
+    }
+
+    pub async fn create_rule(
+        &self,
+        team_id: &Uuid,
+        user_id: &Uuid,
+        request: CreateContentRuleRequest,
+    ) -> Result<ContentRuleResponse, AppError> {
+        self.check_team_admin(team_id, user_id).await?;
+
+        // Built-in detectors take no pattern or words
+        let pattern = request.pattern.filter(|_| request.kind == ContentRuleKind::Regex);
+        let words = match request.kind {
+            ContentRuleKind::Words => clean_words(request.words.unwrap_or_default()),
+            _ => Vec::new(),
+        };
+        filters::detector(request.kind, pattern.as_deref(), &words).map_err(AppError::ValidationError)?;
+
+        let rule = ContentRuleRepository::create(
+            &self.pool,
+            team_id,
+            request.name.trim(),
+            request.kind,
+            pattern.as_deref(),
+            &words,
+            request.action,
+            request.enabled.unwrap_or(true),
+            user_id,
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(Self::rule_response(rule))
+    }
+
+    pub async fn update_rule(
+        &self,
+        team_id: &Uuid,
+        rule_id: &Uuid,
+        user_id: &Uuid,
+        request: UpdateContentRuleRequest,
+    ) -> Result<ContentRuleResponse, AppError> {
+        self.check_team_admin(team_id, user_id).await?;
+        let rule = self.find_team_rule(team_id, rule_id).await?;
+
+        let pattern = request.pattern.filter(|_| rule.kind == ContentRuleKind::Regex);
+        let words = request
+            .words
+            .filter(|_| rule.kind == ContentRuleKind::Words)
+            .map(clean_words);
+        filters::detector(
+            rule.kind,
+            pattern.as_deref().or(rule.pattern.as_deref()),
+            words.as_deref().unwrap_or(&rule.words),
+        )
+        .map_err(AppError::ValidationError)?;
+
+        let rule = ContentRuleRepository::update(
+            &self.pool,
+            rule_id,
+            request.name.as_deref().map(str::trim),
+            pattern.as_deref(),
+            words.as_deref(),
+            request.action,
+            request.enabled,
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(Self::rule_response(rule))
+    }
+
+    /// Flags raised by the rule stay in the moderation queue
+    pub async fn delete_rule(&self, team_id: &Uuid, rule_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
+        self.check_team_admin(team_id, user_id).await?;
+        self.find_team_rule(team_id, rule_id).await?;
+
+        ContentRuleRepository::delete(&self.pool, rule_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    async fn find_team_rule(&self, team_id: &Uuid, rule_id: &Uuid) -> Result<ContentRule, AppError> {
+        let rule = ContentRuleRepository::find_by_id(&self.pool, rule_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Content rule not found".to_string()))?;
+
+        if rule.team_id != *team_id {
+            return Err(AppError::NotFoundError("Content rule not found".to_string()));
+        }
+
+        Ok(rule)
+    }
+
+    async fn check_team_admin(&self, team_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
+        let role = TeamRepository::get_user_role(&self.pool, team_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        match role {
+            Some(TeamRole::Owner) | Some(TeamRole::Admin) => Ok(()),
+            Some(_) => Err(AppError::AuthorizationError(
+                "Only team owners and admins can change content rules".to_string(),
+            )),
+            None => Err(AppError::AuthorizationError(
+                "You are not a member of this team".to_string(),
+            )),
+        }
+    }
+
+    fn rule_response(rule: ContentRule) -> ContentRuleResponse {
+        ContentRuleResponse {
+            id: rule.id,
+            team_id: rule.team_id,
+            name: rule.name,
+            kind: rule.kind,
+            pattern: rule.pattern,
+            words: rule.words,
+            action: rule.action,
+            enabled: rule.enabled,
+            created_by: rule.created_by,
+            created_at: rule.created_at,
+            updated_at: rule.updated_at,
+        }
+    }
+}
+
+/// Trimmed words without blanks or repeats, in their original order
+fn clean_words(words: Vec<String>) -> Vec<String> {
+    let mut cleaned: Vec<String> = Vec::with_capacity(words.len());
+    for word in words {
+        let word = word.trim();
+        if !word.is_empty() && !cleaned.iter().any(|w| w.eq_ignore_ascii_case(word)) {
+            cleaned.push(word.to_string());
+        }
+    }
+    cleaned
+}
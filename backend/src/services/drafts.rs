@@ -0,0 +1,172 @@
+//! Draft service
+
+use chrono::Utc;
+use shared::dto::{DraftResponse, SaveDraftRequest};
+use shared::error::AppError;
+use shared::models::Draft;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::{ChannelRepository, DraftRepository, MessageRepository};
+
+pub struct DraftService {
+    pool: Arc<PgPool>,
+}
+
+impl DraftService {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_drafts(&self, user_id: &Uuid) -> Result<Vec<DraftResponse>, AppError> {
+        let drafts = DraftRepository::find_by_user(&self.pool, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(drafts.into_iter().map(Self::draft_response).collect())
+    }
+
+    /// Save the user's draft for a channel or thread. Empty content clears it.
+    pub async fn save_draft(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+        request: SaveDraftRequest,
+    ) -> Result<DraftResponse, AppError> {
+        self.check_channel_access(channel_id, user_id).await?;
+
+        if request.content.trim().is_empty() {
+            return self
+                .clear_draft(channel_id, user_id, request.thread_root_id.as_ref())
+                .await;
+        }
+
+        if let Some(thread_root_id) = &request.thread_root_id {
+            let root = MessageRepository::find_by_id(&self.pool, thread_root_id)
+                .await
+                .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+            if root.channel_id != *channel_id {
+                return Err(AppError::BadRequest(
+                    "Thread does not belong to this channel".to_string(),
+                ));
+            }
+        }
+
+        let draft = DraftRepository::upsert(
+            &self.pool,
+            user_id,
+            channel_id,
+            request.thread_root_id.as_ref(),
+            &request.content,
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(Self::draft_response(draft))
+    }
+
+    /// Clear the user's draft for a channel or thread, returning the cleared draft to sync
+    pub async fn clear_draft(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+        thread_root_id: Option<&Uuid>,
+    ) -> Result<DraftResponse, AppError> {
+        DraftRepository::delete(&self.pool, user_id, channel_id, thread_root_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(DraftResponse {
+            channel_id: *channel_id,
+            thread_root_id: thread_root_id.copied(),
+            content: String::new(),
+            updated_at: Utc::now(),
+        })
+    }
+
+    async fn check_channel_access(&self, channel_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
+        if !ChannelRepository::is_member(&self.pool, channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You don't have access to this channel".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn draft_response(draft: Draft) -> DraftResponse {
+        DraftResponse {
+            channel_id: draft.channel_id,
+            thread_root_id: draft.thread_root_id,
+            content: draft.content,
+            updated_at: draft.updated_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::services::messages::MessageService;
+    use shared::dto::SendMessageRequest;
+    use shared::models::ChannelType;
+
+    fn draft(content: &str, thread_root_id: Option<Uuid>) -> SaveDraftRequest {
+        SaveDraftRequest {
+            content: content.to_string(),
+            thread_root_id,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drafts_are_kept_per_channel_and_thread() {
+        let Some(pool) = testing::pool().await else { return };
+        let alice = testing::user(&pool, "alice").await;
+        let bob = testing::user(&pool, "bob").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &alice, &[]).await;
+        let other = testing::channel(&pool, None, ChannelType::Private, &alice, &[]).await;
+        let messages = MessageService::new(Arc::new(pool.clone()));
+        let service = DraftService::new(Arc::new(pool));
+
+        let request = SendMessageRequest {
+            content: "root".to_string(),
+            ..Default::default()
+        };
+        let root = messages.send_message(&channel.id, &alice.id, request.clone()).await.unwrap();
+        let other_root = messages.send_message(&other.id, &alice.id, request).await.unwrap();
+
+        service.save_draft(&channel.id, &alice.id, draft("first", None)).await.unwrap();
+        service.save_draft(&channel.id, &alice.id, draft("in the channel", None)).await.unwrap();
+        service.save_draft(&channel.id, &alice.id, draft("in the thread", Some(root.id))).await.unwrap();
+
+        let mut drafts = service.list_drafts(&alice.id).await.unwrap();
+        drafts.sort_by_key(|d| d.thread_root_id.is_some());
+        let contents: Vec<(&str, Option<Uuid>)> =
+            drafts.iter().map(|d| (d.content.as_str(), d.thread_root_id)).collect();
+        assert_eq!(contents, vec![("in the channel", None), ("in the thread", Some(root.id))]);
+
+        // Threads belong to their own channel, and only members keep drafts
+        let result = service.save_draft(&channel.id, &alice.id, draft("x", Some(other_root.id))).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        let result = service.save_draft(&channel.id, &bob.id, draft("x", None)).await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+
+        // Sending in the thread clears only the thread's draft
+        let cleared = service.clear_draft(&channel.id, &alice.id, Some(&root.id)).await.unwrap();
+        assert_eq!(cleared.thread_root_id, Some(root.id));
+        assert!(cleared.content.is_empty());
+        let drafts = service.list_drafts(&alice.id).await.unwrap();
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].content, "in the channel");
+
+        // Saving empty content clears the draft too
+        service.save_draft(&channel.id, &alice.id, draft("  ", None)).await.unwrap();
+        assert!(service.list_drafts(&alice.id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+//! Custom emoji service
+
+use shared::dto::{CreateCustomEmojiRequest, CustomEmojiResponse, UpdateCustomEmojiRequest};
+use shared::emoji::is_valid_custom_emoji_name;
+use shared::error::AppError;
+use shared::models::CustomEmoji;
+use sqlx::PgPool;
+use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::db::{CustomEmojiRepository, FileRepository, TeamRepository};
+
+/// Largest image accepted for a custom emoji
+const MAX_CUSTOM_EMOJI_BYTES: i64 = 256 * 1024;
+
+/// Image types that are safe to serve inline
+const CUSTOM_EMOJI_MIME_TYPES: &[&str] = &["image/png", "image/gif", "image/jpeg", "image/webp"];
+
+pub struct EmojiService {
+    pool: Arc<PgPool>,
+    config: Arc<AppConfig>,
+}
+
+impl EmojiService {
+    pub fn new(pool: Arc<PgPool>, config: Arc<AppConfig>) -> Self {
+        Self { pool, config }
+    }
+
+    pub async fn list_emoji(&self, team_id: &Uuid, user_id: &Uuid) -> Result<Vec<CustomEmojiResponse>, AppError> {
+        self.check_team_member(team_id, user_id).await?;
+
+        let emoji = CustomEmojiRepository::find_by_team(&self.pool, team_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(emoji.into_iter().map(Self::emoji_response).collect())
+    }
+
+    /// Add a custom emoji from an image the user uploaded
+    pub async fn create_emoji(
+        &self,
+        team_id: &Uuid,
+        user_id: &Uuid,
+        request: CreateCustomEmojiRequest,
+    ) -> Result<CustomEmojiResponse, AppError> {
+        self.check_team_member(team_id, user_id).await?;
+        self.check_name_available(team_id, &request.name).await?;
+
+        let file = FileRepository::find_by_id(&self.pool, &request.file_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("File not found".to_string()))?;
+
+        if file.uploader_id != *user_id {
+            return Err(AppError::AuthorizationError(
+                "You can only use your own uploads as emoji".to_string(),
+            ));
+        }
+        if !CUSTOM_EMOJI_MIME_TYPES.contains(&file.mime_type.as_str()) {
+            return Err(AppError::ValidationError(
+                "Emoji must be a PNG, GIF, JPEG or WebP image".to_string(),
+            ));
+        }
+        if file.file_size > MAX_CUSTOM_EMOJI_BYTES {
+            return Err(AppError::ValidationError(format!(
+                "Emoji images must be at most {} KB",
+                MAX_CUSTOM_EMOJI_BYTES / 1024
+            )));
+        }
+
+        let emoji = CustomEmojiRepository::create(&self.pool, team_id, &request.name, &file.id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(Self::emoji_response(emoji))
+    }
+
+    pub async fn rename_emoji(
+        &self,
+        team_id: &Uuid,
+        emoji_id: &Uuid,
+        user_id: &Uuid,
+        request: UpdateCustomEmojiRequest,
+    ) -> Result<CustomEmojiResponse, AppError> {
+        let emoji = self.find_manageable_emoji(team_id, emoji_id, user_id).await?;
+        if emoji.name == request.name {
+            return Ok(Self::emoji_response(emoji));
+        }
+        self.check_name_available(team_id, &request.name).await?;
+
+        let emoji = CustomEmojiRepository::rename(&self.pool, emoji_id, &request.name)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(Self::emoji_response(emoji))
+    }
+
+    /// Existing reactions using the emoji keep their `:name:` text
+    pub async fn delete_emoji(&self, team_id: &Uuid, emoji_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
+        self.find_manageable_emoji(team_id, emoji_id, user_id).await?;
+
+        CustomEmojiRepository::delete(&self.pool, emoji_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    /// Path and content type of an emoji's image, for team members
+    pub async fn get_emoji_image(
+        &self,
+        team_id: &Uuid,
+        emoji_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<(String, String), AppError> {
+        self.check_team_member(team_id, user_id).await?;
+        let emoji = self.find_team_emoji(team_id, emoji_id).await?;
+
+        let file = FileRepository::find_by_id(&self.pool, &emoji.file_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("File not found".to_string()))?;
+
+        let full_path = Path::new(&self.config.storage.upload_path)
+            .join(&file.storage_path)
+            .to_string_lossy()
+            .to_string();
+
+        Ok((full_path, file.mime_type))
+    }
+
+    async fn check_team_member(&self, team_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
+        if !TeamRepository::is_member(&self.pool, team_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You are not a member of this team".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn check_name_available(&self, team_id: &Uuid, name: &str) -> Result<(), AppError> {
+        if !is_valid_custom_emoji_name(name) {
+            return Err(AppError::ValidationError(
+                "Emoji names may only contain lowercase letters, digits, '_', '-' and '+'".to_string(),
+            ));
+        }
+
+        let existing = CustomEmojiRepository::find_by_name(&self.pool, team_id, name)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if existing.is_some() {
+            return Err(AppError::ConflictError(format!("Emoji :{}: already exists", name)));
+        }
+
+        Ok(())
+    }
+
+    async fn find_team_emoji(&self, team_id: &Uuid, emoji_id: &Uuid) -> Result<CustomEmoji, AppError> {
+        let emoji = CustomEmojiRepository::find_by_id(&self.pool, emoji_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Emoji not found".to_string()))?;
+
+        if emoji.team_id != *team_id {
+            return Err(AppError::NotFoundError("Emoji not found".to_string()));
+        }
+
+        Ok(emoji)
+    }
+
+    /// The emoji's creator and the team's moderators may rename or remove it
+    async fn find_manageable_emoji(
+        &self,
+        team_id: &Uuid,
+        emoji_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<CustomEmoji, AppError> {
+        let emoji = self.find_team_emoji(team_id, emoji_id).await?;
+        if emoji.created_by == *user_id {
+            return Ok(emoji);
+        }
+
+        let role = TeamRepository::get_user_role(&self.pool, team_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if !role.is_some_and(|r| r.can_moderate()) {
+            return Err(AppError::AuthorizationError(
+                "You don't have permission to perform this action".to_string(),
+            ));
+        }
+
+        Ok(emoji)
+    }
+
+    fn emoji_response(emoji: CustomEmoji) -> CustomEmojiResponse {
+        CustomEmojiResponse {
+            image_url: format!("/api/v1/teams/{}/emoji/{}/image", emoji.team_id, emoji.id),
+            id: emoji.id,
+            team_id: emoji.team_id,
+            name: emoji.name,
+            created_by: emoji.created_by,
+            created_at: emoji.created_at,
+        }
+    }
+}
@@ -1,7 +1,8 @@
 //! File service
 
-use shared::dto::{FileAttachmentResponse, UploadFileResponse};
+use shared::dto::{FileAttachmentResponse, PaginatedResponse, UploadFileResponse};
 use shared::error::AppError;
+use shared::pagination::{paginate, Cursor};
 use sqlx::PgPool;
 use std::path::Path;
 use std::sync::Arc;
@@ -166,19 +167,30 @@ impl FileService {
             ));
         }
 
-        // Delete from filesystem
-        let full_path = Path::new(&self.config.storage.upload_path).join(&file.storage_path);
-        if full_path.exists() {
-            fs::remove_file(&full_path)
-                .await
-                .map_err(|e| AppError::InternalError(e.to_string()))?;
-        }
-
         // Delete from database
         FileRepository::delete(&self.pool, file_id)
             .await
             .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
+        self.remove_stored_files(&[file.storage_path]).await
+    }
+
+    /// Delete stored files whose attachment rows are gone, unless forwarded copies
+    /// still use them
+    pub async fn remove_stored_files(&self, storage_paths: &[String]) -> Result<(), AppError> {
+        for storage_path in storage_paths {
+            let still_linked = FileRepository::count_by_storage_path(&self.pool, storage_path)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?
+                > 0;
+            let full_path = Path::new(&self.config.storage.upload_path).join(storage_path);
+            if !still_linked && full_path.exists() {
+                fs::remove_file(&full_path)
+                    .await
+                    .map_err(|e| AppError::InternalError(e.to_string()))?;
+            }
+        }
+
         Ok(())
     }
 
@@ -187,8 +199,8 @@ impl FileService {
         channel_id: &Uuid,
         user_id: &Uuid,
         limit: i64,
-        offset: i64,
-    ) -> Result<Vec<FileAttachmentResponse>, AppError> {
+        before: Option<Cursor>,
+    ) -> Result<PaginatedResponse<FileAttachmentResponse>, AppError> {
         // Verify user has access to channel
         if !ChannelRepository::is_member(&self.pool, channel_id, user_id)
             .await
@@ -199,20 +211,17 @@ impl FileService {
             ));
         }
 
-        let files = FileRepository::find_by_channel(&self.pool, channel_id, limit, offset)
+        let files = FileRepository::find_by_channel(&self.pool, channel_id, limit + 1, before)
             .await
             .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        Ok(files
-            .into_iter()
-            .map(|f| FileAttachmentResponse {
-                id: f.id,
-                filename: f.filename,
-                file_size: f.file_size,
-                mime_type: f.mime_type,
-                download_url: format!("/api/v1/files/{}/download", f.id),
-                created_at: f.created_at,
-            })
-            .collect())
+        Ok(paginate(files, limit, |f| Cursor::new(f.created_at, f.id)).map(|f| FileAttachmentResponse {
+            id: f.id,
+            filename: f.filename,
+            file_size: f.file_size,
+            mime_type: f.mime_type,
+            download_url: format!("/api/v1/files/{}/download", f.id),
+            created_at: f.created_at,
+        }))
     }
 }
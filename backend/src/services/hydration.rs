@@ -0,0 +1,858 @@
+//! Message hydration
+//!
+//! Turns stored messages into [`MessageResponse`]s. Everything a page of messages
+//! needs (senders, reply parents, reactions, attachments, mentions, previews,
+//! forwards and polls) is loaded with one query per kind of detail, so the number
+//! of queries does not grow with the size of the page.
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use shared::dto::{
+    FileAttachmentResponse, ForwardedMessageResponse, LinkPreviewResponse, MentionResponse,
+    MessageResponse, PollOptionResponse, PollResponse, ReactionResponse, UserResponse,
+};
+use shared::error::AppError;
+use shared::models::{
+    FileAttachment, LinkPreview, Message, MessageForward, MessageMention, MessageType, Poll,
+    PollOption, PollVote, Reaction, User,
+};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::{
+    ChannelRepository, FileRepository, ForwardRepository, LinkPreviewRepository,
+    MessageRepository, PollRepository, UserRepository,
+};
+
+type Loaded<'a, T> = BoxFuture<'a, Result<Vec<T>, sqlx::Error>>;
+
+/// Set-based lookups used to hydrate messages. Every method answers for all of the
+/// given ids at once.
+pub trait MessageDetailsSource: Send + Sync {
+    fn messages<'a>(&'a self, ids: &'a [Uuid]) -> Loaded<'a, Message>;
+    fn users<'a>(&'a self, ids: &'a [Uuid]) -> Loaded<'a, User>;
+    fn reactions<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, Reaction>;
+    fn attachments<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, FileAttachment>;
+    fn mentions<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, MessageMention>;
+    fn link_previews<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, (Uuid, LinkPreview)>;
+    fn forwards<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, MessageForward>;
+    fn polls<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, Poll>;
+    fn poll_options<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, PollOption>;
+    fn poll_votes<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, PollVote>;
+    /// The channels the user is a member of, with the earliest point in each
+    /// channel's history they may see
+    fn member_visibility<'a>(
+        &'a self,
+        user_id: &'a Uuid,
+        channel_ids: &'a [Uuid],
+    ) -> Loaded<'a, (Uuid, Option<DateTime<Utc>>)>;
+}
+
+/// Loads message details from the database
+pub struct PgMessageDetailsSource {
+    pool: Arc<PgPool>,
+}
+
+impl PgMessageDetailsSource {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+impl MessageDetailsSource for PgMessageDetailsSource {
+    fn messages<'a>(&'a self, ids: &'a [Uuid]) -> Loaded<'a, Message> {
+        Box::pin(MessageRepository::find_by_ids(&self.pool, ids))
+    }
+
+    fn users<'a>(&'a self, ids: &'a [Uuid]) -> Loaded<'a, User> {
+        Box::pin(UserRepository::find_by_ids(&self.pool, ids))
+    }
+
+    fn reactions<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, Reaction> {
+        Box::pin(MessageRepository::get_reactions_for_messages(&self.pool, message_ids))
+    }
+
+    fn attachments<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, FileAttachment> {
+        Box::pin(FileRepository::find_by_messages(&self.pool, message_ids))
+    }
+
+    fn mentions<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, MessageMention> {
+        Box::pin(MessageRepository::get_mentions_for_messages(&self.pool, message_ids))
+    }
+
+    fn link_previews<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, (Uuid, LinkPreview)> {
+        Box::pin(LinkPreviewRepository::find_by_messages(&self.pool, message_ids))
+    }
+
+    fn forwards<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, MessageForward> {
+        Box::pin(ForwardRepository::find_by_messages(&self.pool, message_ids))
+    }
+
+    fn polls<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, Poll> {
+        Box::pin(PollRepository::find_by_messages(&self.pool, message_ids))
+    }
+
+    fn poll_options<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, PollOption> {
+        Box::pin(PollRepository::find_options_for_messages(&self.pool, message_ids))
+    }
+
+    fn poll_votes<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, PollVote> {
+        Box::pin(PollRepository::find_votes_for_messages(&self.pool, message_ids))
+    }
+
+    fn member_visibility<'a>(
+        &'a self,
+        user_id: &'a Uuid,
+        channel_ids: &'a [Uuid],
+    ) -> Loaded<'a, (Uuid, Option<DateTime<Utc>>)> {
+        Box::pin(ChannelRepository::find_member_visibility(&self.pool, user_id, channel_ids))
+    }
+}
+
+pub struct MessageHydrator {
+    source: Arc<dyn MessageDetailsSource>,
+}
+
+impl MessageHydrator {
+    pub fn new(source: Arc<dyn MessageDetailsSource>) -> Self {
+        Self { source }
+    }
+
+    /// Build responses for a page of messages as seen by `user_id`, keeping their order.
+    /// Messages whose sender no longer exists are left out.
+    pub async fn hydrate(
+        &self,
+        messages: Vec<Message>,
+        user_id: &Uuid,
+    ) -> Result<Vec<MessageResponse>, AppError> {
+        if messages.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let now = Utc::now();
+        let messages: Vec<Message> = messages.into_iter().map(|m| expire(m, now)).collect();
+
+        // Tombstones keep their place in the channel and reply context, but nothing else
+        let live_ids: Vec<Uuid> = messages
+            .iter()
+            .filter(|m| m.deleted_at.is_none())
+            .map(|m| m.id)
+            .collect();
+
+        let forwards: HashMap<Uuid, MessageForward> = self
+            .load(self.source.forwards(&live_ids), &live_ids)
+            .await?
+            .into_iter()
+            .map(|f| (f.message_id, f))
+            .collect();
+
+        // Reply parents and forwarded originals outside the page
+        let page_ids: HashSet<Uuid> = messages.iter().map(|m| m.id).collect();
+        let related_ids = unique(
+            messages
+                .iter()
+                .filter_map(|m| m.reply_to_id)
+                .chain(forwards.values().filter_map(|f| f.source_message_id))
+                .filter(|id| !page_ids.contains(id)),
+        );
+        let related: Vec<Message> = self
+            .load(self.source.messages(&related_ids), &related_ids)
+            .await?
+            .into_iter()
+            .map(|m| expire(m, now))
+            .collect();
+        let messages_by_id: HashMap<Uuid, &Message> =
+            messages.iter().chain(related.iter()).map(|m| (m.id, m)).collect();
+
+        let user_ids = unique(
+            messages_by_id
+                .values()
+                .map(|m| m.sender_id)
+                .chain(forwards.values().filter_map(|f| f.source_sender_id)),
+        );
+        let users: HashMap<Uuid, User> = self
+            .load(self.source.users(&user_ids), &user_ids)
+            .await?
+            .into_iter()
+            .map(|u| (u.id, u))
+            .collect();
+
+        let reactions = group_by(
+            self.load(self.source.reactions(&live_ids), &live_ids).await?,
+            |r| r.message_id,
+        );
+        let attachments = group_by(
+            self.load(self.source.attachments(&live_ids), &live_ids).await?,
+            |f| f.message_id.unwrap_or_default(),
+        );
+        let mentions = group_by(
+            self.load(self.source.mentions(&live_ids), &live_ids).await?,
+            |m| m.message_id,
+        );
+        let link_previews = group_by(
+            self.load(self.source.link_previews(&live_ids), &live_ids).await?,
+            |(message_id, _)| *message_id,
+        );
+
+        let poll_ids: Vec<Uuid> = messages
+            .iter()
+            .filter(|m| m.deleted_at.is_none() && m.message_type == MessageType::Poll)
+            .map(|m| m.id)
+            .collect();
+        let polls = self.load(self.source.polls(&poll_ids), &poll_ids).await?;
+        let poll_options = group_by(
+            self.load(self.source.poll_options(&poll_ids), &poll_ids).await?,
+            |o| o.message_id,
+        );
+        let poll_votes = group_by(
+            self.load(self.source.poll_votes(&poll_ids), &poll_ids).await?,
+            |v| v.message_id,
+        );
+
+        // Channels of reply parents and forwarded originals, whose history the viewer may not see
+        let related_channel_ids = unique(
+            messages
+                .iter()
+                .filter_map(|m| m.reply_to_id)
+                .filter_map(|id| messages_by_id.get(&id))
+                .map(|parent| parent.channel_id)
+                .chain(forwards.values().filter_map(|f| f.source_channel_id)),
+        );
+        let visibility: HashMap<Uuid, Option<DateTime<Utc>>> = self
+            .load(self.source.member_visibility(user_id, &related_channel_ids), &related_channel_ids)
+            .await?
+            .into_iter()
+            .collect();
+
+        let mut polls: HashMap<Uuid, PollResponse> = polls
+            .into_iter()
+            .map(|poll| {
+                let options = poll_options.get(&poll.message_id).map(Vec::as_slice).unwrap_or_default();
+                let votes = poll_votes.get(&poll.message_id).map(Vec::as_slice).unwrap_or_default();
+                (poll.message_id, poll_response(&poll, options, votes, user_id))
+            })
+            .collect();
+
+        let mut responses = Vec::with_capacity(messages.len());
+        for message in &messages {
+            let Some(sender) = users.get(&message.sender_id) else {
+                continue;
+            };
+
+            let reply_to = message
+                .reply_to_id
+                .and_then(|reply_id| messages_by_id.get(&reply_id))
+                .and_then(|parent| {
+                    let parent_sender = users.get(&parent.sender_id)?;
+                    let visible = parent.deleted_at.is_none()
+                        && is_visible(&visibility, parent.channel_id, parent.created_at);
+                    let content = if visible {
+                        parent.content.clone()
+                    } else {
+                        String::new()
+                    };
+                    Some(Box::new(bare_response(parent, parent_sender, content)))
+                });
+
+            if message.deleted_at.is_some() {
+                let mut response = bare_response(message, sender, String::new());
+                response.reply_to = reply_to;
+                responses.push(response);
+                continue;
+            }
+
+            let mut response = bare_response(message, sender, message.content.clone());
+            response.reply_to = reply_to;
+            response.reactions = reaction_responses(reactions.get(&message.id), user_id);
+            response.attachments = attachments
+                .get(&message.id)
+                .into_iter()
+                .flatten()
+                .map(|f| FileAttachmentResponse {
+                    id: f.id,
+                    filename: f.filename.clone(),
+                    file_size: f.file_size,
+                    mime_type: f.mime_type.clone(),
+                    download_url: format!("/api/v1/files/{}/download", f.id),
+                    created_at: f.created_at,
+                })
+                .collect();
+            response.mentions = mentions
+                .get(&message.id)
+                .into_iter()
+                .flatten()
+                .map(|m| MentionResponse {
+                    mention_type: m.mention_type,
+                    user_id: m.user_id,
+                    start: m.start_offset as usize,
+                    end: m.end_offset as usize,
+                })
+                .collect();
+            response.link_previews = link_previews
+                .get(&message.id)
+                .into_iter()
+                .flatten()
+                .filter_map(|(_, p)| {
+                    Some(LinkPreviewResponse {
+                        url: p.url.clone(),
+                        title: p.title.clone()?,
+                        description: p.description.clone(),
+                        image_url: p.image_url.clone(),
+                        site_name: p.site_name.clone(),
+                    })
+                })
+                .collect();
+            response.poll = polls.remove(&message.id);
+            response.forwarded = forwards
+                .get(&message.id)
+                .map(|f| forwarded_response(f, &visibility, &messages_by_id, &users));
+
+            responses.push(response);
+        }
+
+        Ok(responses)
+    }
+
+    /// Run a lookup, skipping the query entirely when there is nothing to look up
+    async fn load<T>(&self, query: Loaded<'_, T>, ids: &[Uuid]) -> Result<Vec<T>, AppError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        query.await.map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+}
+
+/// Tallies of a poll. Voters are only listed when the poll is not anonymous.
+pub fn poll_response(poll: &Poll, options: &[PollOption], votes: &[PollVote], user_id: &Uuid) -> PollResponse {
+    let option_responses = options
+        .iter()
+        .map(|option| {
+            let voters: Vec<Uuid> = votes
+                .iter()
+                .filter(|v| v.option_id == option.id)
+                .map(|v| v.user_id)
+                .collect();
+            PollOptionResponse {
+                id: option.id,
+                text: option.text.clone(),
+                vote_count: voters.len() as i64,
+                voters: if poll.anonymous { vec![] } else { voters },
+            }
+        })
+        .collect();
+
+    let total_voters = votes.iter().map(|v| v.user_id).collect::<HashSet<_>>().len() as i64;
+    let my_votes = votes
+        .iter()
+        .filter(|v| v.user_id == *user_id)
+        .map(|v| v.option_id)
+        .collect();
+
+    PollResponse {
+        question: poll.question.clone(),
+        options: option_responses,
+        allow_multiple: poll.allow_multiple,
+        anonymous: poll.anonymous,
+        closes_at: poll.closes_at,
+        closed: poll.is_closed(),
+        total_voters,
+        my_votes,
+    }
+}
+
+/// Reactions grouped by emoji, in the order each emoji was first used
+fn reaction_responses(reactions: Option<&Vec<Reaction>>, user_id: &Uuid) -> Vec<ReactionResponse> {
+    let mut responses: Vec<ReactionResponse> = Vec::new();
+    for reaction in reactions.into_iter().flatten() {
+        match responses.iter_mut().find(|r| r.emoji == reaction.emoji) {
+            Some(response) => {
+                response.count += 1;
+                response.users.push(reaction.user_id);
+            }
+            None => responses.push(ReactionResponse {
+                emoji: reaction.emoji.clone(),
+                count: 1,
+                users: vec![reaction.user_id],
+                reacted_by_me: false,
+            }),
+        }
+    }
+    for response in &mut responses {
+        response.reacted_by_me = response.users.contains(user_id);
+    }
+    responses
+}
+
+/// The original of a forwarded message, expanded only for viewers who can see it
+/// in its channel. Originals deleted or purged since they were forwarded, and those
+/// whose channel is gone, are not shown.
+fn forwarded_response(
+    forward: &MessageForward,
+    visibility: &HashMap<Uuid, Option<DateTime<Utc>>>,
+    messages: &HashMap<Uuid, &Message>,
+    users: &HashMap<Uuid, User>,
+) -> ForwardedMessageResponse {
+    let visible = forward
+        .source_channel_id
+        .is_some_and(|channel_id| is_visible(visibility, channel_id, forward.source_created_at));
+    let source_exists = forward
+        .source_message_id
+        .and_then(|id| messages.get(&id))
+        .is_some_and(|m| m.deleted_at.is_none());
+
+    if !visible || !source_exists {
+        return ForwardedMessageResponse::collapsed();
+    }
+
+    ForwardedMessageResponse {
+        accessible: true,
+        message_id: forward.source_message_id,
+        channel_id: forward.source_channel_id,
+        sender: forward
+            .source_sender_id
+            .and_then(|id| users.get(&id))
+            .map(user_response),
+        content: Some(forward.content.clone()),
+        format: Some(forward.format),
+        created_at: Some(forward.source_created_at),
+    }
+}
+
+/// Whether a message sent at `created_at` is in the part of the channel's history the
+/// viewer may see. Viewers who aren't members of the channel see none of it.
+fn is_visible(visibility: &HashMap<Uuid, Option<DateTime<Utc>>>, channel_id: Uuid, created_at: DateTime<Utc>) -> bool {
+    visibility
+        .get(&channel_id)
+        .is_some_and(|visible_since| !visible_since.is_some_and(|since| created_at < since))
+}
+
+/// A response with the message's own fields and none of its details
+fn bare_response(message: &Message, sender: &User, content: String) -> MessageResponse {
+    MessageResponse {
+        id: message.id,
+        channel_id: message.channel_id,
+        sender: user_response(sender),
+        content,
+        message_type: message.message_type,
+        format: message.format,
+        reply_to: None,
+        reactions: vec![],
+        attachments: vec![],
+        mentions: vec![],
+        link_previews: vec![],
+        poll: None,
+        forwarded: None,
+        edited: message.edited,
+        created_at: message.created_at,
+        updated_at: message.updated_at,
+        deleted_at: message.deleted_at,
+        moderated: message.moderated_by.is_some(),
+        expires_at: message.expires_at,
+    }
+}
+
+/// Treat a message past its time to live as deleted, in case it has not been purged yet
+pub fn expire(mut message: Message, now: DateTime<Utc>) -> Message {
+    if let Some(expires_at) = message.expires_at.filter(|at| *at <= now) {
+        message.deleted_at.get_or_insert(expires_at);
+        message.content.clear();
+    }
+    message
+}
+
+pub fn user_response(user: &User) -> UserResponse {
+    UserResponse {
+        id: user.id,
+        email: user.email.clone(),
+        username: user.username.clone(),
+        display_name: user.display_name.clone(),
+        avatar_url: user.avatar_url.clone(),
+        status: user.status,
+        status_message: user.status_message.clone(),
+        last_seen: user.last_seen,
+        created_at: user.created_at,
+    }
+}
+
+fn unique(ids: impl Iterator<Item = Uuid>) -> Vec<Uuid> {
+    let mut seen = HashSet::new();
+    ids.filter(|id| seen.insert(*id)).collect()
+}
+
+fn group_by<T>(items: Vec<T>, key: impl Fn(&T) -> Uuid) -> HashMap<Uuid, Vec<T>> {
+    let mut groups: HashMap<Uuid, Vec<T>> = HashMap::new();
+    for item in items {
+        groups.entry(key(&item)).or_default().push(item);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use shared::models::{MentionType, MessageFormat, UserStatus};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// In-memory details that count how many lookups are made
+    #[derive(Default)]
+    struct FakeSource {
+        messages: Vec<Message>,
+        users: Vec<User>,
+        reactions: Vec<Reaction>,
+        attachments: Vec<FileAttachment>,
+        mentions: Vec<MessageMention>,
+        forwards: Vec<MessageForward>,
+        polls: Vec<Poll>,
+        poll_options: Vec<PollOption>,
+        poll_votes: Vec<PollVote>,
+        visibility: Vec<(Uuid, Option<DateTime<Utc>>)>,
+        queries: AtomicUsize,
+    }
+
+    impl FakeSource {
+        fn select<'a, T: Clone + Send + Sync + 'a>(
+            &'a self,
+            items: &'a [T],
+            ids: &'a [Uuid],
+            key: fn(&T) -> Uuid,
+        ) -> Loaded<'a, T> {
+            Box::pin(async move {
+                self.queries.fetch_add(1, Ordering::SeqCst);
+                Ok(items.iter().filter(|item| ids.contains(&key(item))).cloned().collect())
+            })
+        }
+    }
+
+    impl MessageDetailsSource for FakeSource {
+        fn messages<'a>(&'a self, ids: &'a [Uuid]) -> Loaded<'a, Message> {
+            self.select(&self.messages, ids, |m| m.id)
+        }
+
+        fn users<'a>(&'a self, ids: &'a [Uuid]) -> Loaded<'a, User> {
+            self.select(&self.users, ids, |u| u.id)
+        }
+
+        fn reactions<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, Reaction> {
+            self.select(&self.reactions, message_ids, |r| r.message_id)
+        }
+
+        fn attachments<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, FileAttachment> {
+            self.select(&self.attachments, message_ids, |f| f.message_id.unwrap_or_default())
+        }
+
+        fn mentions<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, MessageMention> {
+            self.select(&self.mentions, message_ids, |m| m.message_id)
+        }
+
+        fn link_previews<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, (Uuid, LinkPreview)> {
+            self.select(&[], message_ids, |(message_id, _)| *message_id)
+        }
+
+        fn forwards<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, MessageForward> {
+            self.select(&self.forwards, message_ids, |f| f.message_id)
+        }
+
+        fn polls<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, Poll> {
+            self.select(&self.polls, message_ids, |p| p.message_id)
+        }
+
+        fn poll_options<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, PollOption> {
+            self.select(&self.poll_options, message_ids, |o| o.message_id)
+        }
+
+        fn poll_votes<'a>(&'a self, message_ids: &'a [Uuid]) -> Loaded<'a, PollVote> {
+            self.select(&self.poll_votes, message_ids, |v| v.message_id)
+        }
+
+        fn member_visibility<'a>(
+            &'a self,
+            _user_id: &'a Uuid,
+            channel_ids: &'a [Uuid],
+        ) -> Loaded<'a, (Uuid, Option<DateTime<Utc>>)> {
+            self.select(&self.visibility, channel_ids, |(channel_id, _)| *channel_id)
+        }
+    }
+
+    fn user(id: Uuid) -> User {
+        User {
+            id,
+            email: format!("{}@example.com", id),
+            username: id.to_string(),
+            display_name: id.to_string(),
+            password_hash: String::new(),
+            avatar_url: None,
+            status: UserStatus::Online,
+            status_message: None,
+            last_seen: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn message(channel_id: Uuid, sender_id: Uuid, reply_to_id: Option<Uuid>) -> Message {
+        Message {
+            id: Uuid::new_v4(),
+            channel_id,
+            sender_id,
+            content: "hello".to_string(),
+            message_type: MessageType::Text,
+            format: MessageFormat::Plain,
+            reply_to_id,
+            edited: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+            deleted_by: None,
+            moderated_by: None,
+            expires_at: None,
+        }
+    }
+
+    /// A channel of `count` messages from different senders, each replying to the
+    /// previous one and carrying reactions, an attachment, a mention, a poll or a forward
+    fn channel_with_messages(count: usize, viewer: Uuid) -> (FakeSource, Vec<Message>) {
+        let channel_id = Uuid::new_v4();
+        let source_channel_id = Uuid::new_v4();
+        let mut source = FakeSource {
+            users: vec![user(viewer)],
+            visibility: vec![(channel_id, None), (source_channel_id, None)],
+            ..Default::default()
+        };
+
+        let original = message(source_channel_id, viewer, None);
+        source.messages.push(original.clone());
+
+        let mut page = Vec::new();
+        let mut previous = None;
+        for i in 0..count {
+            let sender = user(Uuid::new_v4());
+            let mut m = message(channel_id, sender.id, previous);
+            source.users.push(sender);
+
+            for reactor in [viewer, Uuid::new_v4()] {
+                source.reactions.push(Reaction {
+                    id: Uuid::new_v4(),
+                    message_id: m.id,
+                    user_id: reactor,
+                    emoji: "👍".to_string(),
+                    created_at: Utc::now(),
+                });
+            }
+            source.attachments.push(FileAttachment {
+                id: Uuid::new_v4(),
+                message_id: Some(m.id),
+                channel_id,
+                uploader_id: m.sender_id,
+                filename: "notes.txt".to_string(),
+                file_size: 5,
+                mime_type: "text/plain".to_string(),
+                storage_path: "notes.txt".to_string(),
+                created_at: Utc::now(),
+            });
+            source.mentions.push(MessageMention {
+                id: Uuid::new_v4(),
+                message_id: m.id,
+                mention_type: MentionType::User,
+                user_id: Some(viewer),
+                start_offset: 0,
+                end_offset: 5,
+            });
+
+            match i % 3 {
+                1 => {
+                    m.message_type = MessageType::Poll;
+                    let option_id = Uuid::new_v4();
+                    source.polls.push(Poll {
+                        message_id: m.id,
+                        question: "Lunch?".to_string(),
+                        allow_multiple: false,
+                        anonymous: false,
+                        closes_at: None,
+                        created_at: Utc::now(),
+                    });
+                    source.poll_options.push(PollOption {
+                        id: option_id,
+                        message_id: m.id,
+                        position: 0,
+                        text: "Yes".to_string(),
+                    });
+                    source.poll_votes.push(PollVote {
+                        message_id: m.id,
+                        option_id,
+                        user_id: viewer,
+                        created_at: Utc::now(),
+                    });
+                }
+                2 => source.forwards.push(MessageForward {
+                    message_id: m.id,
+                    source_message_id: Some(original.id),
+                    source_channel_id: Some(source_channel_id),
+                    source_sender_id: Some(viewer),
+                    content: original.content.clone(),
+                    format: original.format,
+                    source_created_at: original.created_at,
+                }),
+                _ => {}
+            }
+
+            previous = Some(m.id);
+            source.messages.push(m.clone());
+            page.push(m);
+        }
+
+        (source, page)
+    }
+
+    #[tokio::test]
+    async fn test_query_count_does_not_grow_with_page_size() {
+        let viewer = Uuid::new_v4();
+        let mut counts = Vec::new();
+
+        for page_size in [3, 50] {
+            let (source, page) = channel_with_messages(page_size, viewer);
+            let source = Arc::new(source);
+            let hydrator = MessageHydrator::new(source.clone());
+
+            let responses = hydrator.hydrate(page, &viewer).await.unwrap();
+            assert_eq!(responses.len(), page_size);
+            counts.push(source.queries.load(Ordering::SeqCst));
+        }
+
+        // One lookup for each kind of detail
+        assert!(counts[1] <= 11, "{} queries for a page of 50", counts[1]);
+        assert_eq!(counts[0], counts[1]);
+    }
+
+    #[tokio::test]
+    async fn test_hydrated_details() {
+        let viewer = Uuid::new_v4();
+        let (source, page) = channel_with_messages(3, viewer);
+        let hydrator = MessageHydrator::new(Arc::new(source));
+
+        let responses = hydrator.hydrate(page.clone(), &viewer).await.unwrap();
+        let ids: Vec<Uuid> = responses.iter().map(|r| r.id).collect();
+        assert_eq!(ids, page.iter().map(|m| m.id).collect::<Vec<_>>());
+
+        assert!(responses[0].reply_to.is_none());
+        let reply_to = responses[1].reply_to.as_ref().unwrap();
+        assert_eq!(reply_to.id, page[0].id);
+        assert_eq!(reply_to.sender.id, page[0].sender_id);
+        assert_eq!(reply_to.content, "hello");
+
+        for response in &responses {
+            assert_eq!(response.reactions.len(), 1);
+            assert_eq!(response.reactions[0].count, 2);
+            assert!(response.reactions[0].reacted_by_me);
+            assert_eq!(response.attachments.len(), 1);
+            assert_eq!(response.mentions.len(), 1);
+        }
+
+        let poll = responses[1].poll.as_ref().unwrap();
+        assert_eq!(poll.total_voters, 1);
+        assert_eq!(poll.my_votes.len(), 1);
+
+        let forwarded = responses[2].forwarded.as_ref().unwrap();
+        assert!(forwarded.accessible);
+        assert_eq!(forwarded.sender.as_ref().unwrap().id, viewer);
+    }
+
+    #[tokio::test]
+    async fn test_forward_hidden_outside_visible_history() {
+        let viewer = Uuid::new_v4();
+        let (mut source, page) = channel_with_messages(3, viewer);
+        let source_channel_id = source.forwards[0].source_channel_id.unwrap();
+        source.visibility = vec![(source_channel_id, Some(Utc::now() + Duration::hours(1)))];
+        let hydrator = MessageHydrator::new(Arc::new(source));
+
+        let responses = hydrator.hydrate(page, &viewer).await.unwrap();
+        let forwarded = responses[2].forwarded.as_ref().unwrap();
+        assert!(!forwarded.accessible);
+        assert!(forwarded.content.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_forward_hidden_once_original_is_gone() {
+        let viewer = Uuid::new_v4();
+        let (mut source, page) = channel_with_messages(3, viewer);
+        source.forwards[0].source_message_id = None;
+        let hydrator = MessageHydrator::new(Arc::new(source));
+
+        let responses = hydrator.hydrate(page, &viewer).await.unwrap();
+        let forwarded = responses[2].forwarded.as_ref().unwrap();
+        assert!(!forwarded.accessible);
+        assert!(forwarded.content.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_forward_hidden_from_non_members() {
+        let viewer = Uuid::new_v4();
+        let (mut source, page) = channel_with_messages(3, viewer);
+        source.visibility.clear();
+        let hydrator = MessageHydrator::new(Arc::new(source));
+
+        let responses = hydrator.hydrate(page, &viewer).await.unwrap();
+        let forwarded = responses[2].forwarded.as_ref().unwrap();
+        assert!(!forwarded.accessible);
+        assert!(forwarded.sender.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reply_parent_hidden_outside_visible_history() {
+        let viewer = Uuid::new_v4();
+        let (mut source, mut page) = channel_with_messages(3, viewer);
+        let channel_id = page[0].channel_id;
+        let joined_at = page[1].created_at;
+        page[0].created_at = joined_at - Duration::hours(1);
+        source.messages.retain(|m| m.id != page[0].id);
+        source.messages.push(page[0].clone());
+        source.visibility.retain(|(id, _)| *id != channel_id);
+        source.visibility.push((channel_id, Some(joined_at)));
+        let hydrator = MessageHydrator::new(Arc::new(source));
+
+        // The viewer only loaded messages since joining, replying to one from before
+        let responses = hydrator.hydrate(page[1..].to_vec(), &viewer).await.unwrap();
+        let reply_to = responses[0].reply_to.as_ref().unwrap();
+        assert_eq!(reply_to.id, page[0].id);
+        assert!(reply_to.content.is_empty());
+
+        // Replies to messages from after joining keep the parent
+        assert_eq!(responses[1].reply_to.as_ref().unwrap().content, "hello");
+    }
+
+    #[tokio::test]
+    async fn test_tombstones_have_no_details() {
+        let viewer = Uuid::new_v4();
+        let (source, mut page) = channel_with_messages(2, viewer);
+        page[1].deleted_at = Some(Utc::now());
+        let hydrator = MessageHydrator::new(Arc::new(source));
+
+        let responses = hydrator.hydrate(page, &viewer).await.unwrap();
+        assert!(responses[1].content.is_empty());
+        assert!(responses[1].reactions.is_empty());
+        assert!(responses[1].poll.is_none());
+        assert!(responses[1].reply_to.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_expired_messages_are_tombstones() {
+        let viewer = Uuid::new_v4();
+        let (source, mut page) = channel_with_messages(2, viewer);
+        let expired_at = Utc::now() - Duration::seconds(1);
+        page[0].expires_at = Some(expired_at);
+        page[1].expires_at = Some(Utc::now() + Duration::hours(1));
+        let hydrator = MessageHydrator::new(Arc::new(source));
+
+        let responses = hydrator.hydrate(page, &viewer).await.unwrap();
+        assert!(responses[0].content.is_empty());
+        assert_eq!(responses[0].deleted_at, Some(expired_at));
+        assert!(responses[0].reactions.is_empty());
+
+        assert_eq!(responses[1].content, "hello");
+        assert!(responses[1].deleted_at.is_none());
+        assert!(responses[1].reply_to.as_ref().unwrap().content.is_empty());
+    }
+}
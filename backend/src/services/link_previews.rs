@@ -0,0 +1,425 @@
+//! Link preview service
+//!
+//! Previews are built from a page's OpenGraph tags, falling back to its oEmbed
+//! document and plain `<title>`/description. Results are cached per URL, including
+//! failures, so a popular link is fetched once rather than once per message.
+
+use chrono::{Duration, Utc};
+use regex::Regex;
+use shared::error::AppError;
+use shared::markdown;
+use shared::models::LinkPreview;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use uuid::Uuid;
+
+use crate::db::LinkPreviewRepository;
+use crate::fetcher::PageFetcher;
+
+/// Links beyond this many in one message are not previewed
+pub const MAX_PREVIEWS_PER_MESSAGE: usize = 3;
+const MAX_URL_LEN: usize = 2048;
+const MAX_TITLE_LEN: usize = 300;
+const MAX_DESCRIPTION_LEN: usize = 1000;
+const MAX_SITE_NAME_LEN: usize = 100;
+
+static META_TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<meta\s[^>]*>").unwrap());
+static LINK_TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<link\s[^>]*>").unwrap());
+static ATTRIBUTE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)([a-z:_-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+static TITLE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+pub struct LinkPreviewService {
+    pool: Arc<PgPool>,
+    fetcher: Arc<dyn PageFetcher>,
+}
+
+impl LinkPreviewService {
+    pub fn new(pool: Arc<PgPool>, fetcher: Arc<dyn PageFetcher>) -> Self {
+        Self { pool, fetcher }
+    }
+
+    /// Fetch previews for the links in a message and attach them.
+    /// Returns whether the message's previews changed.
+    pub async fn unfurl_message(&self, message_id: &Uuid, content: &str) -> Result<bool, AppError> {
+        let urls: Vec<String> = markdown::collect_links(&markdown::parse(content))
+            .into_iter()
+            .filter(|url| url.len() <= MAX_URL_LEN && (url.starts_with("http://") || url.starts_with("https://")))
+            .take(MAX_PREVIEWS_PER_MESSAGE)
+            .collect();
+
+        let mut previewed = Vec::new();
+        for url in urls {
+            if self.get_preview(&url).await?.title.is_some() {
+                previewed.push(url);
+            }
+        }
+
+        let current: Vec<String> = LinkPreviewRepository::find_by_message(&self.pool, message_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .into_iter()
+            .map(|p| p.url)
+            .collect();
+        if current == previewed {
+            return Ok(false);
+        }
+
+        LinkPreviewRepository::replace_for_message(&self.pool, message_id, &previewed)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(true)
+    }
+
+    /// The cached preview for a URL, fetching it again once the cache entry expires.
+    /// Failed fetches are retried sooner than successful ones are refreshed.
+    async fn get_preview(&self, url: &str) -> Result<LinkPreview, AppError> {
+        let cached = LinkPreviewRepository::find_by_url(&self.pool, url)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        if let Some(cached) = cached {
+            let ttl = if cached.title.is_some() { Duration::hours(24) } else { Duration::hours(1) };
+            if cached.fetched_at + ttl > Utc::now() {
+                return Ok(cached);
+            }
+        }
+
+        let preview = self.fetch_preview(url).await;
+        LinkPreviewRepository::upsert(&self.pool, &preview)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(preview)
+    }
+
+    async fn fetch_preview(&self, url: &str) -> LinkPreview {
+        let mut preview = LinkPreview {
+            url: url.to_string(),
+            title: None,
+            description: None,
+            image_url: None,
+            site_name: None,
+            fetched_at: Utc::now(),
+        };
+
+        let page = match self.fetcher.fetch(url).await {
+            Ok(page) if page.content_type.contains("html") => page,
+            Ok(page) => {
+                tracing::debug!("Not previewing {}: content type {}", url, page.content_type);
+                return preview;
+            }
+            Err(e) => {
+                tracing::debug!("Failed to fetch {} for preview: {}", url, e);
+                return preview;
+            }
+        };
+
+        let mut metadata = PageMetadata::parse(&page.body);
+
+        // Pages without OpenGraph tags often still describe themselves through oEmbed
+        if metadata.og_title.is_none() {
+            if let Some(oembed_url) = metadata.oembed_url.take().and_then(|u| resolve_url(&page.url, &u)) {
+                match self.fetcher.fetch(&oembed_url).await {
+                    Ok(document) => metadata.apply_oembed(&document.body),
+                    Err(e) => tracing::debug!("Failed to fetch oEmbed for {}: {}", url, e),
+                }
+            }
+        }
+
+        preview.title = metadata.og_title.or(metadata.title).map(|t| truncate(&t, MAX_TITLE_LEN));
+        preview.description = metadata.description.map(|d| truncate(&d, MAX_DESCRIPTION_LEN));
+        preview.site_name = metadata.site_name.map(|s| truncate(&s, MAX_SITE_NAME_LEN));
+        preview.image_url = metadata
+            .image
+            .and_then(|image| resolve_url(&page.url, &image))
+            .filter(|image| image.len() <= MAX_URL_LEN);
+        preview
+    }
+}
+
+/// Metadata found in a page's `<head>`. OpenGraph values take precedence over
+/// the generic `<title>` and `description`.
+#[derive(Debug, Default)]
+struct PageMetadata {
+    og_title: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    image: Option<String>,
+    site_name: Option<String>,
+    oembed_url: Option<String>,
+}
+
+impl PageMetadata {
+    fn parse(html: &str) -> Self {
+        let mut metadata = PageMetadata::default();
+        let mut plain_description = None;
+
+        for tag in META_TAG_REGEX.find_iter(html) {
+            let attributes = parse_attributes(tag.as_str());
+            let key = attributes
+                .get("property")
+                .or_else(|| attributes.get("name"))
+                .map(|k| k.to_ascii_lowercase());
+            let (Some(key), Some(value)) = (key, attributes.get("content").and_then(|v| clean_text(v))) else {
+                continue;
+            };
+
+            let slot = match key.as_str() {
+                "og:title" => &mut metadata.og_title,
+                "og:description" => &mut metadata.description,
+                "og:image" | "og:image:url" | "og:image:secure_url" => &mut metadata.image,
+                "og:site_name" => &mut metadata.site_name,
+                "description" => &mut plain_description,
+                _ => continue,
+            };
+            slot.get_or_insert(value);
+        }
+
+        for tag in LINK_TAG_REGEX.find_iter(html) {
+            let attributes = parse_attributes(tag.as_str());
+            let is_oembed = attributes.get("type").is_some_and(|t| t.eq_ignore_ascii_case("application/json+oembed"));
+            if is_oembed {
+                if let Some(href) = attributes.get("href") {
+                    metadata.oembed_url = Some(decode_entities(href));
+                    break;
+                }
+            }
+        }
+
+        metadata.title = TITLE_REGEX
+            .captures(html)
+            .and_then(|c| clean_text(c.get(1)?.as_str()));
+        if metadata.description.is_none() {
+            metadata.description = plain_description;
+        }
+        metadata
+    }
+
+    fn apply_oembed(&mut self, json: &str) {
+        let Ok(document) = serde_json::from_str::<serde_json::Value>(json) else {
+            return;
+        };
+        let field = |name: &str| document.get(name).and_then(|v| v.as_str()).and_then(clean_text);
+
+        if let Some(title) = field("title") {
+            self.og_title = Some(title);
+        }
+        if self.site_name.is_none() {
+            self.site_name = field("provider_name");
+        }
+        if self.image.is_none() {
+            self.image = field("thumbnail_url");
+        }
+    }
+}
+
+fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    ATTRIBUTE_REGEX
+        .captures_iter(tag)
+        .filter_map(|c| {
+            let value = c.get(2).or_else(|| c.get(3)).or_else(|| c.get(4))?;
+            Some((c[1].to_ascii_lowercase(), value.as_str().to_string()))
+        })
+        .collect()
+}
+
+/// Decode entities and collapse whitespace. Empty values count as missing.
+fn clean_text(value: &str) -> Option<String> {
+    let text = decode_entities(value).split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() { None } else { Some(text) }
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Resolve a possibly relative URL against the page it was found on,
+/// keeping it only if it is safe for clients to load
+fn resolve_url(base: &str, value: &str) -> Option<String> {
+    let resolved = reqwest::Url::parse(base).ok()?.join(value).ok()?.to_string();
+    let is_http = resolved.starts_with("http://") || resolved.starts_with("https://");
+    (is_http && markdown::is_safe_url(&resolved)).then_some(resolved)
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    match value.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", value[..end].trim_end()),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::db::testing;
+    use crate::fetcher::{FetchError, FetchedPage};
+    use crate::services::Services;
+    use futures_util::future::BoxFuture;
+    use shared::dto::SendMessageRequest;
+    use shared::models::ChannelType;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Serves canned pages and counts the requests made
+    struct StubFetcher {
+        pages: HashMap<String, FetchedPage>,
+        fetches: AtomicUsize,
+    }
+
+    impl StubFetcher {
+        fn new(pages: &[(&str, &str, &str)]) -> Self {
+            let pages = pages
+                .iter()
+                .map(|(url, content_type, body)| {
+                    let page = FetchedPage {
+                        url: url.to_string(),
+                        content_type: content_type.to_string(),
+                        body: body.to_string(),
+                    };
+                    (url.to_string(), page)
+                })
+                .collect();
+            Self { pages, fetches: AtomicUsize::new(0) }
+        }
+    }
+
+    impl PageFetcher for StubFetcher {
+        fn fetch<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<FetchedPage, FetchError>> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            let page = self.pages.get(url).cloned().ok_or_else(|| FetchError::Request("Not found".to_string()));
+            Box::pin(async move { page })
+        }
+    }
+
+    #[test]
+    fn test_parse_open_graph() {
+        let metadata = PageMetadata::parse(
+            r#"<html><head>
+            <title>Plain title</title>
+            <meta name="description" content="Plain description">
+            <meta property="og:title" content="Tom &amp; Jerry">
+            <meta content='A   cat
+                and a mouse' property='og:description'>
+            <meta property="og:image" content="/cover.png">
+            <meta property="og:image" content="/second.png">
+            <meta property="og:site_name" content="">
+            </head></html>"#,
+        );
+        assert_eq!(metadata.og_title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(metadata.title.as_deref(), Some("Plain title"));
+        assert_eq!(metadata.description.as_deref(), Some("A cat and a mouse"));
+        assert_eq!(metadata.image.as_deref(), Some("/cover.png"));
+        assert_eq!(metadata.site_name, None);
+        assert_eq!(metadata.oembed_url, None);
+    }
+
+    #[test]
+    fn test_parse_oembed_fallback() {
+        let mut metadata = PageMetadata::parse(
+            r#"<title>Video</title><meta name="description" content="Watch this">
+            <link rel="alternate" type="application/json+oembed" href="/oembed?url=a&amp;format=json">"#,
+        );
+        assert_eq!(metadata.description.as_deref(), Some("Watch this"));
+        assert_eq!(metadata.oembed_url.as_deref(), Some("/oembed?url=a&format=json"));
+
+        metadata.apply_oembed(r#"{"title": "Funny cats", "provider_name": "Tube", "thumbnail_url": "https://i.tube/1.jpg"}"#);
+        assert_eq!(metadata.og_title.as_deref(), Some("Funny cats"));
+        assert_eq!(metadata.site_name.as_deref(), Some("Tube"));
+        assert_eq!(metadata.image.as_deref(), Some("https://i.tube/1.jpg"));
+
+        // Malformed documents change nothing
+        metadata.apply_oembed("not json");
+        assert_eq!(metadata.og_title.as_deref(), Some("Funny cats"));
+    }
+
+    #[test]
+    fn test_resolve_and_truncate() {
+        assert_eq!(
+            resolve_url("https://example.com/a/b", "../cover.png").as_deref(),
+            Some("https://example.com/cover.png")
+        );
+        assert_eq!(resolve_url("https://example.com/", "javascript:alert(1)"), None);
+        assert_eq!(truncate("héllo world", 5), "héllo…");
+        assert_eq!(truncate("short", 5), "short");
+    }
+
+    #[tokio::test]
+    async fn test_previews_are_fetched_once_and_cached() {
+        let Some(pool) = testing::pool().await else { return };
+        let alice = testing::user(&pool, "alice").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &alice, &[]).await;
+        let fetcher = Arc::new(StubFetcher::new(&[
+            (
+                "https://example.com/article",
+                "text/html; charset=utf-8",
+                r#"<meta property="og:title" content="Article"><meta property="og:image" content="/cover.png">"#,
+            ),
+            (
+                "https://example.com/video",
+                "text/html",
+                r#"<title>Video page</title><link type="application/json+oembed" href="/oembed">"#,
+            ),
+            ("https://example.com/oembed", "application/json", r#"{"title": "Video", "provider_name": "Example"}"#),
+            ("https://example.com/data.json", "application/json", "{}"),
+        ]));
+        let services = Services::with_page_fetcher(pool.clone(), AppConfig::load().unwrap(), fetcher.clone());
+
+        let content = "https://example.com/article https://example.com/video https://example.com/missing";
+        let send = || {
+            let request = SendMessageRequest {
+                content: content.to_string(),
+                ..Default::default()
+            };
+            services.messages.send_message(&channel.id, &alice.id, request)
+        };
+
+        // Pages that fail or have no title are not attached
+        let first = send().await.unwrap();
+        assert!(services.link_previews.unfurl_message(&first.id, content).await.unwrap());
+        let previews = LinkPreviewRepository::find_by_message(&pool, &first.id).await.unwrap();
+        assert_eq!(previews.len(), 2);
+        assert_eq!(previews[0].title.as_deref(), Some("Article"));
+        assert_eq!(previews[0].image_url.as_deref(), Some("https://example.com/cover.png"));
+        assert_eq!(previews[1].title.as_deref(), Some("Video"));
+        assert_eq!(previews[1].site_name.as_deref(), Some("Example"));
+        assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 4);
+
+        // Later messages with the same links use the cache, failures included
+        let second = send().await.unwrap();
+        assert!(services.link_previews.unfurl_message(&second.id, content).await.unwrap());
+        assert!(!services.link_previews.unfurl_message(&first.id, content).await.unwrap());
+        assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 4);
+
+        // Failures expire after an hour, previews after a day
+        for (url, age) in [("https://example.com/missing", 2), ("https://example.com/video", 2)] {
+            let mut preview = LinkPreviewRepository::find_by_url(&pool, url).await.unwrap().unwrap();
+            preview.fetched_at = Utc::now() - Duration::hours(age);
+            LinkPreviewRepository::upsert(&pool, &preview).await.unwrap();
+        }
+        let third = send().await.unwrap();
+        services.link_previews.unfurl_message(&third.id, content).await.unwrap();
+        assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 5);
+
+        let mut preview = LinkPreviewRepository::find_by_url(&pool, "https://example.com/article").await.unwrap().unwrap();
+        preview.fetched_at = Utc::now() - Duration::hours(25);
+        LinkPreviewRepository::upsert(&pool, &preview).await.unwrap();
+        services.link_previews.unfurl_message(&third.id, content).await.unwrap();
+        assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 6);
+
+        // Other documents are not previewed, and an edit that drops the links drops their previews
+        assert!(services.link_previews.unfurl_message(&third.id, "https://example.com/data.json").await.unwrap());
+        assert!(LinkPreviewRepository::find_by_message(&pool, &third.id).await.unwrap().is_empty());
+    }
+}
@@ -1,26 +1,62 @@
 //! Message service
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use shared::dto::{
-    FileAttachmentResponse, MessageResponse, ReactionResponse, SendMessageRequest,
-    UpdateMessageRequest, UserResponse,
+    CreatePollRequest, EphemeralMessageResponse, FlaggedMessageResponse, ForwardMessageRequest, MessageResponse,
+    MessageRevisionResponse, PaginatedResponse, PollResponse, ScheduledMessageResponse, SendEphemeralMessageRequest,
+    SendMessageRequest, UpdateMessageRequest, UpdateScheduledMessageRequest, UserResponse,
 };
+use shared::emoji;
+use shared::filters::ContentFilter;
+use shared::markdown;
+use shared::mentions::parse_mentions;
+use shared::pagination::{paginate, Cursor};
 use shared::error::AppError;
-use shared::models::MessageType;
-use sqlx::PgPool;
-use std::collections::HashMap;
+use shared::models::{
+    Channel, ChannelType, HistoryVisibility, Message, MessageFormat, MessageType,
+    Poll, ScheduledMessage, ScheduledMessageStatus, TeamRole,
+};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::db::{ChannelRepository, FileRepository, MessageRepository, UserRepository};
+use crate::db::{
+    ChannelRepository, ContentRuleRepository, CustomEmojiRepository, FileRepository,
+    ForwardRepository, MessageRepository, ModerationRepository, PollRepository, ScheduledMessageRepository,
+    TeamRepository, UserRepository,
+};
+use crate::services::hydration::{self, MessageHydrator, PgMessageDetailsSource};
+
+/// "Seen by" lists are only available in DMs and channels up to this size
+const SEEN_BY_MAX_MEMBERS: i64 = 10;
+
+/// Longest reason accepted for a moderation action
+const MAX_MODERATION_REASON_LEN: usize = 500;
+
+/// Longest text accepted for a poll option
+const MAX_POLL_OPTION_LEN: usize = 200;
+
+/// How long a client nonce identifies a send, so retries within it are not posted twice
+const CLIENT_NONCE_WINDOW_HOURS: i64 = 24;
+/// How long a claimed scheduled message may go unsent before another dispatch picks it up
+const SCHEDULED_MESSAGE_CLAIM_TIMEOUT_MINUTES: i64 = 5;
+
+/// Whole seconds from `now` until `until`, rounded up and at least one
+fn seconds_until(now: DateTime<Utc>, until: DateTime<Utc>) -> u64 {
+    let millis = (until - now).num_milliseconds().max(0) as u64;
+    millis.div_ceil(1000).max(1)
+}
 
 pub struct MessageService {
     pool: Arc<PgPool>,
+    hydrator: MessageHydrator,
 }
 
 impl MessageService {
     pub fn new(pool: Arc<PgPool>) -> Self {
-        Self { pool }
+        let hydrator = MessageHydrator::new(Arc::new(PgMessageDetailsSource::new(pool.clone())));
+        Self { pool, hydrator }
     }
 
     pub async fn send_message(
@@ -29,6 +65,24 @@ impl MessageService {
         sender_id: &Uuid,
         request: SendMessageRequest,
     ) -> Result<MessageResponse, AppError> {
+        let (message, _) = self.send_message_once(channel_id, sender_id, request).await?;
+        Ok(message)
+    }
+
+    /// Send a message, or get the one already sent with the request's client nonce
+    /// when a send is retried. The flag is true if this call posted the message.
+    pub async fn send_message_once(
+        &self,
+        channel_id: &Uuid,
+        sender_id: &Uuid,
+        request: SendMessageRequest,
+    ) -> Result<(MessageResponse, bool), AppError> {
+        if let Some(client_nonce) = &request.client_nonce {
+            if let Some(message) = self.find_sent_message(channel_id, sender_id, client_nonce).await? {
+                return Ok((message, false));
+            }
+        }
+
         // Verify sender has access to channel
         if !ChannelRepository::is_member(&self.pool, channel_id, sender_id)
             .await
@@ -39,19 +93,64 @@ impl MessageService {
             ));
         }
 
+        let channel = self.check_post_permission(channel_id, sender_id).await?;
+        let mut tx = self.pool.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if let Err(e) = self.check_rate_limit(&mut tx, &channel, sender_id).await {
+            // A retry that waited for the sender lock behind the original send is rate
+            // limited by it, and has committed by now
+            if let (AppError::RateLimitExceeded { .. }, Some(client_nonce)) = (&e, &request.client_nonce) {
+                if let Some(message) = self.find_sent_message(channel_id, sender_id, client_nonce).await? {
+                    return Ok((message, false));
+                }
+            }
+            return Err(e);
+        }
+
         let message_type = request.message_type.unwrap_or(MessageType::Text);
+        if message_type == MessageType::Poll {
+            return Err(AppError::ValidationError(
+                "Polls are created through the poll endpoint".to_string(),
+            ));
+        }
+
+        let (content, flags) = self.apply_content_rules(&channel, &request.content).await?;
 
         let message = MessageRepository::create(
-            &self.pool,
+            &mut tx,
             channel_id,
             sender_id,
-            &request.content,
+            &content,
             message_type,
+            request.format.unwrap_or_default(),
             request.reply_to_id.as_ref(),
+            request.ttl_seconds.map(|ttl| Utc::now() + Duration::seconds(ttl)),
         )
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
+        if let Some(client_nonce) = &request.client_nonce {
+            let expired_before = Utc::now() - Duration::hours(CLIENT_NONCE_WINDOW_HOURS);
+            let reserved =
+                MessageRepository::reserve_client_nonce(&mut tx, sender_id, client_nonce, &message.id, expired_before)
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+            // A concurrent send with this nonce got there first and has committed by now
+            if !reserved {
+                tx.rollback().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                return match self.find_sent_message(channel_id, sender_id, client_nonce).await? {
+                    Some(message) => Ok((message, false)),
+                    None => Err(AppError::ConflictError(
+                        "This client nonce is already in use".to_string(),
+                    )),
+                };
+            }
+        }
+        tx.commit().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.store_mentions(&message.id, &message.content, message.format).await?;
+        self.flag_message(&message.id, &flags).await?;
+
         // Attach files if any
         if let Some(attachment_ids) = request.attachment_ids {
             for file_id in attachment_ids {
@@ -59,41 +158,41 @@ impl MessageService {
             }
         }
 
-        self.get_message_response(&message.id, sender_id).await
+        let message = self.get_message_response(&message.id, sender_id).await?;
+        Ok((message, true))
     }
 
-    pub async fn get_message(
+    /// The message the sender already posted with this nonce, when a send is retried
+    pub async fn find_sent_message(
         &self,
-        message_id: &Uuid,
-        user_id: &Uuid,
-    ) -> Result<MessageResponse, AppError> {
-        let message = MessageRepository::find_by_id(&self.pool, message_id)
+        channel_id: &Uuid,
+        sender_id: &Uuid,
+        client_nonce: &str,
+    ) -> Result<Option<MessageResponse>, AppError> {
+        let since = Utc::now() - Duration::hours(CLIENT_NONCE_WINDOW_HOURS);
+        let message = MessageRepository::find_by_client_nonce(&self.pool, sender_id, client_nonce, since)
             .await
-            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        // Verify user has access to channel
-        if !ChannelRepository::is_member(&self.pool, &message.channel_id, user_id)
-            .await
-            .unwrap_or(false)
-        {
-            return Err(AppError::AuthorizationError(
-                "You don't have access to this message".to_string(),
-            ));
+        match message {
+            Some(message) if message.channel_id != *channel_id => Err(AppError::ConflictError(
+                "This client nonce was already used in another channel".to_string(),
+            )),
+            Some(message) => Ok(self.hydrator.hydrate(vec![message], sender_id).await?.pop()),
+            None => Ok(None),
         }
-
-        self.get_message_response(message_id, user_id).await
     }
 
-    pub async fn list_messages(
+    /// Store a message to be posted at `send_at`. Access is checked now and again
+    /// when the dispatcher sends it.
+    pub async fn schedule_message(
         &self,
         channel_id: &Uuid,
-        user_id: &Uuid,
-        limit: i64,
-        before: Option<DateTime<Utc>>,
-        after: Option<DateTime<Utc>>,
-    ) -> Result<Vec<MessageResponse>, AppError> {
-        // Verify user has access to channel
-        if !ChannelRepository::is_member(&self.pool, channel_id, user_id)
+        sender_id: &Uuid,
+        request: SendMessageRequest,
+        send_at: DateTime<Utc>,
+    ) -> Result<ScheduledMessageResponse, AppError> {
+        if !ChannelRepository::is_member(&self.pool, channel_id, sender_id)
             .await
             .unwrap_or(false)
         {
@@ -102,274 +201,2108 @@ impl MessageService {
             ));
         }
 
-        let messages = MessageRepository::find_by_channel(&self.pool, channel_id, limit, before, after)
-            .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        self.check_post_permission(channel_id, sender_id).await?;
 
-        let mut responses = Vec::new();
-        for message in messages {
-            if let Ok(response) = self.get_message_response(&message.id, user_id).await {
-                responses.push(response);
-            }
+        if request.message_type == Some(MessageType::Poll) {
+            return Err(AppError::ValidationError(
+                "Polls can't be scheduled".to_string(),
+            ));
         }
 
-        // Reverse to get chronological order
-        responses.reverse();
+        if request.ttl_seconds.is_some() {
+            return Err(AppError::ValidationError(
+                "Self-destructing messages can't be scheduled".to_string(),
+            ));
+        }
+
+        let scheduled = ScheduledMessageRepository::create(
+            &self.pool,
+            channel_id,
+            sender_id,
+            &request.content,
+            request.message_type.unwrap_or(MessageType::Text),
+            request.format.unwrap_or_default(),
+            request.reply_to_id.as_ref(),
+            &request.attachment_ids.unwrap_or_default(),
+            send_at,
+            request.client_nonce.as_deref(),
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        if scheduled.channel_id != *channel_id {
+            return Err(AppError::ConflictError(
+                "This client nonce was already used in another channel".to_string(),
+            ));
+        }
 
-        Ok(responses)
+        Ok(Self::scheduled_message_response(scheduled))
     }
 
-    pub async fn update_message(
+    pub async fn list_scheduled_messages(
         &self,
-        message_id: &Uuid,
         user_id: &Uuid,
-        request: UpdateMessageRequest,
-    ) -> Result<MessageResponse, AppError> {
-        let message = MessageRepository::find_by_id(&self.pool, message_id)
+    ) -> Result<Vec<ScheduledMessageResponse>, AppError> {
+        let scheduled = ScheduledMessageRepository::find_by_sender(&self.pool, user_id)
             .await
-            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        // Only sender can edit
-        if message.sender_id != *user_id {
-            return Err(AppError::AuthorizationError(
-                "You can only edit your own messages".to_string(),
+        Ok(scheduled.into_iter().map(Self::scheduled_message_response).collect())
+    }
+
+    pub async fn update_scheduled_message(
+        &self,
+        scheduled_id: &Uuid,
+        user_id: &Uuid,
+        request: UpdateScheduledMessageRequest,
+    ) -> Result<ScheduledMessageResponse, AppError> {
+        let scheduled = self.find_own_scheduled_message(scheduled_id, user_id).await?;
+
+        if scheduled.status == ScheduledMessageStatus::Sending {
+            return Err(AppError::ConflictError(
+                "The scheduled message is being sent".to_string(),
+            ));
+        }
+
+        if request.send_at.is_some_and(|send_at| send_at <= Utc::now()) {
+            return Err(AppError::ValidationError(
+                "Scheduled time must be in the future".to_string(),
             ));
         }
 
-        MessageRepository::update(&self.pool, message_id, &request.content)
+        let scheduled = ScheduledMessageRepository::update(
+            &self.pool,
+            scheduled_id,
+            request.content.as_deref(),
+            request.send_at,
+        )
+        .await
+        .map_err(|_| AppError::NotFoundError("Scheduled message not found".to_string()))?;
+
+        Ok(Self::scheduled_message_response(scheduled))
+    }
+
+    pub async fn cancel_scheduled_message(
+        &self,
+        scheduled_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<(), AppError> {
+        self.find_own_scheduled_message(scheduled_id, user_id).await?;
+
+        let deleted = ScheduledMessageRepository::delete(&self.pool, scheduled_id)
             .await
             .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        self.get_message_response(message_id, user_id).await
+        if !deleted {
+            // The dispatcher took it in the meantime
+            return Err(AppError::NotFoundError("Scheduled message not found".to_string()));
+        }
+
+        Ok(())
     }
 
-    pub async fn delete_message(&self, message_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
-        let message = MessageRepository::find_by_id(&self.pool, message_id)
+    /// Purge up to `limit` messages whose time to live has run out. Returns each
+    /// message as it was before the purge, with the storage paths of its removed
+    /// attachments for the caller to delete.
+    pub async fn purge_expired_messages(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<(Message, Vec<String>)>, AppError> {
+        let expired = MessageRepository::find_expired(&self.pool, Utc::now(), limit)
             .await
-            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        // Only sender can delete
-        if message.sender_id != *user_id {
-            return Err(AppError::AuthorizationError(
-                "You can only delete your own messages".to_string(),
-            ));
+        let mut purged = Vec::with_capacity(expired.len());
+        for message_id in expired {
+            let (message, storage_paths) = MessageRepository::purge_expired(&self.pool, &message_id)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            if let Some(message) = message {
+                purged.push((message, storage_paths));
+            }
         }
 
-        MessageRepository::delete(&self.pool, message_id)
+        Ok(purged)
+    }
+
+    /// Claim scheduled messages that are due, for the dispatcher to send
+    pub async fn claim_due_scheduled_messages(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<ScheduledMessage>, AppError> {
+        let now = Utc::now();
+        let stale_before = now - Duration::minutes(SCHEDULED_MESSAGE_CLAIM_TIMEOUT_MINUTES);
+        let mut due = ScheduledMessageRepository::claim_due(&self.pool, now, stale_before, limit)
             .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        due.sort_by_key(|m| m.send_at);
+        Ok(due)
     }
 
-    pub async fn add_reaction(
+    /// Post a claimed scheduled message. It is removed once posted, queued again while
+    /// slow mode holds it back, and otherwise kept as failed for its author to retry.
+    /// Returns the message if this call posted it.
+    pub async fn dispatch_scheduled_message(
         &self,
-        message_id: &Uuid,
+        scheduled: ScheduledMessage,
+    ) -> Result<Option<MessageResponse>, AppError> {
+        // Sending under the request's nonce, or else the scheduled message's id, means a
+        // dispatcher that stopped after posting it, but before removing it, doesn't post
+        // it twice
+        let client_nonce = scheduled.client_nonce.unwrap_or_else(|| scheduled.id.to_string());
+        let request = SendMessageRequest {
+            content: scheduled.content,
+            message_type: Some(scheduled.message_type),
+            format: Some(scheduled.format),
+            reply_to_id: scheduled.reply_to_id,
+            attachment_ids: Some(scheduled.attachment_ids),
+            send_at: None,
+            client_nonce: Some(client_nonce),
+            ttl_seconds: None,
+        };
+
+        match self.send_message_once(&scheduled.channel_id, &scheduled.sender_id, request).await {
+            Ok((message, posted)) => {
+                ScheduledMessageRepository::remove_sent(&self.pool, &scheduled.id)
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                Ok(posted.then_some(message))
+            }
+            Err(AppError::RateLimitExceeded { retry_after }) => {
+                let send_at = Utc::now() + Duration::seconds(retry_after as i64);
+                ScheduledMessageRepository::requeue(&self.pool, &scheduled.id, send_at)
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                Ok(None)
+            }
+            Err(e) => {
+                ScheduledMessageRepository::mark_failed(&self.pool, &scheduled.id, &e.to_string())
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Queue a failed scheduled message to be sent right away
+    pub async fn retry_scheduled_message(
+        &self,
+        scheduled_id: &Uuid,
         user_id: &Uuid,
-        emoji: &str,
-    ) -> Result<(), AppError> {
-        let message = MessageRepository::find_by_id(&self.pool, message_id)
-            .await
-            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+    ) -> Result<ScheduledMessageResponse, AppError> {
+        let scheduled = self.find_own_scheduled_message(scheduled_id, user_id).await?;
 
-        // Verify user has access to channel
-        if !ChannelRepository::is_member(&self.pool, &message.channel_id, user_id)
-            .await
-            .unwrap_or(false)
-        {
-            return Err(AppError::AuthorizationError(
-                "You don't have access to this message".to_string(),
+        if scheduled.status != ScheduledMessageStatus::Failed {
+            return Err(AppError::ConflictError(
+                "Only failed scheduled messages can be retried".to_string(),
             ));
         }
 
-        // Check if already reacted
-        if MessageRepository::has_user_reacted(&self.pool, message_id, user_id, emoji)
+        let scheduled = ScheduledMessageRepository::requeue(&self.pool, scheduled_id, Utc::now())
             .await
-            .unwrap_or(false)
-        {
-            return Err(AppError::ConflictError("You already reacted with this emoji".to_string()));
-        }
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        MessageRepository::add_reaction(&self.pool, message_id, user_id, emoji)
+        Ok(Self::scheduled_message_response(scheduled))
+    }
+
+    async fn find_own_scheduled_message(
+        &self,
+        scheduled_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<ScheduledMessage, AppError> {
+        let scheduled = ScheduledMessageRepository::find_by_id(&self.pool, scheduled_id)
             .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            .map_err(|_| AppError::NotFoundError("Scheduled message not found".to_string()))?;
 
-        Ok(())
+        // Scheduled messages are private to their author
+        if scheduled.sender_id != *user_id {
+            return Err(AppError::NotFoundError("Scheduled message not found".to_string()));
+        }
+
+        Ok(scheduled)
     }
 
-    pub async fn remove_reaction(
+    fn scheduled_message_response(scheduled: ScheduledMessage) -> ScheduledMessageResponse {
+        ScheduledMessageResponse {
+            id: scheduled.id,
+            channel_id: scheduled.channel_id,
+            content: scheduled.content,
+            message_type: scheduled.message_type,
+            format: scheduled.format,
+            reply_to_id: scheduled.reply_to_id,
+            attachment_ids: scheduled.attachment_ids,
+            send_at: scheduled.send_at,
+            status: scheduled.status,
+            error: scheduled.error,
+            created_at: scheduled.created_at,
+            updated_at: scheduled.updated_at,
+        }
+    }
+
+    pub async fn get_message(
         &self,
         message_id: &Uuid,
         user_id: &Uuid,
-        emoji: &str,
-    ) -> Result<(), AppError> {
-        MessageRepository::remove_reaction(&self.pool, message_id, user_id, emoji)
+    ) -> Result<MessageResponse, AppError> {
+        let message = MessageRepository::find_by_id(&self.pool, message_id)
             .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))
+            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+        self.check_message_access(&message, user_id).await?;
+
+        self.get_message_response(message_id, user_id).await
     }
 
-    pub async fn search_messages(
+    /// Previous versions of an edited message, oldest first
+    pub async fn get_message_history(
         &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
         user_id: &Uuid,
-        query: &str,
-        channel_id: Option<&Uuid>,
-        from_user_id: Option<&Uuid>,
-        from_date: Option<DateTime<Utc>>,
-        to_date: Option<DateTime<Utc>>,
-        limit: i64,
-        offset: i64,
-    ) -> Result<(Vec<MessageResponse>, i64), AppError> {
-        // If channel_id is specified, verify access
-        if let Some(cid) = channel_id {
-            if !ChannelRepository::is_member(&self.pool, cid, user_id)
-                .await
-                .unwrap_or(false)
-            {
-                return Err(AppError::AuthorizationError(
-                    "You don't have access to this channel".to_string(),
-                ));
-            }
+    ) -> Result<Vec<MessageRevisionResponse>, AppError> {
+        let message = MessageRepository::find_by_id(&self.pool, message_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+        if message.channel_id != *channel_id {
+            return Err(AppError::NotFoundError("Message not found".to_string()));
         }
 
-        let (messages, total_count) = MessageRepository::search(
-            &self.pool,
-            query,
-            channel_id,
-            from_user_id,
-            from_date,
-            to_date,
-            limit,
-            offset,
-        )
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        self.check_message_access(&message, user_id).await?;
 
-        let mut responses = Vec::new();
-        for message in messages {
-            // Filter out messages from channels user doesn't have access to
-            if ChannelRepository::is_member(&self.pool, &message.channel_id, user_id)
-                .await
-                .unwrap_or(false)
-            {
-                if let Ok(response) = self.get_message_response(&message.id, user_id).await {
-                    responses.push(response);
-                }
-            }
+        // The content of deleted messages is only kept for auditing
+        if message.deleted_at.is_some() {
+            return Ok(vec![]);
         }
 
-        Ok((responses, total_count))
+        let revisions = MessageRepository::find_revisions(&self.pool, message_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        // Text a moderator edited out stays visible to moderators only
+        let can_moderate = self.can_moderate(&message.channel_id, user_id).await?;
+
+        Ok(revisions
+            .into_iter()
+            .filter(|r| can_moderate || r.replaced_by == message.sender_id)
+            .map(|r| MessageRevisionResponse {
+                content: r.content,
+                created_at: r.created_at,
+                replaced_at: r.replaced_at,
+            })
+            .collect())
     }
 
-    async fn get_message_response(
+    /// Members who have read a message, for DMs and small channels
+    pub async fn get_seen_by(
         &self,
+        channel_id: &Uuid,
         message_id: &Uuid,
         user_id: &Uuid,
-    ) -> Result<MessageResponse, AppError> {
+    ) -> Result<Vec<UserResponse>, AppError> {
         let message = MessageRepository::find_by_id(&self.pool, message_id)
             .await
             .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
 
-        let sender = UserRepository::find_by_id(&self.pool, &message.sender_id)
+        if message.channel_id != *channel_id {
+            return Err(AppError::NotFoundError("Message not found".to_string()));
+        }
+
+        if !ChannelRepository::is_member(&self.pool, channel_id, user_id)
             .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You don't have access to this message".to_string(),
+            ));
+        }
 
-        // Get reply_to message if exists
-        let reply_to = if let Some(reply_id) = message.reply_to_id {
-            if let Ok(reply_msg) = MessageRepository::find_by_id(&self.pool, &reply_id).await {
-                if let Ok(reply_sender) = UserRepository::find_by_id(&self.pool, &reply_msg.sender_id).await {
-                    Some(Box::new(MessageResponse {
-                        id: reply_msg.id,
-                        channel_id: reply_msg.channel_id,
-                        sender: UserResponse {
-                            id: reply_sender.id,
-                            email: reply_sender.email,
-                            username: reply_sender.username,
-                            display_name: reply_sender.display_name,
-                            avatar_url: reply_sender.avatar_url,
-                            status: reply_sender.status,
-                            status_message: reply_sender.status_message,
-                            last_seen: reply_sender.last_seen,
-                            created_at: reply_sender.created_at,
-                        },
-                        content: reply_msg.content,
-                        message_type: reply_msg.message_type,
-                        reply_to: None,
-                        reactions: vec![],
-                        attachments: vec![],
-                        edited: reply_msg.edited,
-                        created_at: reply_msg.created_at,
-                        updated_at: reply_msg.updated_at,
-                    }))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        if !self.read_receipts_available(channel_id).await? {
+            return Err(AppError::BadRequest(format!(
+                "Read receipts are only available in channels with up to {} members",
+                SEEN_BY_MAX_MEMBERS
+            )));
+        }
 
-        // Get reactions
-        let reactions = MessageRepository::get_reactions(&self.pool, message_id)
+        // Receipts are reciprocal: users who don't share theirs don't see others'
+        if !UserRepository::get_read_receipts_enabled(&self.pool, user_id)
             .await
-            .unwrap_or_default();
-
-        // Group reactions by emoji
-        let mut reaction_map: HashMap<String, (i64, Vec<Uuid>)> = HashMap::new();
-        for reaction in reactions {
-            let entry = reaction_map.entry(reaction.emoji.clone()).or_insert((0, vec![]));
-            entry.0 += 1;
-            entry.1.push(reaction.user_id);
+            .unwrap_or(false)
+        {
+            return Ok(vec![]);
         }
 
-        let reaction_responses: Vec<ReactionResponse> = reaction_map
+        let readers = UserRepository::find_channel_readers(
+            &self.pool,
+            channel_id,
+            &message.created_at,
+            &message.sender_id,
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(readers
             .into_iter()
-            .map(|(emoji, (count, users))| ReactionResponse {
-                emoji,
-                count,
-                reacted_by_me: users.contains(user_id),
-                users,
+            .map(|user| UserResponse {
+                id: user.id,
+                email: user.email,
+                username: user.username,
+                display_name: user.display_name,
+                avatar_url: user.avatar_url,
+                status: user.status,
+                status_message: user.status_message,
+                last_seen: user.last_seen,
+                created_at: user.created_at,
             })
-            .collect();
+            .collect())
+    }
 
-        // Get attachments
-        let attachments = FileRepository::find_by_message(&self.pool, message_id)
+    /// Members to send a user's new read position in the channel to. Like the seen-by
+    /// list, receipts are limited to DMs and small channels, and are reciprocal: they
+    /// aren't shared by or with users who turned them off.
+    pub async fn read_receipt_recipients(&self, channel_id: &Uuid, user_id: &Uuid) -> Result<Vec<Uuid>, AppError> {
+        if !UserRepository::get_read_receipts_enabled(&self.pool, user_id)
             .await
-            .unwrap_or_default();
+            .unwrap_or(false)
+        {
+            return Ok(vec![]);
+        }
 
-        let attachment_responses: Vec<FileAttachmentResponse> = attachments
-            .into_iter()
-            .map(|f| FileAttachmentResponse {
-                id: f.id,
-                filename: f.filename,
-                file_size: f.file_size,
-                mime_type: f.mime_type,
-                download_url: format!("/api/v1/files/{}/download", f.id),
-                created_at: f.created_at,
-            })
+        if !self.read_receipts_available(channel_id).await? {
+            return Ok(vec![]);
+        }
+
+        UserRepository::find_receipt_sharing_members(&self.pool, channel_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    /// Read receipts are shown in DMs and in channels of up to `SEEN_BY_MAX_MEMBERS` members
+    async fn read_receipts_available(&self, channel_id: &Uuid) -> Result<bool, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        if channel.channel_type == ChannelType::DirectMessage {
+            return Ok(true);
+        }
+
+        let member_count = ChannelRepository::get_member_count(&self.pool, channel_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        Ok(member_count <= SEEN_BY_MAX_MEMBERS)
+    }
+
+    pub async fn list_messages(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+        limit: i64,
+        before: Option<Cursor>,
+    ) -> Result<PaginatedResponse<MessageResponse>, AppError> {
+        // Verify user has access to channel
+        if !ChannelRepository::is_member(&self.pool, channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You don't have access to this channel".to_string(),
+            ));
+        }
+
+        // Hide messages from before the user joined if the channel's history is restricted
+        let visible_since = self.visible_since(channel_id, user_id).await?;
+
+        let messages = MessageRepository::find_by_channel(&self.pool, channel_id, limit + 1, before, visible_since)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let page = paginate(messages, limit, |m| Cursor::new(m.created_at, m.id));
+        let mut items = self.hydrator.hydrate(page.items, user_id).await?;
+
+        // Reverse to get chronological order
+        items.reverse();
+
+        Ok(PaginatedResponse {
+            items,
+            has_more: page.has_more,
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    pub async fn update_message(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+        request: UpdateMessageRequest,
+    ) -> Result<MessageResponse, AppError> {
+        let message = MessageRepository::find_by_id(&self.pool, message_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+        if message.channel_id != *channel_id || message.deleted_at.is_some() {
+            return Err(AppError::NotFoundError("Message not found".to_string()));
+        }
+
+        if message.message_type == MessageType::Poll {
+            return Err(AppError::ValidationError("Polls can't be edited".to_string()));
+        }
+
+        let moderation_reason = self
+            .moderation_reason(
+                &message,
+                user_id,
+                request.reason.as_deref(),
+                "You can only edit your own messages",
+            )
+            .await?;
+        let channel = if moderation_reason.is_none() {
+            self.check_channel_writable(&message.channel_id).await?
+        } else {
+            ChannelRepository::find_by_id(&self.pool, &message.channel_id)
+                .await
+                .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?
+        };
+
+        let (content, flags) = self.apply_content_rules(&channel, &request.content).await?;
+
+        MessageRepository::update(&self.pool, message_id, &content, user_id, moderation_reason)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.store_mentions(message_id, &content, message.format).await?;
+        self.flag_message(message_id, &flags).await?;
+
+        self.get_message_response(message_id, user_id).await
+    }
+
+    /// Messages the team's content rules flagged, newest first, for its moderators
+    pub async fn list_flagged_messages(
+        &self,
+        team_id: &Uuid,
+        user_id: &Uuid,
+        limit: i64,
+        before: Option<Cursor>,
+    ) -> Result<PaginatedResponse<FlaggedMessageResponse>, AppError> {
+        self.check_team_moderator(team_id, user_id).await?;
+
+        let flags = ModerationRepository::find_flagged(&self.pool, team_id, limit + 1, before)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let page = paginate(flags, limit, |f| Cursor::new(f.created_at, f.id));
+
+        let message_ids: Vec<Uuid> = page.items.iter().map(|f| f.message_id).collect();
+        let messages = MessageRepository::find_by_ids(&self.pool, &message_ids)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let messages: HashMap<Uuid, MessageResponse> = self
+            .hydrator
+            .hydrate(messages, user_id)
+            .await?
+            .into_iter()
+            .map(|m| (m.id, m))
+            .collect();
+
+        let items = page
+            .items
+            .into_iter()
+            .filter_map(|flag| {
+                Some(FlaggedMessageResponse {
+                    id: flag.id,
+                    message: messages.get(&flag.message_id)?.clone(),
+                    rule_id: flag.rule_id,
+                    rule_name: flag.rule_name,
+                    created_at: flag.created_at,
+                })
+            })
             .collect();
 
-        Ok(MessageResponse {
-            id: message.id,
-            channel_id: message.channel_id,
-            sender: UserResponse {
-                id: sender.id,
-                email: sender.email,
-                username: sender.username,
-                display_name: sender.display_name,
-                avatar_url: sender.avatar_url,
-                status: sender.status,
-                status_message: sender.status_message,
-                last_seen: sender.last_seen,
-                created_at: sender.created_at,
-            },
-            content: message.content,
-            message_type: message.message_type,
-            reply_to,
-            reactions: reaction_responses,
-            attachments: attachment_responses,
-            edited: message.edited,
-            created_at: message.created_at,
-            updated_at: message.updated_at,
+        Ok(PaginatedResponse {
+            items,
+            has_more: page.has_more,
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Take a flagged message off the moderation queue, leaving the message as is
+    pub async fn resolve_flagged_message(
+        &self,
+        team_id: &Uuid,
+        flag_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<(), AppError> {
+        self.check_team_moderator(team_id, user_id).await?;
+
+        let resolved = ModerationRepository::resolve_flag(&self.pool, flag_id, team_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if !resolved {
+            return Err(AppError::NotFoundError("Flagged message not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Delete a message, leaving a tombstone. Returns whether a moderator removed it.
+    pub async fn delete_message(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+        reason: Option<&str>,
+    ) -> Result<bool, AppError> {
+        let message = MessageRepository::find_by_id(&self.pool, message_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+        if message.channel_id != *channel_id || message.deleted_at.is_some() {
+            return Err(AppError::NotFoundError("Message not found".to_string()));
+        }
+
+        let moderation_reason = self
+            .moderation_reason(&message, user_id, reason, "You can only delete your own messages")
+            .await?;
+        if moderation_reason.is_none() {
+            self.check_channel_writable(&message.channel_id).await?;
+        }
+
+        MessageRepository::soft_delete(&self.pool, message_id, user_id, moderation_reason)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(moderation_reason.is_some())
+    }
+
+    pub async fn add_reaction(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+        emoji: &str,
+    ) -> Result<(), AppError> {
+        let message = self.find_reactable_message(channel_id, message_id, user_id).await?;
+        self.check_reaction_emoji(&message.channel_id, emoji).await?;
+
+        // Check if already reacted
+        if MessageRepository::has_user_reacted(&self.pool, message_id, user_id, emoji)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::ConflictError("You already reacted with this emoji".to_string()));
+        }
+
+        MessageRepository::add_reaction(&self.pool, message_id, user_id, emoji)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Returns whether the user had reacted with the emoji
+    pub async fn remove_reaction(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+        emoji: &str,
+    ) -> Result<bool, AppError> {
+        self.find_reactable_message(channel_id, message_id, user_id).await?;
+
+        MessageRepository::remove_reaction(&self.pool, message_id, user_id, emoji)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    /// Share a message into another channel or DM. The new message embeds a snapshot
+    /// of the original, and the original's attachments are linked rather than copied.
+    pub async fn forward_message(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+        request: ForwardMessageRequest,
+    ) -> Result<MessageResponse, AppError> {
+        let source = MessageRepository::find_by_id(&self.pool, message_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+        if source.channel_id != *channel_id || source.deleted_at.is_some() {
+            return Err(AppError::NotFoundError("Message not found".to_string()));
+        }
+
+        // Copies would outlive the original
+        if source.expires_at.is_some() {
+            return Err(AppError::ValidationError(
+                "Self-destructing messages can't be forwarded".to_string(),
+            ));
+        }
+
+        self.check_message_access(&source, user_id).await?;
+
+        let target_channel_id = request.target_channel_id;
+        if !ChannelRepository::is_member(&self.pool, &target_channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You don't have access to this channel".to_string(),
+            ));
+        }
+
+        let channel = self.check_post_permission(&target_channel_id, user_id).await?;
+        let mut tx = self.pool.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        self.check_rate_limit(&mut tx, &channel, user_id).await?;
+
+        let comment = request.comment.unwrap_or_default();
+        let (comment, mut flags) = self.apply_content_rules(&channel, comment.trim()).await?;
+        // The copy is shown in the target channel, so that team's rules apply to it too
+        let (snapshot, snapshot_flags) = self.apply_content_rules(&channel, &source.content).await?;
+        flags.extend(snapshot_flags);
+        flags.sort();
+        flags.dedup();
+
+        let message = MessageRepository::create(
+            &mut tx,
+            &target_channel_id,
+            user_id,
+            &comment,
+            MessageType::Text,
+            request.format.unwrap_or_default(),
+            None,
+            None,
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        ForwardRepository::create(
+            &mut tx,
+            &message.id,
+            &source.id,
+            &source.channel_id,
+            &source.sender_id,
+            &snapshot,
+            source.format,
+            source.created_at,
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let attachments = FileRepository::find_by_message(&self.pool, &source.id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        for file in attachments {
+            FileRepository::link_to_message(&mut tx, &file, &message.id, &target_channel_id, user_id)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        }
+        tx.commit().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.store_mentions(&message.id, &message.content, message.format).await?;
+        self.flag_message(&message.id, &flags).await?;
+
+        self.get_message_response(&message.id, user_id).await
+    }
+
+    /// Build an ephemeral message for one member of the channel. It is not stored and
+    /// leaves nothing for moderators to flag, so only the team's moderators may send
+    /// one, and it passes the same posting and content checks as a message.
+    pub async fn create_ephemeral_message(
+        &self,
+        channel_id: &Uuid,
+        sender_id: &Uuid,
+        request: SendEphemeralMessageRequest,
+    ) -> Result<EphemeralMessageResponse, AppError> {
+        if !ChannelRepository::is_member(&self.pool, channel_id, sender_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You don't have access to this channel".to_string(),
+            ));
+        }
+
+        if !self.can_moderate(channel_id, sender_id).await? {
+            return Err(AppError::AuthorizationError(
+                "Only moderators can send ephemeral messages".to_string(),
+            ));
+        }
+
+        let channel = self.check_post_permission(channel_id, sender_id).await?;
+
+        if !ChannelRepository::is_member(&self.pool, channel_id, &request.user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::NotFoundError(
+                "User is not a member of this channel".to_string(),
+            ));
+        }
+
+        let (content, _) = self.apply_content_rules(&channel, &request.content).await?;
+
+        Ok(EphemeralMessageResponse {
+            id: Uuid::new_v4(),
+            channel_id: *channel_id,
+            sender_id: Some(*sender_id),
+            content,
+            format: request.format.unwrap_or_default(),
+            created_at: Utc::now(),
         })
     }
+
+    /// Post a poll. The question becomes the message content.
+    pub async fn create_poll(
+        &self,
+        channel_id: &Uuid,
+        sender_id: &Uuid,
+        request: CreatePollRequest,
+    ) -> Result<MessageResponse, AppError> {
+        if !ChannelRepository::is_member(&self.pool, channel_id, sender_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You don't have access to this channel".to_string(),
+            ));
+        }
+
+        let channel = self.check_post_permission(channel_id, sender_id).await?;
+        let mut tx = self.pool.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        self.check_rate_limit(&mut tx, &channel, sender_id).await?;
+
+        let question = request.question.trim();
+        if question.is_empty() {
+            return Err(AppError::ValidationError("Question must be 1-500 characters".to_string()));
+        }
+
+        let options: Vec<String> = request.options.iter().map(|o| o.trim().to_string()).collect();
+        if options.iter().any(|o| o.is_empty() || o.chars().count() > MAX_POLL_OPTION_LEN) {
+            return Err(AppError::ValidationError("Options must be 1-200 characters".to_string()));
+        }
+
+        let (question, mut flags) = self.apply_content_rules(&channel, question).await?;
+        let mut filtered = Vec::with_capacity(options.len());
+        for option in &options {
+            let (option, option_flags) = self.apply_content_rules(&channel, option).await?;
+            filtered.push(option);
+            flags.extend(option_flags);
+        }
+        let options = filtered;
+        flags.sort();
+        flags.dedup();
+
+        let mut seen = HashSet::new();
+        if !options.iter().all(|o| seen.insert(o.to_lowercase())) {
+            return Err(AppError::ValidationError("Options must be distinct".to_string()));
+        }
+
+        if request.closes_at.is_some_and(|closes_at| closes_at <= Utc::now()) {
+            return Err(AppError::ValidationError("Close time must be in the future".to_string()));
+        }
+
+        let message = MessageRepository::create(
+            &mut tx,
+            channel_id,
+            sender_id,
+            &question,
+            MessageType::Poll,
+            MessageFormat::Plain,
+            None,
+            None,
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        PollRepository::create(
+            &mut tx,
+            &message.id,
+            &question,
+            &options,
+            request.allow_multiple,
+            request.anonymous,
+            request.closes_at,
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        tx.commit().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.store_mentions(&message.id, &message.content, message.format).await?;
+        self.flag_message(&message.id, &flags).await?;
+
+        self.get_message_response(&message.id, sender_id).await
+    }
+
+    /// Vote for an option. In single choice polls this replaces the user's previous vote.
+    pub async fn vote_poll(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+        option_id: &Uuid,
+    ) -> Result<PollResponse, AppError> {
+        let poll = self.find_open_poll(channel_id, message_id, user_id).await?;
+
+        let options = PollRepository::find_options(&self.pool, message_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if !options.iter().any(|o| o.id == *option_id) {
+            return Err(AppError::NotFoundError("Poll option not found".to_string()));
+        }
+
+        PollRepository::vote(&self.pool, message_id, option_id, user_id, !poll.allow_multiple)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.poll_response(&poll, user_id).await
+    }
+
+    pub async fn retract_poll_vote(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+        option_id: &Uuid,
+    ) -> Result<PollResponse, AppError> {
+        let poll = self.find_open_poll(channel_id, message_id, user_id).await?;
+
+        let retracted = PollRepository::retract_vote(&self.pool, message_id, option_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if !retracted {
+            return Err(AppError::NotFoundError("Vote not found".to_string()));
+        }
+
+        self.poll_response(&poll, user_id).await
+    }
+
+    pub async fn search_messages(
+        &self,
+        user_id: &Uuid,
+        query: &str,
+        channel_id: Option<&Uuid>,
+        from_user_id: Option<&Uuid>,
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+        limit: i64,
+        before: Option<Cursor>,
+    ) -> Result<PaginatedResponse<MessageResponse>, AppError> {
+        // If channel_id is specified, verify access
+        if let Some(cid) = channel_id {
+            if !ChannelRepository::is_member(&self.pool, cid, user_id)
+                .await
+                .unwrap_or(false)
+            {
+                return Err(AppError::AuthorizationError(
+                    "You don't have access to this channel".to_string(),
+                ));
+            }
+        }
+
+        let messages = MessageRepository::search(
+            &self.pool,
+            user_id,
+            query,
+            channel_id,
+            from_user_id,
+            from_date,
+            to_date,
+            limit + 1,
+            before,
+        )
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let page = paginate(messages, limit, |m| Cursor::new(m.created_at, m.id));
+        let items = self.hydrator.hydrate(page.items, user_id).await?;
+
+        Ok(PaginatedResponse {
+            items,
+            has_more: page.has_more,
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Parse mentions from the content and store those that resolve to a user or keyword.
+    /// Markdown messages ignore mentions inside code.
+    async fn store_mentions(
+        &self,
+        message_id: &Uuid,
+        content: &str,
+        format: MessageFormat,
+    ) -> Result<(), AppError> {
+        let parsed = match format {
+            MessageFormat::Plain => parse_mentions(content),
+            MessageFormat::Markdown => markdown::collect_mentions(&markdown::parse(content)),
+        };
+
+        let mut mentions = Vec::new();
+        for mention in parsed {
+            let user_id = match &mention.username {
+                Some(username) => match UserRepository::find_by_username(&self.pool, username).await {
+                    Ok(user) => Some(user.id),
+                    Err(_) => continue,
+                },
+                None => None,
+            };
+
+            mentions.push((
+                mention.mention_type,
+                user_id,
+                mention.start as i32,
+                mention.end as i32,
+            ));
+        }
+
+        MessageRepository::replace_mentions(&self.pool, message_id, &mentions)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    /// The earliest point in the channel's history the member may see, if restricted
+    async fn visible_since(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        if channel.history_visibility == HistoryVisibility::Full {
+            return Ok(None);
+        }
+
+        let member = ChannelRepository::find_member(&self.pool, channel_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(Some(member.joined_at))
+    }
+
+    /// Members may read messages in their channels, within the history they can see
+    async fn check_message_access(&self, message: &Message, user_id: &Uuid) -> Result<(), AppError> {
+        if !ChannelRepository::is_member(&self.pool, &message.channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You don't have access to this message".to_string(),
+            ));
+        }
+
+        if let Some(visible_since) = self.visible_since(&message.channel_id, user_id).await? {
+            if message.created_at < visible_since {
+                return Err(AppError::AuthorizationError(
+                    "You don't have access to this message".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A message in the channel that the user may react to
+    async fn find_reactable_message(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<Message, AppError> {
+        let message = MessageRepository::find_by_id(&self.pool, message_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+        if message.channel_id != *channel_id {
+            return Err(AppError::NotFoundError("Message not found".to_string()));
+        }
+
+        // Verify user has access to channel
+        if !ChannelRepository::is_member(&self.pool, &message.channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You don't have access to this message".to_string(),
+            ));
+        }
+
+        if message.deleted_at.is_some() {
+            return Err(AppError::NotFoundError("Message not found".to_string()));
+        }
+
+        self.check_channel_writable(&message.channel_id).await?;
+
+        Ok(message)
+    }
+
+    /// Reactions are a single Unicode emoji, or a custom emoji of the channel's team
+    async fn check_reaction_emoji(&self, channel_id: &Uuid, emoji: &str) -> Result<(), AppError> {
+        if emoji::is_emoji(emoji) {
+            return Ok(());
+        }
+
+        match emoji::custom_emoji_name(emoji) {
+            Some(name) => {
+                let exists = CustomEmojiRepository::exists_for_channel(&self.pool, channel_id, name)
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                if exists {
+                    Ok(())
+                } else {
+                    Err(AppError::ValidationError(format!("Unknown custom emoji :{}:", name)))
+                }
+            }
+            None => Err(AppError::ValidationError("Reactions must be a single emoji".to_string())),
+        }
+    }
+
+    /// A poll in the channel that the user can still vote in
+    async fn find_open_poll(
+        &self,
+        channel_id: &Uuid,
+        message_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<Poll, AppError> {
+        let message = MessageRepository::find_by_id(&self.pool, message_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+        if message.channel_id != *channel_id || message.deleted_at.is_some() {
+            return Err(AppError::NotFoundError("Message not found".to_string()));
+        }
+
+        self.check_message_access(&message, user_id).await?;
+        self.check_channel_writable(&message.channel_id).await?;
+
+        let poll = PollRepository::find_by_message(&self.pool, message_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .ok_or_else(|| AppError::NotFoundError("Poll not found".to_string()))?;
+
+        if poll.is_closed() {
+            return Err(AppError::ValidationError("This poll is closed".to_string()));
+        }
+
+        Ok(poll)
+    }
+
+    async fn poll_response(&self, poll: &Poll, user_id: &Uuid) -> Result<PollResponse, AppError> {
+        let options = PollRepository::find_options(&self.pool, &poll.message_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let votes = PollRepository::find_votes(&self.pool, &poll.message_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(hydration::poll_response(poll, &options, &votes, user_id))
+    }
+
+    /// Senders may change their own messages. Team owners, admins and moderators may also
+    /// change anyone's message in the team's channels, giving a reason for the moderation log.
+    /// Returns the reason when the change is a moderation action.
+    async fn moderation_reason<'a>(
+        &self,
+        message: &Message,
+        user_id: &Uuid,
+        reason: Option<&'a str>,
+        denied: &str,
+    ) -> Result<Option<&'a str>, AppError> {
+        if message.sender_id == *user_id {
+            return Ok(None);
+        }
+
+        if !self.can_moderate(&message.channel_id, user_id).await? {
+            return Err(AppError::AuthorizationError(denied.to_string()));
+        }
+
+        match reason.map(str::trim).filter(|r| !r.is_empty()) {
+            Some(reason) if reason.chars().count() > MAX_MODERATION_REASON_LEN => Err(
+                AppError::ValidationError("Reason must be 1-500 characters".to_string()),
+            ),
+            Some(reason) => Ok(Some(reason)),
+            None => Err(AppError::ValidationError(
+                "A reason is required to moderate another user's message".to_string(),
+            )),
+        }
+    }
+
+    /// Run the team's content rules over a message. Returns the content to store,
+    /// with any redactions, and the flagging rules that matched. Direct messages
+    /// belong to no team and are not filtered.
+    async fn apply_content_rules(
+        &self,
+        channel: &Channel,
+        content: &str,
+    ) -> Result<(String, Vec<(Uuid, String)>), AppError> {
+        let Some(team_id) = &channel.team_id else {
+            return Ok((content.to_string(), Vec::new()));
+        };
+
+        let rules = ContentRuleRepository::find_by_team(&self.pool, team_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let filter = ContentFilter::new(&rules);
+        if filter.is_empty() {
+            return Ok((content.to_string(), Vec::new()));
+        }
+
+        let outcome = filter.apply(content);
+        if let Some(rule) = outcome.blocked_by {
+            return Err(AppError::ValidationError(format!(
+                "Message blocked by content rule \"{}\"",
+                rule
+            )));
+        }
+
+        Ok((outcome.content, outcome.flagged_by))
+    }
+
+    async fn flag_message(&self, message_id: &Uuid, flags: &[(Uuid, String)]) -> Result<(), AppError> {
+        for (rule_id, rule_name) in flags {
+            ModerationRepository::flag(&self.pool, message_id, rule_id, rule_name)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the user moderates the team the channel belongs to
+    async fn can_moderate(&self, channel_id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        let role = match &channel.team_id {
+            Some(team_id) => TeamRepository::get_user_role(&self.pool, team_id, user_id)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?,
+            None => None,
+        };
+
+        Ok(role.is_some_and(|r| r.can_moderate()))
+    }
+
+    async fn check_team_moderator(&self, team_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
+        let role = TeamRepository::get_user_role(&self.pool, team_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        if !role.is_some_and(|r| r.can_moderate()) {
+            return Err(AppError::AuthorizationError(
+                "You don't have permission to perform this action".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Archived channels are read-only
+    async fn check_channel_writable(&self, channel_id: &Uuid) -> Result<Channel, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        if channel.is_archived {
+            return Err(AppError::AuthorizationError(
+                "This channel is archived and read-only".to_string(),
+            ));
+        }
+
+        Ok(channel)
+    }
+
+    /// Announcement channels only accept messages from team owners and admins
+    /// and from members designated as posters
+    async fn check_post_permission(&self, channel_id: &Uuid, user_id: &Uuid) -> Result<Channel, AppError> {
+        let channel = self.check_channel_writable(channel_id).await?;
+
+        if !channel.is_announcement {
+            return Ok(channel);
+        }
+
+        if let Some(team_id) = &channel.team_id {
+            let role = TeamRepository::get_user_role(&self.pool, team_id, user_id)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+            if matches!(role, Some(TeamRole::Owner) | Some(TeamRole::Admin)) {
+                return Ok(channel);
+            }
+        }
+
+        let member = ChannelRepository::find_member(&self.pool, channel_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        if member.can_post {
+            return Ok(channel);
+        }
+
+        Err(AppError::AuthorizationError(
+            "Only admins and designated posters can send messages in this channel".to_string(),
+        ))
+    }
+
+    /// Check the channel's slow mode and burst limit for the sender, unless they are a team
+    /// moderator. The sender stays locked until `tx` ends, so the message must be created in it.
+    async fn check_rate_limit(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        channel: &Channel,
+        sender_id: &Uuid,
+    ) -> Result<(), AppError> {
+        if channel.slow_mode_seconds <= 0 && channel.burst_limit <= 0 {
+            return Ok(());
+        }
+
+        if let Some(team_id) = &channel.team_id {
+            let role = TeamRepository::get_user_role(&self.pool, team_id, sender_id)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+            if role.is_some_and(|r| r.can_moderate()) {
+                return Ok(());
+            }
+        }
+
+        MessageRepository::lock_sender(tx, &channel.id, sender_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let now = Utc::now();
+
+        if channel.slow_mode_seconds > 0 {
+            let last_sent_at = MessageRepository::get_last_sent_at(tx, &channel.id, sender_id)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+            if let Some(last_sent_at) = last_sent_at {
+                let next_allowed = last_sent_at + Duration::seconds(channel.slow_mode_seconds as i64);
+                if next_allowed > now {
+                    return Err(AppError::RateLimitExceeded {
+                        retry_after: seconds_until(now, next_allowed),
+                    });
+                }
+            }
+        }
+
+        if channel.burst_limit > 0 {
+            let window_start = now - Duration::minutes(1);
+            let (sent, oldest) =
+                MessageRepository::count_sent_since(tx, &channel.id, sender_id, &window_start)
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+            if sent >= channel.burst_limit as i64 {
+                let next_allowed = oldest.unwrap_or(now) + Duration::minutes(1);
+                return Err(AppError::RateLimitExceeded {
+                    retry_after: seconds_until(now, next_allowed),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_message_response(
+        &self,
+        message_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<MessageResponse, AppError> {
+        let message = MessageRepository::find_by_id(&self.pool, message_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Message not found".to_string()))?;
+
+        self.hydrator
+            .hydrate(vec![message], user_id)
+            .await?
+            .pop()
+            .ok_or_else(|| AppError::NotFoundError("Sender not found".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use shared::models::{ContentRuleAction, ContentRuleKind, MentionType, Team, User};
+
+    fn text(content: &str) -> SendMessageRequest {
+        SendMessageRequest {
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_announcement_channel_posting() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let poster = testing::team_member(&pool, &team, "poster", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&member, &poster]).await;
+        ChannelRepository::set_announcement(&pool, &channel.id, true).await.unwrap();
+        ChannelRepository::set_can_post(&pool, &channel.id, &poster.id, true).await.unwrap();
+        let service = MessageService::new(Arc::new(pool));
+
+        let denied = service.send_message(&channel.id, &member.id, text("hi")).await;
+        assert!(matches!(denied, Err(AppError::AuthorizationError(_))));
+        assert!(service.send_message(&channel.id, &poster.id, text("hi")).await.is_ok());
+        assert!(service.send_message(&channel.id, &owner.id, text("hi")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_seen_by_respects_privacy_setting() {
+        let Some(pool) = testing::pool().await else { return };
+        let alice = testing::user(&pool, "alice").await;
+        let bob = testing::user(&pool, "bob").await;
+        let dm = testing::channel(&pool, None, ChannelType::DirectMessage, &alice, &[&bob]).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        let message = service.send_message(&dm.id, &alice.id, text("hi")).await.unwrap();
+        let seen_by = service.get_seen_by(&dm.id, &message.id, &alice.id).await.unwrap();
+        assert!(seen_by.is_empty());
+
+        ChannelRepository::mark_as_read(&pool, &dm.id, &bob.id).await.unwrap();
+        let seen_by = service.get_seen_by(&dm.id, &message.id, &alice.id).await.unwrap();
+        assert_eq!(seen_by.iter().map(|u| u.id).collect::<Vec<_>>(), vec![bob.id]);
+
+        // Opting out hides the user's receipts from others
+        UserRepository::set_read_receipts_enabled(&pool, &bob.id, false).await.unwrap();
+        assert!(service.get_seen_by(&dm.id, &message.id, &alice.id).await.unwrap().is_empty());
+
+        // Receipts are reciprocal
+        UserRepository::set_read_receipts_enabled(&pool, &bob.id, true).await.unwrap();
+        UserRepository::set_read_receipts_enabled(&pool, &alice.id, false).await.unwrap();
+        assert!(service.get_seen_by(&dm.id, &message.id, &alice.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_read_receipt_recipients() {
+        let Some(pool) = testing::pool().await else { return };
+        let alice = testing::user(&pool, "alice").await;
+        let bob = testing::user(&pool, "bob").await;
+        let dm = testing::channel(&pool, None, ChannelType::DirectMessage, &alice, &[&bob]).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        assert_eq!(service.read_receipt_recipients(&dm.id, &alice.id).await.unwrap(), vec![bob.id]);
+
+        // Members who opted out don't receive others' receipts
+        UserRepository::set_read_receipts_enabled(&pool, &bob.id, false).await.unwrap();
+        assert!(service.read_receipt_recipients(&dm.id, &alice.id).await.unwrap().is_empty());
+
+        // Nor do they share their own
+        UserRepository::set_read_receipts_enabled(&pool, &bob.id, true).await.unwrap();
+        UserRepository::set_read_receipts_enabled(&pool, &alice.id, false).await.unwrap();
+        assert!(service.read_receipt_recipients(&dm.id, &alice.id).await.unwrap().is_empty());
+
+        // Large channels don't share receipts at all
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let mut members = Vec::new();
+        for i in 0..SEEN_BY_MAX_MEMBERS {
+            members.push(testing::team_member(&pool, &team, &format!("member{}", i), TeamRole::Member).await);
+        }
+        let member_refs = members.iter().collect::<Vec<_>>();
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &member_refs).await;
+        assert!(service.read_receipt_recipients(&channel.id, &owner.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_slow_mode_admits_one_concurrent_send() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&member]).await;
+        ChannelRepository::set_rate_limits(&pool, &channel.id, 30, 0).await.unwrap();
+        let service = MessageService::new(Arc::new(pool));
+
+        let (first, second, third) = tokio::join!(
+            service.send_message(&channel.id, &member.id, text("one")),
+            service.send_message(&channel.id, &member.id, text("two")),
+            service.send_message(&channel.id, &member.id, text("three")),
+        );
+        let results = [first, second, third];
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        for result in results.iter().filter(|r| r.is_err()) {
+            assert!(matches!(result, Err(AppError::RateLimitExceeded { retry_after }) if *retry_after <= 30));
+        }
+
+        // Moderators aren't slowed down
+        assert!(service.send_message(&channel.id, &owner.id, text("one")).await.is_ok());
+        assert!(service.send_message(&channel.id, &owner.id, text("two")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_burst_limit_counts_concurrent_sends() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&member]).await;
+        ChannelRepository::set_rate_limits(&pool, &channel.id, 0, 2).await.unwrap();
+        let service = MessageService::new(Arc::new(pool));
+
+        let (first, second, third) = tokio::join!(
+            service.send_message(&channel.id, &member.id, text("one")),
+            service.send_message(&channel.id, &member.id, text("two")),
+            service.send_message(&channel.id, &member.id, text("three")),
+        );
+        let results = [first, second, third];
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 2);
+
+        let result = service.send_message(&channel.id, &member.id, text("four")).await;
+        assert!(matches!(result, Err(AppError::RateLimitExceeded { retry_after }) if retry_after <= 60));
+    }
+
+    #[tokio::test]
+    async fn test_joined_history_hides_earlier_messages() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        ChannelRepository::set_history_visibility(&pool, &channel.id, HistoryVisibility::Joined).await.unwrap();
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        service.send_message(&channel.id, &owner.id, text("hello before")).await.unwrap();
+        ChannelRepository::add_member(&pool, &channel.id, &member.id).await.unwrap();
+        service.send_message(&channel.id, &owner.id, text("hello after")).await.unwrap();
+
+        let contents = |page: PaginatedResponse<MessageResponse>| -> Vec<String> {
+            page.items.into_iter().map(|m| m.content).collect()
+        };
+        let listed = service.list_messages(&channel.id, &member.id, 50, None).await.unwrap();
+        assert_eq!(contents(listed), vec!["hello after"]);
+        let found = service
+            .search_messages(&member.id, "hello", None, None, None, None, 50, None)
+            .await
+            .unwrap();
+        assert_eq!(contents(found), vec!["hello after"]);
+
+        let listed = service.list_messages(&channel.id, &owner.id, 50, None).await.unwrap();
+        assert_eq!(contents(listed), vec!["hello before", "hello after"]);
+
+        ChannelRepository::set_history_visibility(&pool, &channel.id, HistoryVisibility::Full).await.unwrap();
+        let listed = service.list_messages(&channel.id, &member.id, 50, None).await.unwrap();
+        assert_eq!(listed.items.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_joined_history_hides_earlier_reply_parents() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        ChannelRepository::set_history_visibility(&pool, &channel.id, HistoryVisibility::Joined).await.unwrap();
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        let parent = service.send_message(&channel.id, &owner.id, text("before")).await.unwrap();
+        ChannelRepository::add_member(&pool, &channel.id, &member.id).await.unwrap();
+        let request = SendMessageRequest {
+            reply_to_id: Some(parent.id),
+            ..text("reply")
+        };
+        let reply = service.send_message(&channel.id, &owner.id, request).await.unwrap();
+        assert_eq!(reply.reply_to.as_ref().unwrap().content, "before");
+        assert!(reply.for_broadcast().reply_to.unwrap().content.is_empty());
+
+        let listed = service.list_messages(&channel.id, &member.id, 50, None).await.unwrap();
+        let reply_to = listed.items[0].reply_to.as_ref().unwrap();
+        assert_eq!(reply_to.id, parent.id);
+        assert!(reply_to.content.is_empty());
+
+        let listed = service.list_messages(&channel.id, &owner.id, 50, None).await.unwrap();
+        assert_eq!(listed.items[1].reply_to.as_ref().unwrap().content, "before");
+    }
+
+    #[tokio::test]
+    async fn test_mentions_are_stored_for_known_users() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let bob = testing::user(&pool, "bob").await;
+        testing::user(&pool, "carol").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &owner, &[&bob]).await;
+        let service = MessageService::new(Arc::new(pool));
+
+        let content = "hi @bob and @nobody, `@carol` @here";
+        let request = SendMessageRequest {
+            format: Some(MessageFormat::Markdown),
+            ..text(content)
+        };
+        let message = service.send_message(&channel.id, &owner.id, request).await.unwrap();
+
+        let mentions: Vec<_> = message
+            .mentions
+            .iter()
+            .map(|m| (m.mention_type, m.user_id, &content[m.start..m.end]))
+            .collect();
+        assert_eq!(
+            mentions,
+            vec![(MentionType::User, Some(bob.id), "@bob"), (MentionType::Here, None, "@here")]
+        );
+
+        let edited = service
+            .update_message(
+                &channel.id,
+                &message.id,
+                &owner.id,
+                UpdateMessageRequest { content: "thanks".to_string(), reason: None },
+            )
+            .await
+            .unwrap();
+        assert!(edited.mentions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_edit_history_and_tombstones() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let other = testing::user(&pool, "other").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &owner, &[&other]).await;
+        let service = MessageService::new(Arc::new(pool));
+        let edit = |content: &str| UpdateMessageRequest { content: content.to_string(), reason: None };
+
+        let message = service.send_message(&channel.id, &owner.id, text("first")).await.unwrap();
+        service.update_message(&channel.id, &message.id, &owner.id, edit("second")).await.unwrap();
+        let edited = service.update_message(&channel.id, &message.id, &owner.id, edit("third")).await.unwrap();
+        assert!(edited.edited);
+        assert_eq!(edited.content, "third");
+
+        let result = service.update_message(&channel.id, &message.id, &other.id, edit("mine")).await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+
+        let history = service.get_message_history(&channel.id, &message.id, &other.id).await.unwrap();
+        let contents: Vec<&str> = history.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, vec!["first", "second"]);
+
+        assert!(!service.delete_message(&channel.id, &message.id, &owner.id, None).await.unwrap());
+        let listed = service.list_messages(&channel.id, &other.id, 50, None).await.unwrap();
+        assert_eq!(listed.items.len(), 1);
+        assert!(listed.items[0].deleted_at.is_some());
+        assert_eq!(listed.items[0].content, "");
+        assert!(service.get_message_history(&channel.id, &message.id, &other.id).await.unwrap().is_empty());
+
+        let result = service.update_message(&channel.id, &message.id, &owner.id, edit("back")).await;
+        assert!(matches!(result, Err(AppError::NotFoundError(_))));
+        let result = service.delete_message(&channel.id, &message.id, &owner.id, None).await;
+        assert!(matches!(result, Err(AppError::NotFoundError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_moderated_revisions_are_hidden_from_members() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let moderator = testing::team_member(&pool, &team, "moderator", TeamRole::Moderator).await;
+        let author = testing::team_member(&pool, &team, "author", TeamRole::Member).await;
+        let reader = testing::team_member(&pool, &team, "reader", TeamRole::Member).await;
+        let channel =
+            testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&moderator, &author, &reader]).await;
+        let service = MessageService::new(Arc::new(pool));
+
+        let message = service.send_message(&channel.id, &author.id, text("draft")).await.unwrap();
+        let edit = |content: &str, reason: Option<&str>| UpdateMessageRequest {
+            content: content.to_string(),
+            reason: reason.map(str::to_string),
+        };
+        service.update_message(&channel.id, &message.id, &author.id, edit("leaked secret", None)).await.unwrap();
+        service
+            .update_message(&channel.id, &message.id, &moderator.id, edit("[removed]", Some("Leaked a secret")))
+            .await
+            .unwrap();
+        service.update_message(&channel.id, &message.id, &author.id, edit("sorry", None)).await.unwrap();
+
+        let contents = |history: Vec<MessageRevisionResponse>| -> Vec<String> {
+            history.into_iter().map(|r| r.content).collect()
+        };
+        let history = service.get_message_history(&channel.id, &message.id, &reader.id).await.unwrap();
+        assert_eq!(contents(history), vec!["draft", "[removed]"]);
+        let history = service.get_message_history(&channel.id, &message.id, &author.id).await.unwrap();
+        assert_eq!(contents(history), vec!["draft", "[removed]"]);
+        let history = service.get_message_history(&channel.id, &message.id, &moderator.id).await.unwrap();
+        assert_eq!(contents(history), vec!["draft", "leaked secret", "[removed]"]);
+
+        let moderated = service
+            .delete_message(&channel.id, &message.id, &moderator.id, Some("Leaked a secret"))
+            .await
+            .unwrap();
+        assert!(moderated);
+        let listed = service.list_messages(&channel.id, &reader.id, 50, None).await.unwrap();
+        assert!(listed.items[0].moderated);
+        assert!(listed.items[0].deleted_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_message_changes_stay_in_the_message_channel() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let moderator = testing::team_member(&pool, &team, "moderator", TeamRole::Moderator).await;
+        let author = testing::team_member(&pool, &team, "author", TeamRole::Member).await;
+        let members = [&moderator, &author];
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &members).await;
+        let other = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &members).await;
+        let service = MessageService::new(Arc::new(pool));
+
+        let message = service.send_message(&channel.id, &author.id, text("hello")).await.unwrap();
+        for user_id in [author.id, moderator.id] {
+            let edit = UpdateMessageRequest {
+                content: "[removed]".to_string(),
+                reason: Some("Off topic".to_string()),
+            };
+            let result = service.update_message(&other.id, &message.id, &user_id, edit).await;
+            assert!(matches!(result, Err(AppError::NotFoundError(_))));
+            let result = service.delete_message(&other.id, &message.id, &user_id, Some("Off topic")).await;
+            assert!(matches!(result, Err(AppError::NotFoundError(_))));
+            let result = service.get_message_history(&other.id, &message.id, &user_id).await;
+            assert!(matches!(result, Err(AppError::NotFoundError(_))));
+        }
+
+        let listed = service.list_messages(&channel.id, &author.id, 50, None).await.unwrap();
+        assert_eq!(listed.items[0].content, "hello");
+        assert!(listed.items[0].deleted_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_scheduled_messages_are_kept_for_retry() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let member = testing::user(&pool, "member").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &owner, &[&member]).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        let scheduled = service
+            .schedule_message(&channel.id, &member.id, text("later"), Utc::now())
+            .await
+            .unwrap();
+        ChannelRepository::remove_member(&pool, &channel.id, &member.id).await.unwrap();
+
+        let due = service.claim_due_scheduled_messages(10).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert!(service.claim_due_scheduled_messages(10).await.unwrap().is_empty());
+        let result = service.dispatch_scheduled_message(due[0].clone()).await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+
+        let listed = service.list_scheduled_messages(&member.id).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].status, ScheduledMessageStatus::Failed);
+        assert!(listed[0].error.is_some());
+        assert!(service.claim_due_scheduled_messages(10).await.unwrap().is_empty());
+
+        ChannelRepository::add_member(&pool, &channel.id, &member.id).await.unwrap();
+        let retried = service.retry_scheduled_message(&scheduled.id, &member.id).await.unwrap();
+        assert_eq!(retried.status, ScheduledMessageStatus::Pending);
+        let result = service.retry_scheduled_message(&scheduled.id, &member.id).await;
+        assert!(matches!(result, Err(AppError::ConflictError(_))));
+
+        let due = service.claim_due_scheduled_messages(10).await.unwrap();
+        let sent = service.dispatch_scheduled_message(due[0].clone()).await.unwrap();
+        assert_eq!(sent.unwrap().content, "later");
+        assert!(service.list_scheduled_messages(&member.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reclaimed_scheduled_message_is_not_posted_twice() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &owner, &[]).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        service
+            .schedule_message(&channel.id, &owner.id, text("once"), Utc::now())
+            .await
+            .unwrap();
+        let due = service.claim_due_scheduled_messages(10).await.unwrap();
+
+        // A dispatcher posts the message and stops before removing it
+        let request = SendMessageRequest {
+            client_nonce: Some(due[0].id.to_string()),
+            ..text("once")
+        };
+        service.send_message(&channel.id, &owner.id, request).await.unwrap();
+        sqlx::query("UPDATE scheduled_messages SET claimed_at = claimed_at - INTERVAL '1 hour'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let due = service.claim_due_scheduled_messages(10).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert!(service.dispatch_scheduled_message(due[0].clone()).await.unwrap().is_none());
+
+        let listed = service.list_messages(&channel.id, &owner.id, 50, None).await.unwrap();
+        assert_eq!(listed.items.len(), 1);
+        assert!(service.list_scheduled_messages(&owner.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_removing_reactions_requires_membership() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let member = testing::user(&pool, "member").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &owner, &[&member]).await;
+        let other = testing::channel(&pool, None, ChannelType::Private, &member, &[]).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        let message = service.send_message(&channel.id, &owner.id, text("hi")).await.unwrap();
+        service.add_reaction(&channel.id, &message.id, &member.id, "👍").await.unwrap();
+
+        let result = service.remove_reaction(&other.id, &message.id, &member.id, "👍").await;
+        assert!(matches!(result, Err(AppError::NotFoundError(_))));
+
+        ChannelRepository::remove_member(&pool, &channel.id, &member.id).await.unwrap();
+        let result = service.remove_reaction(&channel.id, &message.id, &member.id, "👍").await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+        let reacted = MessageRepository::has_user_reacted(&pool, &message.id, &member.id, "👍").await.unwrap();
+        assert!(reacted);
+
+        ChannelRepository::add_member(&pool, &channel.id, &member.id).await.unwrap();
+        assert!(service.remove_reaction(&channel.id, &message.id, &member.id, "👍").await.unwrap());
+        assert!(!service.remove_reaction(&channel.id, &message.id, &member.id, "👍").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_sends_with_one_nonce_post_once() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+        let request = || SendMessageRequest {
+            client_nonce: Some("nonce-1".to_string()),
+            ..text("hello")
+        };
+
+        let (first, second) = tokio::join!(
+            service.send_message_once(&channel.id, &owner.id, request()),
+            service.send_message_once(&channel.id, &owner.id, request()),
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first.0.id, second.0.id);
+        assert!(first.1 != second.1);
+
+        let listed = service.list_messages(&channel.id, &owner.id, 50, None).await.unwrap();
+        assert_eq!(listed.items.len(), 1);
+
+        // The nonce belongs to this channel
+        let other = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        let result = service.send_message_once(&other.id, &owner.id, request()).await;
+        assert!(matches!(result, Err(AppError::ConflictError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_retries_in_slow_mode_get_the_message() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&member]).await;
+        ChannelRepository::set_rate_limits(&pool, &channel.id, 30, 0).await.unwrap();
+        let service = MessageService::new(Arc::new(pool.clone()));
+        let request = |nonce: &str| SendMessageRequest {
+            client_nonce: Some(nonce.to_string()),
+            ..text("hello")
+        };
+
+        let (first, second, third) = tokio::join!(
+            service.send_message_once(&channel.id, &member.id, request("nonce-1")),
+            service.send_message_once(&channel.id, &member.id, request("nonce-1")),
+            service.send_message_once(&channel.id, &member.id, request("nonce-1")),
+        );
+        let results = [first.unwrap(), second.unwrap(), third.unwrap()];
+        assert!(results.iter().all(|(message, _)| message.id == results[0].0.id));
+        assert_eq!(results.iter().filter(|(_, posted)| *posted).count(), 1);
+
+        // A new message is still slowed down
+        let result = service.send_message_once(&channel.id, &member.id, request("nonce-2")).await;
+        assert!(matches!(result, Err(AppError::RateLimitExceeded { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_retried_schedule_request_queues_once() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+        let request = || SendMessageRequest {
+            client_nonce: Some("nonce-1".to_string()),
+            ..text("later")
+        };
+
+        let send_at = Utc::now() + Duration::hours(1);
+        let first = service.schedule_message(&channel.id, &owner.id, request(), send_at).await.unwrap();
+        let second = service.schedule_message(&channel.id, &owner.id, request(), send_at).await.unwrap();
+        assert_eq!(first.id, second.id);
+        assert_eq!(service.list_scheduled_messages(&owner.id).await.unwrap().len(), 1);
+
+        // Once posted, a retry of the request gets the posted message
+        let scheduled = ScheduledMessageRepository::find_by_id(&pool, &first.id).await.unwrap();
+        let posted = service.dispatch_scheduled_message(scheduled).await.unwrap().unwrap();
+        let (message, sent) = service.send_message_once(&channel.id, &owner.id, request()).await.unwrap();
+        assert_eq!(message.id, posted.id);
+        assert!(!sent);
+    }
+
+    #[tokio::test]
+    async fn test_expired_messages_are_purged() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        let request = SendMessageRequest { ttl_seconds: Some(60), ..text("secret") };
+        let secret = service.send_message(&channel.id, &owner.id, request).await.unwrap();
+        let kept = service.send_message(&channel.id, &owner.id, text("kept")).await.unwrap();
+        service.add_reaction(&channel.id, &secret.id, &owner.id, "👍").await.unwrap();
+
+        // Not due yet
+        assert!(service.purge_expired_messages(100).await.unwrap().is_empty());
+
+        sqlx::query("UPDATE messages SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1")
+            .bind(secret.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let purged = service.purge_expired_messages(100).await.unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].0.id, secret.id);
+        assert_eq!(purged[0].0.content, "secret");
+        assert!(service.purge_expired_messages(100).await.unwrap().is_empty());
+
+        let tombstone = MessageRepository::find_by_id(&pool, &secret.id).await.unwrap();
+        assert_eq!(tombstone.content, "");
+        assert!(tombstone.deleted_at.is_some());
+        assert!(tombstone.expires_at.is_none());
+        assert!(!MessageRepository::has_user_reacted(&pool, &secret.id, &owner.id, "👍").await.unwrap());
+
+        let kept = MessageRepository::find_by_id(&pool, &kept.id).await.unwrap();
+        assert_eq!(kept.content, "kept");
+        assert!(kept.deleted_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_self_destructing_messages_are_not_forwarded_or_scheduled() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        let other = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        let service = MessageService::new(Arc::new(pool));
+
+        let request = SendMessageRequest { ttl_seconds: Some(60), ..text("secret") };
+        let secret = service.send_message(&channel.id, &owner.id, request).await.unwrap();
+        let forward = ForwardMessageRequest { target_channel_id: other.id, comment: None, format: None };
+        let result = service.forward_message(&channel.id, &secret.id, &owner.id, forward).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+        let request = SendMessageRequest { ttl_seconds: Some(60), ..text("later") };
+        let send_at = Utc::now() + Duration::hours(1);
+        let result = service.schedule_message(&channel.id, &owner.id, request, send_at).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+        assert!(service.list_scheduled_messages(&owner.id).await.unwrap().is_empty());
+    }
+
+    /// Block "forbidden", redact "secret" and flag "refund" in the team
+    async fn word_rules(pool: &PgPool, team: &Team, owner: &User) {
+        for (word, action) in [
+            ("forbidden", ContentRuleAction::Block),
+            ("secret", ContentRuleAction::Redact),
+            ("refund", ContentRuleAction::Flag),
+        ] {
+            let words = [word.to_string()];
+            ContentRuleRepository::create(pool, &team.id, word, ContentRuleKind::Words, None, &words, action, true, &owner.id)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forwards_pass_the_target_team_rules() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let source_team = testing::team(&pool, &owner).await;
+        let target_team = testing::team(&pool, &owner).await;
+        let source = testing::channel(&pool, Some(&source_team), ChannelType::Public, &owner, &[]).await;
+        let target = testing::channel(&pool, Some(&target_team), ChannelType::Public, &owner, &[]).await;
+        word_rules(&pool, &target_team, &owner).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        let forward = |comment: &str| ForwardMessageRequest {
+            target_channel_id: target.id,
+            comment: Some(comment.to_string()),
+            format: None,
+        };
+        let message = service.send_message(&source.id, &owner.id, text("the secret plan")).await.unwrap();
+        let forwarded = service
+            .forward_message(&source.id, &message.id, &owner.id, forward("refund a secret"))
+            .await
+            .unwrap();
+        assert_eq!(forwarded.content, "refund a [redacted]");
+        assert_eq!(forwarded.forwarded.unwrap().content.as_deref(), Some("the [redacted] plan"));
+        let flagged = ModerationRepository::find_flagged(&pool, &target_team.id, 50, None).await.unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].message_id, forwarded.id);
+
+        let result = service.forward_message(&source.id, &message.id, &owner.id, forward("forbidden")).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+        let message = service.send_message(&source.id, &owner.id, text("forbidden fruit")).await.unwrap();
+        let result = service.forward_message(&source.id, &message.id, &owner.id, forward("look")).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_polls_pass_content_rules() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        word_rules(&pool, &team, &owner).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        let poll = |question: &str, options: &[&str]| CreatePollRequest {
+            question: question.to_string(),
+            options: options.iter().map(|o| o.to_string()).collect(),
+            ..Default::default()
+        };
+        let message = service
+            .create_poll(&channel.id, &owner.id, poll("Share the secret?", &["yes", "secret refund"]))
+            .await
+            .unwrap();
+        assert_eq!(message.content, "Share the [redacted]?");
+        let created = message.poll.unwrap();
+        assert_eq!(created.question, "Share the [redacted]?");
+        let options: Vec<&str> = created.options.iter().map(|o| o.text.as_str()).collect();
+        assert_eq!(options, vec!["yes", "[redacted] refund"]);
+        let flagged = ModerationRepository::find_flagged(&pool, &team.id, 50, None).await.unwrap();
+        assert_eq!(flagged.len(), 1);
+
+        let result = service.create_poll(&channel.id, &owner.id, poll("forbidden?", &["yes", "no"])).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+        let result = service.create_poll(&channel.id, &owner.id, poll("Lunch?", &["yes", "forbidden"])).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_messages_come_from_moderators() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let outsider = testing::team_member(&pool, &team, "outsider", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&member]).await;
+        word_rules(&pool, &team, &owner).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        let ephemeral = |user_id: Uuid, content: &str| SendEphemeralMessageRequest {
+            user_id,
+            content: content.to_string(),
+            format: None,
+        };
+        let message = service
+            .create_ephemeral_message(&channel.id, &owner.id, ephemeral(member.id, "your secret code"))
+            .await
+            .unwrap();
+        assert_eq!(message.channel_id, channel.id);
+        assert_eq!(message.sender_id, Some(owner.id));
+        assert_eq!(message.content, "your [redacted] code");
+
+        // Nothing is stored in the channel's history
+        let page = service.list_messages(&channel.id, &member.id, 50, None).await.unwrap();
+        assert!(page.items.is_empty());
+
+        let result = service
+            .create_ephemeral_message(&channel.id, &owner.id, ephemeral(outsider.id, "hi"))
+            .await;
+        assert!(matches!(result, Err(AppError::NotFoundError(_))));
+        let result = service
+            .create_ephemeral_message(&channel.id, &outsider.id, ephemeral(member.id, "hi"))
+            .await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+        let result = service
+            .create_ephemeral_message(&channel.id, &owner.id, ephemeral(member.id, "forbidden"))
+            .await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+        // Members can't send them to each other
+        let result = service
+            .create_ephemeral_message(&channel.id, &member.id, ephemeral(owner.id, "hi"))
+            .await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+    }
+
+    fn poll(question: &str, allow_multiple: bool) -> CreatePollRequest {
+        CreatePollRequest {
+            question: question.to_string(),
+            options: vec!["yes".to_string(), "no".to_string()],
+            allow_multiple,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_votes_and_retractions() {
+        let Some(pool) = testing::pool().await else { return };
+        let alice = testing::user(&pool, "alice").await;
+        let bob = testing::user(&pool, "bob").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &alice, &[&bob]).await;
+        let service = MessageService::new(Arc::new(pool));
+
+        // A single choice vote replaces the previous one
+        let single = service.create_poll(&channel.id, &alice.id, poll("Lunch?", false)).await.unwrap();
+        let [yes, no] = [0, 1].map(|i| single.poll.as_ref().unwrap().options[i].id);
+        service.vote_poll(&channel.id, &single.id, &bob.id, &yes).await.unwrap();
+        let result = service.vote_poll(&channel.id, &single.id, &bob.id, &no).await.unwrap();
+        assert_eq!(result.my_votes, vec![no]);
+        assert_eq!(result.total_voters, 1);
+        assert_eq!(result.options.iter().map(|o| o.vote_count).collect::<Vec<_>>(), vec![0, 1]);
+
+        // Options of another poll are rejected
+        let multiple = service.create_poll(&channel.id, &alice.id, poll("Snacks?", true)).await.unwrap();
+        let [chips, fruit] = [0, 1].map(|i| multiple.poll.as_ref().unwrap().options[i].id);
+        let result = service.vote_poll(&channel.id, &multiple.id, &bob.id, &yes).await;
+        assert!(matches!(result, Err(AppError::NotFoundError(_))));
+
+        service.vote_poll(&channel.id, &multiple.id, &bob.id, &chips).await.unwrap();
+        let result = service.vote_poll(&channel.id, &multiple.id, &bob.id, &fruit).await.unwrap();
+        assert_eq!(result.my_votes.len(), 2);
+        assert_eq!(result.total_voters, 1);
+
+        let result = service.retract_poll_vote(&channel.id, &multiple.id, &bob.id, &chips).await.unwrap();
+        assert_eq!(result.my_votes, vec![fruit]);
+        let result = service.retract_poll_vote(&channel.id, &multiple.id, &bob.id, &chips).await;
+        assert!(matches!(result, Err(AppError::NotFoundError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_retracting_a_vote_stays_in_its_poll() {
+        let Some(pool) = testing::pool().await else { return };
+        let alice = testing::user(&pool, "alice").await;
+        let bob = testing::user(&pool, "bob").await;
+        let channel = testing::channel(&pool, None, ChannelType::Private, &alice, &[&bob]).await;
+        let service = MessageService::new(Arc::new(pool.clone()));
+
+        let closed = service.create_poll(&channel.id, &alice.id, poll("Lunch?", false)).await.unwrap();
+        let yes = closed.poll.unwrap().options[0].id;
+        service.vote_poll(&channel.id, &closed.id, &bob.id, &yes).await.unwrap();
+        sqlx::query("UPDATE polls SET closes_at = NOW() - INTERVAL '1 minute' WHERE message_id = $1")
+            .bind(closed.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let result = service.retract_poll_vote(&channel.id, &closed.id, &bob.id, &yes).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+        // Naming an open poll in the path doesn't reach the closed poll's votes
+        let open = service.create_poll(&channel.id, &alice.id, poll("Snacks?", false)).await.unwrap();
+        let result = service.retract_poll_vote(&channel.id, &open.id, &bob.id, &yes).await;
+        assert!(matches!(result, Err(AppError::NotFoundError(_))));
+        let votes = PollRepository::find_votes(&pool, &closed.id).await.unwrap();
+        assert_eq!(votes.len(), 1);
+    }
 }
@@ -4,16 +4,23 @@ pub mod auth;
 pub mod users;
 pub mod teams;
 pub mod channels;
+pub mod content_rules;
+pub mod drafts;
+pub mod emoji;
+pub mod hydration;
+pub mod link_previews;
 pub mod messages;
 pub mod files;
 pub mod calls;
 pub mod notifications;
 pub mod meetings;
+pub mod retention;
 
 use sqlx::PgPool;
 use std::sync::Arc;
 
 use crate::config::AppConfig;
+use crate::fetcher::{HttpPageFetcher, PageFetcher};
 
 /// Container for all application services
 pub struct Services {
@@ -22,14 +29,24 @@ pub struct Services {
     pub teams: teams::TeamService,
     pub channels: channels::ChannelService,
     pub messages: messages::MessageService,
+    pub content_rules: content_rules::ContentRuleService,
+    pub drafts: drafts::DraftService,
+    pub emoji: emoji::EmojiService,
+    pub link_previews: link_previews::LinkPreviewService,
     pub files: files::FileService,
     pub calls: calls::CallService,
     pub notifications: notifications::NotificationService,
     pub meetings: meetings::MeetingService,
+    pub retention: retention::RetentionService,
 }
 
 impl Services {
     pub fn new(pool: PgPool, config: AppConfig) -> Self {
+        Self::with_page_fetcher(pool, config, Arc::new(HttpPageFetcher::new()))
+    }
+
+    /// Build the services with a custom fetcher for link previews
+    pub fn with_page_fetcher(pool: PgPool, config: AppConfig, page_fetcher: Arc<dyn PageFetcher>) -> Self {
         let pool = Arc::new(pool);
         let config = Arc::new(config);
 
@@ -39,10 +56,15 @@ impl Services {
             teams: teams::TeamService::new(pool.clone()),
             channels: channels::ChannelService::new(pool.clone()),
             messages: messages::MessageService::new(pool.clone()),
+            content_rules: content_rules::ContentRuleService::new(pool.clone()),
+            drafts: drafts::DraftService::new(pool.clone()),
+            emoji: emoji::EmojiService::new(pool.clone(), config.clone()),
+            link_previews: link_previews::LinkPreviewService::new(pool.clone(), page_fetcher),
             files: files::FileService::new(pool.clone(), config.clone()),
             calls: calls::CallService::new(pool.clone()),
             notifications: notifications::NotificationService::new(pool.clone()),
             meetings: meetings::MeetingService::new(pool.clone()),
+            retention: retention::RetentionService::new(pool.clone(), config.clone()),
         }
     }
 }
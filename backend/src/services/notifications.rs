@@ -1,12 +1,15 @@
 //! Notification service
 
-use shared::dto::NotificationResponse;
+use chrono::Utc;
+use shared::dto::{MessageResponse, NotificationResponse, PaginatedResponse};
 use shared::error::AppError;
+use shared::models::{ChannelType, MentionType, Notification, NotificationLevel};
+use shared::pagination::{paginate, Cursor};
 use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::db::NotificationRepository;
+use crate::db::{ChannelRepository, NotificationRepository};
 
 pub struct NotificationService {
     pool: Arc<PgPool>,
@@ -36,15 +39,7 @@ impl NotificationService {
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        Ok(NotificationResponse {
-            id: notification.id,
-            title: notification.title,
-            body: notification.body,
-            notification_type: notification.notification_type.to_string(),
-            reference_id: notification.reference_id.and_then(|s| Uuid::parse_str(&s).ok()),
-            read: notification.read,
-            created_at: notification.created_at,
-        })
+        Ok(Self::notification_response(notification))
     }
 
     pub async fn list_notifications(
@@ -52,30 +47,19 @@ impl NotificationService {
         user_id: &Uuid,
         unread_only: bool,
         limit: i64,
-        offset: i64,
-    ) -> Result<Vec<NotificationResponse>, AppError> {
+        before: Option<Cursor>,
+    ) -> Result<PaginatedResponse<NotificationResponse>, AppError> {
         let notifications = NotificationRepository::find_by_user(
             &self.pool,
             user_id,
             unread_only,
-            limit,
-            offset,
+            limit + 1,
+            before,
         )
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        Ok(notifications
-            .into_iter()
-            .map(|n| NotificationResponse {
-                id: n.id,
-                title: n.title,
-                body: n.body,
-                notification_type: n.notification_type.to_string(),
-                reference_id: n.reference_id.and_then(|s| Uuid::parse_str(&s).ok()),
-                read: n.read,
-                created_at: n.created_at,
-            })
-            .collect())
+        Ok(paginate(notifications, limit, |n| Cursor::new(n.created_at, n.id)).map(Self::notification_response))
     }
 
     pub async fn mark_as_read(&self, notification_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
@@ -107,41 +91,71 @@ impl NotificationService {
             .map_err(|e| AppError::DatabaseError(e.to_string()))
     }
 
-    // Helper methods for creating specific notification types
-    pub async fn notify_new_message(
+    /// Notify channel members about a new message according to their channel preferences.
+    /// Mentioned members get a mention notification instead; `@here` only reaches the
+    /// members in `online_user_ids`. Returns the created notifications with their recipients.
+    pub async fn notify_channel_message(
         &self,
-        user_id: &Uuid,
-        sender_name: &str,
-        channel_name: &str,
-        message_id: &Uuid,
-    ) -> Result<NotificationResponse, AppError> {
-        self.create_notification(
-            user_id,
-            &format!("New message from {}", sender_name),
-            &format!("You have a new message in {}", channel_name),
-            "new_message",
-            Some(message_id),
-        )
-        .await
-    }
+        message: &MessageResponse,
+        online_user_ids: &[Uuid],
+    ) -> Result<Vec<(Uuid, NotificationResponse)>, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, &message.channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
 
-    pub async fn notify_mention(
-        &self,
-        user_id: &Uuid,
-        sender_name: &str,
-        channel_name: &str,
-        message_id: &Uuid,
-    ) -> Result<NotificationResponse, AppError> {
-        self.create_notification(
-            user_id,
-            &format!("{} mentioned you", sender_name),
-            &format!("You were mentioned in {}", channel_name),
-            "mention",
-            Some(message_id),
-        )
-        .await
+        let members = ChannelRepository::find_members(&self.pool, &message.channel_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let channel_name = match channel.channel_type {
+            ChannelType::DirectMessage => "a direct message".to_string(),
+            _ => channel.name,
+        };
+
+        let now = Utc::now();
+        let mut recipients = Vec::new();
+        for member in members {
+            if member.user_id == message.sender.id
+                || member.notify_level == NotificationLevel::Nothing
+                || member.is_muted_at(now)
+            {
+                continue;
+            }
+
+            let mentioned = message.mentions.iter().any(|m| match m.mention_type {
+                MentionType::User => m.user_id == Some(member.user_id),
+                MentionType::Channel => true,
+                MentionType::Here => online_user_ids.contains(&member.user_id),
+            });
+
+            if mentioned {
+                recipients.push((
+                    member.user_id,
+                    format!("{} mentioned you", message.sender.display_name),
+                    format!("You were mentioned in {}", channel_name),
+                    "mention",
+                ));
+            } else if member.notify_level == NotificationLevel::All {
+                recipients.push((
+                    member.user_id,
+                    format!("New message from {}", message.sender.display_name),
+                    format!("You have a new message in {}", channel_name),
+                    "message",
+                ));
+            }
+        }
+
+        let notifications = NotificationRepository::create_many(&self.pool, &recipients, Some(&message.id))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(notifications
+            .into_iter()
+            .map(|n| (n.user_id, Self::notification_response(n)))
+            .collect())
     }
 
+    // Helper methods for creating specific notification types
     pub async fn notify_team_invite(
         &self,
         user_id: &Uuid,
@@ -174,4 +188,167 @@ impl NotificationService {
         )
         .await
     }
+
+    fn notification_response(notification: Notification) -> NotificationResponse {
+        NotificationResponse {
+            id: notification.id,
+            title: notification.title,
+            body: notification.body,
+            notification_type: notification.notification_type.to_string(),
+            reference_id: notification.reference_id.and_then(|s| Uuid::parse_str(&s).ok()),
+            read: notification.read,
+            created_at: notification.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::services::messages::MessageService;
+    use shared::dto::SendMessageRequest;
+    use shared::models::TeamRole;
+
+    fn text(content: &str) -> SendMessageRequest {
+        SendMessageRequest {
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_channel_message_notifications() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let quiet = testing::team_member(&pool, &team, "quiet", TeamRole::Member).await;
+        let loud = testing::team_member(&pool, &team, "loud", TeamRole::Member).await;
+        let mentioned = testing::team_member(&pool, &team, "mentioned", TeamRole::Member).await;
+        let channel = testing::channel(
+            &pool,
+            Some(&team),
+            ChannelType::Public,
+            &owner,
+            &[&quiet, &loud, &mentioned],
+        )
+        .await;
+        ChannelRepository::update_settings(&pool, &channel.id, &loud.id, NotificationLevel::All, None, false)
+            .await
+            .unwrap();
+
+        let pool = Arc::new(pool);
+        let messages = MessageService::new(pool.clone());
+        let notifications = NotificationService::new(pool.clone());
+
+        let message = messages
+            .send_message(&channel.id, &owner.id, text("hi @mentioned"))
+            .await
+            .unwrap();
+        let mut sent: Vec<(Uuid, String)> = notifications
+            .notify_channel_message(&message, &[])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(user_id, n)| (user_id, n.notification_type))
+            .collect();
+        sent.sort();
+
+        let mut expected = vec![(loud.id, "message".to_string()), (mentioned.id, "mention".to_string())];
+        expected.sort();
+        assert_eq!(sent, expected);
+    }
+
+    #[tokio::test]
+    async fn test_default_levels() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let other = testing::user(&pool, "other").await;
+        let team = testing::team(&pool, &owner).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        let dm = testing::channel(&pool, None, ChannelType::DirectMessage, &owner, &[&other]).await;
+
+        let member = ChannelRepository::find_member(&pool, &channel.id, &owner.id).await.unwrap();
+        assert_eq!(member.notify_level, NotificationLevel::Mentions);
+        let member = ChannelRepository::find_member(&pool, &dm.id, &other.id).await.unwrap();
+        assert_eq!(member.notify_level, NotificationLevel::All);
+    }
+
+    #[tokio::test]
+    async fn test_mention_counts_match_notifications() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let online = testing::team_member(&pool, &team, "online", TeamRole::Member).await;
+        let offline = testing::team_member(&pool, &team, "offline", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&online, &offline]).await;
+
+        let pool = Arc::new(pool);
+        let messages = MessageService::new(pool.clone());
+        let notifications = NotificationService::new(pool.clone());
+        let mention_count = |user_id: Uuid| {
+            let pool = pool.clone();
+            let channel_id = channel.id;
+            async move { ChannelRepository::get_mention_count(&pool, &channel_id, &user_id).await.unwrap() }
+        };
+
+        let here = messages.send_message(&channel.id, &owner.id, text("@here standup")).await.unwrap();
+        notifications.notify_channel_message(&here, &[online.id]).await.unwrap();
+        assert_eq!(mention_count(online.id).await, 1);
+        assert_eq!(mention_count(offline.id).await, 0);
+
+        let everyone = messages.send_message(&channel.id, &owner.id, text("@channel release")).await.unwrap();
+        assert_eq!(mention_count(offline.id).await, 1);
+
+        // Messages that are no longer visible stop counting
+        sqlx::query("UPDATE messages SET expires_at = NOW() - INTERVAL '1 second' WHERE id = $1")
+            .bind(here.id)
+            .execute(pool.as_ref())
+            .await
+            .unwrap();
+        messages.delete_message(&channel.id, &everyone.id, &owner.id, None).await.unwrap();
+        assert_eq!(mention_count(online.id).await, 0);
+        assert_eq!(mention_count(offline.id).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_muted_members_are_not_notified() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let muted = testing::team_member(&pool, &team, "muted", TeamRole::Member).await;
+        let silent = testing::team_member(&pool, &team, "silent", TeamRole::Member).await;
+        let unmuted = testing::team_member(&pool, &team, "unmuted", TeamRole::Member).await;
+        let channel = testing::channel(
+            &pool,
+            Some(&team),
+            ChannelType::Public,
+            &owner,
+            &[&muted, &silent, &unmuted],
+        )
+        .await;
+        let now = Utc::now();
+        let settings = [
+            (&muted, NotificationLevel::All, Some(now + chrono::Duration::hours(1))),
+            (&silent, NotificationLevel::Nothing, None),
+            (&unmuted, NotificationLevel::Mentions, Some(now - chrono::Duration::hours(1))),
+        ];
+        for (user, level, muted_until) in settings {
+            ChannelRepository::update_settings(&pool, &channel.id, &user.id, level, muted_until, false)
+                .await
+                .unwrap();
+        }
+
+        let pool = Arc::new(pool);
+        let messages = MessageService::new(pool.clone());
+        let notifications = NotificationService::new(pool.clone());
+
+        let message = messages
+            .send_message(&channel.id, &owner.id, text("@muted @silent @unmuted"))
+            .await
+            .unwrap();
+        let sent = notifications.notify_channel_message(&message, &[]).await.unwrap();
+        let recipients: Vec<Uuid> = sent.into_iter().map(|(user_id, _)| user_id).collect();
+        assert_eq!(recipients, vec![unmuted.id]);
+    }
 }
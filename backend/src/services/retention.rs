@@ -0,0 +1,332 @@
+//! Message retention service
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use shared::dto::{RetentionPolicyResponse, UpdateRetentionPolicyRequest};
+use shared::error::AppError;
+use shared::models::TeamRole;
+use sqlx::PgPool;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::db::{ChannelRepository, RetentionRepository, TeamRepository};
+use crate::services::files::FileService;
+
+/// What one purge deleted, or would have deleted in dry-run mode
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub channels: u64,
+    pub messages: u64,
+    pub files: u64,
+}
+
+/// Totals since the server started, reported by the health check
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionMetrics {
+    pub runs: u64,
+    pub purged_messages: u64,
+    pub purged_files: u64,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_report: Option<RetentionReport>,
+}
+
+pub struct RetentionService {
+    pool: Arc<PgPool>,
+    config: Arc<AppConfig>,
+    metrics: Mutex<RetentionMetrics>,
+}
+
+impl RetentionService {
+    pub fn new(pool: Arc<PgPool>, config: Arc<AppConfig>) -> Self {
+        Self {
+            pool,
+            config,
+            metrics: Mutex::new(RetentionMetrics::default()),
+        }
+    }
+
+    pub async fn get_team_policy(
+        &self,
+        team_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<RetentionPolicyResponse, AppError> {
+        if !TeamRepository::is_member(&self.pool, team_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You are not a member of this team".to_string(),
+            ));
+        }
+
+        let retention_days = RetentionRepository::get_team_policy(&self.pool, team_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Team not found".to_string()))?;
+
+        Ok(self.policy_response(retention_days, &[retention_days]))
+    }
+
+    /// Team owners and admins decide how long the team's channels keep messages
+    pub async fn update_team_policy(
+        &self,
+        team_id: &Uuid,
+        user_id: &Uuid,
+        request: UpdateRetentionPolicyRequest,
+    ) -> Result<RetentionPolicyResponse, AppError> {
+        self.check_team_admin(team_id, user_id).await?;
+
+        RetentionRepository::set_team_policy(&self.pool, team_id, request.retention_days)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        self.get_team_policy(team_id, user_id).await
+    }
+
+    pub async fn get_channel_policy(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<RetentionPolicyResponse, AppError> {
+        if !ChannelRepository::is_member(&self.pool, channel_id, user_id)
+            .await
+            .unwrap_or(false)
+        {
+            return Err(AppError::AuthorizationError(
+                "You don't have access to this channel".to_string(),
+            ));
+        }
+
+        let (channel_days, team_days) = RetentionRepository::get_channel_policy(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        Ok(self.policy_response(channel_days, &[channel_days, team_days]))
+    }
+
+    /// Override the team's policy for one channel. Reserved for team owners and
+    /// admins, since retention is a compliance decision rather than a channel setting.
+    pub async fn update_channel_policy(
+        &self,
+        channel_id: &Uuid,
+        user_id: &Uuid,
+        request: UpdateRetentionPolicyRequest,
+    ) -> Result<RetentionPolicyResponse, AppError> {
+        let channel = ChannelRepository::find_by_id(&self.pool, channel_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("Channel not found".to_string()))?;
+
+        let Some(team_id) = channel.team_id else {
+            return Err(AppError::BadRequest(
+                "Retention can only be set on team channels".to_string(),
+            ));
+        };
+        self.check_team_admin(&team_id, user_id).await?;
+
+        RetentionRepository::set_channel_policy(&self.pool, channel_id, request.retention_days)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let (channel_days, team_days) = RetentionRepository::get_channel_policy(&self.pool, channel_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(self.policy_response(channel_days, &[channel_days, team_days]))
+    }
+
+    /// Delete messages older than their channel's retention, along with their
+    /// attachments. In dry-run mode nothing is deleted and the report counts what
+    /// would have been.
+    pub async fn purge_expired(&self, files: &FileService) -> Result<RetentionReport, AppError> {
+        let retention = &self.config.retention;
+        let mut report = RetentionReport {
+            dry_run: retention.dry_run,
+            ..Default::default()
+        };
+
+        let channels = RetentionRepository::find_expiring_channels(&self.pool, retention.default_days)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let now = Utc::now();
+        for (channel_id, retention_days) in channels {
+            let cutoff = now - Duration::days(retention_days as i64);
+
+            let (messages, attachments) = if retention.dry_run {
+                let (messages, attachments) = RetentionRepository::count_expired(&self.pool, &channel_id, cutoff)
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                (messages as u64, attachments as u64)
+            } else {
+                self.purge_channel(files, &channel_id, cutoff).await?
+            };
+
+            if messages > 0 {
+                report.channels += 1;
+                report.messages += messages;
+                report.files += attachments;
+            }
+        }
+
+        let mut metrics = self.metrics.lock().unwrap();
+        metrics.runs += 1;
+        if !report.dry_run {
+            metrics.purged_messages += report.messages;
+            metrics.purged_files += report.files;
+        }
+        metrics.last_run_at = Some(now);
+        metrics.last_report = Some(report);
+
+        Ok(report)
+    }
+
+    pub fn metrics(&self) -> RetentionMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    /// Delete the channel's expired messages in batches, so no single transaction
+    /// holds locks on a large backlog
+    async fn purge_channel(
+        &self,
+        files: &FileService,
+        channel_id: &Uuid,
+        cutoff: DateTime<Utc>,
+    ) -> Result<(u64, u64), AppError> {
+        let batch_size = self.config.retention.batch_size.max(1);
+        let (mut messages, mut attachments) = (0, 0);
+
+        loop {
+            let (deleted, storage_paths) =
+                RetentionRepository::purge_expired(&self.pool, channel_id, cutoff, batch_size)
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+            messages += deleted;
+            attachments += storage_paths.len() as u64;
+            files.remove_stored_files(&storage_paths).await?;
+
+            if (deleted as i64) < batch_size {
+                return Ok((messages, attachments));
+            }
+        }
+    }
+
+    /// The effective retention is the first policy set among `policies`, ordered
+    /// from the most specific, then the server default. 0 means forever.
+    fn policy_response(&self, retention_days: Option<i32>, policies: &[Option<i32>]) -> RetentionPolicyResponse {
+        let effective = policies
+            .iter()
+            .flatten()
+            .copied()
+            .next()
+            .unwrap_or(self.config.retention.default_days);
+
+        RetentionPolicyResponse {
+            retention_days,
+            effective_retention_days: Some(effective).filter(|days| *days > 0),
+        }
+    }
+
+    async fn check_team_admin(&self, team_id: &Uuid, user_id: &Uuid) -> Result<(), AppError> {
+        let role = TeamRepository::get_user_role(&self.pool, team_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        match role {
+            Some(TeamRole::Owner) | Some(TeamRole::Admin) => Ok(()),
+            Some(_) => Err(AppError::AuthorizationError(
+                "Only team owners and admins can change retention".to_string(),
+            )),
+            None => Err(AppError::AuthorizationError(
+                "You are not a member of this team".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{testing, ForwardRepository, MessageRepository};
+    use crate::services::messages::MessageService;
+    use shared::dto::{ForwardMessageRequest, SendMessageRequest};
+    use shared::models::ChannelType;
+
+    fn config(dry_run: bool) -> Arc<AppConfig> {
+        let mut config = AppConfig::load().unwrap();
+        config.retention.default_days = 0;
+        config.retention.batch_size = 1;
+        config.retention.dry_run = dry_run;
+        Arc::new(config)
+    }
+
+    #[tokio::test]
+    async fn test_purge_deletes_expired_messages_and_forwarded_copies() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[]).await;
+        let other = testing::channel(&pool, None, ChannelType::Public, &owner, &[]).await;
+        let pool = Arc::new(pool);
+        let messages = MessageService::new(pool.clone());
+
+        let send = |content: &str| SendMessageRequest { content: content.to_string(), ..Default::default() };
+        let old = messages.send_message(&channel.id, &owner.id, send("old")).await.unwrap();
+        let recent = messages.send_message(&channel.id, &owner.id, send("recent")).await.unwrap();
+        let forward = ForwardMessageRequest { target_channel_id: other.id, comment: None, format: None };
+        let forwarded = messages.forward_message(&channel.id, &old.id, &owner.id, forward).await.unwrap();
+        sqlx::query("UPDATE messages SET created_at = NOW() - INTERVAL '10 days' WHERE id = $1")
+            .bind(old.id)
+            .execute(&*pool)
+            .await
+            .unwrap();
+        RetentionRepository::set_team_policy(&pool, &team.id, Some(7)).await.unwrap();
+
+        let dry_run = RetentionService::new(pool.clone(), config(true));
+        let files = FileService::new(pool.clone(), config(true));
+        let report = dry_run.purge_expired(&files).await.unwrap();
+        assert_eq!((report.channels, report.messages), (1, 1));
+        assert!(MessageRepository::find_by_id(&pool, &old.id).await.is_ok());
+
+        let service = RetentionService::new(pool.clone(), config(false));
+        let report = service.purge_expired(&files).await.unwrap();
+        assert_eq!((report.channels, report.messages), (1, 1));
+        assert!(MessageRepository::find_by_id(&pool, &old.id).await.is_err());
+        assert!(MessageRepository::find_by_id(&pool, &recent.id).await.is_ok());
+
+        // The forward stays, without the purged content
+        let forwards = ForwardRepository::find_by_messages(&pool, &[forwarded.id]).await.unwrap();
+        assert_eq!(forwards.len(), 1);
+        assert_eq!(forwards[0].content, "");
+        assert_eq!(forwards[0].source_message_id, None);
+
+        let report = service.purge_expired(&files).await.unwrap();
+        assert_eq!(report.messages, 0);
+        assert_eq!(service.metrics().purged_messages, 1);
+    }
+
+    #[tokio::test]
+    async fn test_only_team_admins_change_retention() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let channel = testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&member]).await;
+        let service = RetentionService::new(Arc::new(pool), config(false));
+
+        let request = UpdateRetentionPolicyRequest { retention_days: Some(30) };
+        let result = service.update_team_policy(&team.id, &member.id, request.clone()).await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+
+        let policy = service.update_team_policy(&team.id, &owner.id, request).await.unwrap();
+        assert_eq!(policy.effective_retention_days, Some(30));
+
+        // The channel inherits until it sets its own, and 0 keeps messages forever
+        let policy = service.get_channel_policy(&channel.id, &member.id).await.unwrap();
+        assert_eq!((policy.retention_days, policy.effective_retention_days), (None, Some(30)));
+        let request = UpdateRetentionPolicyRequest { retention_days: Some(0) };
+        let policy = service.update_channel_policy(&channel.id, &owner.id, request).await.unwrap();
+        assert_eq!((policy.retention_days, policy.effective_retention_days), (Some(0), None));
+    }
+}
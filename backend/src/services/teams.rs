@@ -1,15 +1,19 @@
 //! Team service
 
 use shared::dto::{
-    CreateTeamRequest, TeamMemberResponse, TeamResponse, UpdateTeamRequest, UserResponse,
+    CreateTeamRequest, ModerationRecordResponse, PaginatedResponse, TeamMemberResponse, TeamResponse,
+    UpdateTeamRequest, UserResponse,
 };
 use shared::error::AppError;
-use shared::models::TeamRole;
+use shared::models::{TeamRole, User};
+use shared::pagination::{paginate, Cursor};
 use sqlx::PgPool;
+use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::db::{TeamRepository, UserRepository};
+use crate::db::{ModerationRepository, TeamRepository, UserRepository};
+use crate::services::hydration;
 
 pub struct TeamService {
     pool: Arc<PgPool>,
@@ -299,6 +303,60 @@ impl TeamService {
             .map_err(|e| AppError::DatabaseError(e.to_string()))
     }
 
+    /// Moderation actions taken in the team's channels, visible to those who can moderate
+    pub async fn get_moderation_log(
+        &self,
+        team_id: &Uuid,
+        user_id: &Uuid,
+        limit: i64,
+        before: Option<Cursor>,
+    ) -> Result<PaginatedResponse<ModerationRecordResponse>, AppError> {
+        let role = TeamRepository::get_user_role(&self.pool, team_id, user_id)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        if !role.is_some_and(|r| r.can_moderate()) {
+            return Err(AppError::AuthorizationError(
+                "You don't have permission to perform this action".to_string(),
+            ));
+        }
+
+        let records = ModerationRepository::find_by_team(&self.pool, team_id, limit + 1, before)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let page = paginate(records, limit, |r| Cursor::new(r.created_at, r.id));
+
+        let moderator_ids: Vec<Uuid> = page.items.iter().map(|r| r.moderator_id).collect();
+        let moderators: HashMap<Uuid, User> = UserRepository::find_by_ids(&self.pool, &moderator_ids)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .into_iter()
+            .map(|u| (u.id, u))
+            .collect();
+
+        let items = page
+            .items
+            .into_iter()
+            .filter_map(|record| {
+                Some(ModerationRecordResponse {
+                    id: record.id,
+                    message_id: record.message_id,
+                    channel_id: record.channel_id,
+                    moderator: hydration::user_response(moderators.get(&record.moderator_id)?),
+                    action: record.action,
+                    reason: record.reason,
+                    created_at: record.created_at,
+                })
+            })
+            .collect();
+
+        Ok(PaginatedResponse {
+            items,
+            has_more: page.has_more,
+            next_cursor: page.next_cursor,
+        })
+    }
+
     pub async fn is_member(&self, team_id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
         TeamRepository::is_member(&self.pool, team_id, user_id)
             .await
@@ -321,3 +379,52 @@ impl TeamService {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing;
+    use crate::services::messages::MessageService;
+    use shared::dto::{SendMessageRequest, UpdateMessageRequest};
+    use shared::models::ChannelType;
+
+    #[tokio::test]
+    async fn test_moderation_log_pages() {
+        let Some(pool) = testing::pool().await else { return };
+        let owner = testing::user(&pool, "owner").await;
+        let team = testing::team(&pool, &owner).await;
+        let moderator = testing::team_member(&pool, &team, "moderator", TeamRole::Moderator).await;
+        let member = testing::team_member(&pool, &team, "member", TeamRole::Member).await;
+        let channel =
+            testing::channel(&pool, Some(&team), ChannelType::Public, &owner, &[&moderator, &member]).await;
+        let pool = Arc::new(pool);
+        let messages = MessageService::new(pool.clone());
+        let teams = TeamService::new(pool.clone());
+
+        for reason in ["first", "second", "third"] {
+            let request = SendMessageRequest {
+                content: "spam".to_string(),
+                ..Default::default()
+            };
+            let message = messages.send_message(&channel.id, &member.id, request).await.unwrap();
+            let edit = UpdateMessageRequest {
+                content: "[removed]".to_string(),
+                reason: Some(reason.to_string()),
+            };
+            messages.update_message(&channel.id, &message.id, &moderator.id, edit).await.unwrap();
+        }
+
+        let result = teams.get_moderation_log(&team.id, &member.id, 2, None).await;
+        assert!(matches!(result, Err(AppError::AuthorizationError(_))));
+
+        let first = teams.get_moderation_log(&team.id, &moderator.id, 2, None).await.unwrap();
+        assert!(first.has_more);
+        let cursor = Cursor::parse(first.next_cursor.as_deref()).unwrap();
+        let second = teams.get_moderation_log(&team.id, &owner.id, 2, cursor).await.unwrap();
+        assert!(!second.has_more);
+
+        let reasons: Vec<String> = first.items.iter().chain(&second.items).map(|r| r.reason.clone()).collect();
+        assert_eq!(reasons, vec!["third", "second", "first"]);
+        assert!(first.items.iter().all(|r| r.moderator.id == moderator.id));
+    }
+}
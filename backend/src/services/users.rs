@@ -1,6 +1,8 @@
 //! User service
 
-use shared::dto::{UpdateUserRequest, UserResponse};
+use shared::dto::{
+    PrivacySettingsResponse, UpdatePrivacySettingsRequest, UpdateUserRequest, UserResponse,
+};
 use shared::error::AppError;
 use shared::models::UserStatus;
 use sqlx::PgPool;
@@ -95,6 +97,33 @@ impl UserService {
         })
     }
 
+    pub async fn get_privacy_settings(
+        &self,
+        user_id: &Uuid,
+    ) -> Result<PrivacySettingsResponse, AppError> {
+        let read_receipts_enabled = UserRepository::get_read_receipts_enabled(&self.pool, user_id)
+            .await
+            .map_err(|_| AppError::NotFoundError("User not found".to_string()))?;
+
+        Ok(PrivacySettingsResponse {
+            read_receipts_enabled,
+        })
+    }
+
+    pub async fn update_privacy_settings(
+        &self,
+        user_id: &Uuid,
+        request: UpdatePrivacySettingsRequest,
+    ) -> Result<PrivacySettingsResponse, AppError> {
+        if let Some(enabled) = request.read_receipts_enabled {
+            UserRepository::set_read_receipts_enabled(&self.pool, user_id, enabled)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        }
+
+        self.get_privacy_settings(user_id).await
+    }
+
     pub async fn change_password(
         &self,
         user_id: &Uuid,
@@ -0,0 +1,139 @@
+//! Background tasks
+
+use shared::dto::WebSocketMessage;
+use shared::error::AppError;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::handlers::messages::{
+    broadcast_new_message, send_ephemeral_message, spawn_link_previews, spawn_message_notifications,
+};
+use crate::services::Services;
+use crate::websocket::WebSocketServer;
+
+/// How often the dispatcher looks for due scheduled messages
+const SCHEDULED_MESSAGE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// Most scheduled messages sent per poll
+const SCHEDULED_MESSAGE_BATCH_SIZE: i64 = 100;
+/// How often the purger looks for expired self-destructing messages
+const EXPIRED_MESSAGE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// Most expired messages purged per poll
+const EXPIRED_MESSAGE_BATCH_SIZE: i64 = 100;
+
+/// Post scheduled messages once they're due, with the same checks and broadcasts
+/// as sending them directly
+pub fn spawn_scheduled_message_dispatcher(services: Arc<Services>, ws_server: Arc<WebSocketServer>) {
+    actix_rt::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULED_MESSAGE_POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let due = match services
+                .messages
+                .claim_due_scheduled_messages(SCHEDULED_MESSAGE_BATCH_SIZE)
+                .await
+            {
+                Ok(due) => due,
+                Err(e) => {
+                    warn!("Failed to load due scheduled messages: {}", e);
+                    continue;
+                }
+            };
+
+            for scheduled in due {
+                let channel_id = scheduled.channel_id;
+                let sender_id = scheduled.sender_id;
+
+                match services.messages.dispatch_scheduled_message(scheduled).await {
+                    Ok(Some(message)) => {
+                        broadcast_new_message(&ws_server, &channel_id, &message, None, None);
+
+                        spawn_message_notifications(services.clone(), ws_server.clone(), message.clone());
+                        spawn_link_previews(services.clone(), ws_server.clone(), message);
+                    }
+                    Ok(None) => {}
+                    Err(e) => notify_dispatch_failure(&ws_server, &channel_id, &sender_id, e),
+                }
+            }
+        }
+    });
+}
+
+/// Let the author know, in the channel, that their scheduled message could not be posted.
+/// It stays in their scheduled messages, marked as failed, for them to retry.
+fn notify_dispatch_failure(ws_server: &WebSocketServer, channel_id: &Uuid, sender_id: &Uuid, e: AppError) {
+    warn!("Failed to send scheduled message for user {}: {}", sender_id, e);
+    send_ephemeral_message(
+        ws_server,
+        channel_id,
+        sender_id,
+        format!("Your scheduled message could not be sent: {}", e),
+    );
+}
+
+/// Purge self-destructing messages once their time to live has run out, and tell
+/// the channel they are gone
+pub fn spawn_expired_message_purger(services: Arc<Services>, ws_server: Arc<WebSocketServer>) {
+    actix_rt::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRED_MESSAGE_POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let purged = match services
+                .messages
+                .purge_expired_messages(EXPIRED_MESSAGE_BATCH_SIZE)
+                .await
+            {
+                Ok(purged) => purged,
+                Err(e) => {
+                    warn!("Failed to purge expired messages: {}", e);
+                    continue;
+                }
+            };
+
+            for (message, storage_paths) in purged {
+                if let Err(e) = services.files.remove_stored_files(&storage_paths).await {
+                    warn!("Failed to remove files of expired message {}: {}", message.id, e);
+                }
+
+                // Messages deleted before they expired were announced already
+                if message.deleted_at.is_none() {
+                    let ws_msg = WebSocketMessage::MessageDeleted {
+                        channel_id: message.channel_id,
+                        message_id: message.id,
+                        moderated: false,
+                    };
+                    ws_server.broadcast_to_channel(&message.channel_id, &ws_msg, None);
+                }
+            }
+        }
+    });
+}
+
+/// Delete messages past their channel's retention every `interval_secs`, logging
+/// what was purged, or what would have been in dry-run mode
+pub fn spawn_retention_purger(services: Arc<Services>, interval_secs: u64) {
+    actix_rt::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(1)));
+
+        loop {
+            interval.tick().await;
+
+            match services.retention.purge_expired(&services.files).await {
+                Ok(report) if report.dry_run => info!(
+                    "Retention dry run: would purge {} messages and {} files in {} channels",
+                    report.messages, report.files, report.channels
+                ),
+                Ok(report) if report.messages > 0 => info!(
+                    "Retention purged {} messages and {} files in {} channels",
+                    report.messages, report.files, report.channels
+                ),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to purge messages past retention: {}", e),
+            }
+        }
+    });
+}
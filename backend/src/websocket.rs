@@ -2,24 +2,30 @@
 
 use actix_web::{web, HttpRequest, HttpResponse};
 use actix_ws::Message;
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use futures_util::StreamExt;
-use shared::dto::WebSocketMessage;
+use shared::dto::{MessageResponse, SendMessageRequest, WebSocketMessage};
+use shared::error::AppError;
 use shared::models::UserStatus;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
+use validator::Validate;
 
 use crate::services::Services;
 
 /// WebSocket server managing all connections
 pub struct WebSocketServer {
-    /// Map of user_id to their sender channel
+    /// Map of user_id to their sender channel, shared by all of the user's connections
     connections: DashMap<Uuid, broadcast::Sender<String>>,
-    /// Map of channel_id to set of user_ids subscribed to it
-    channel_subscriptions: DashMap<Uuid, HashSet<Uuid>>,
+    /// Map of user_id to their number of open connections
+    connection_counts: DashMap<Uuid, usize>,
+    /// Map of channel_id to the users subscribed to it, with the ids of each user's
+    /// connections that are subscribed
+    channel_subscriptions: DashMap<Uuid, HashMap<Uuid, HashSet<Uuid>>>,
     /// Map of user_id to their current status
     user_statuses: DashMap<Uuid, UserStatus>,
     /// Map of call_id to set of user_ids participating in the call
@@ -30,23 +36,45 @@ impl WebSocketServer {
     pub fn new() -> Self {
         Self {
             connections: DashMap::new(),
+            connection_counts: DashMap::new(),
             channel_subscriptions: DashMap::new(),
             user_statuses: DashMap::new(),
             call_subscriptions: DashMap::new(),
         }
     }
 
-    /// Register a new connection
+    /// Register a new connection. A user may be connected from several devices at once,
+    /// and each connection receives everything sent to the user.
     pub fn register(&self, user_id: Uuid) -> broadcast::Receiver<String> {
-        let (tx, rx) = broadcast::channel(100);
-        self.connections.insert(user_id, tx);
+        let mut count = self.connection_counts.entry(user_id).or_insert(0);
+        *count += 1;
+
+        let rx = match self.connections.get(&user_id) {
+            Some(sender) => sender.subscribe(),
+            None => {
+                let (tx, rx) = broadcast::channel(100);
+                self.connections.insert(user_id, tx);
+                rx
+            }
+        };
         self.user_statuses.insert(user_id, UserStatus::Online);
         rx
     }
 
-    /// Unregister a connection
-    pub fn unregister(&self, user_id: &Uuid) {
-        self.connections.remove(user_id);
+    /// Unregister a connection. Returns true when it was the user's last one.
+    pub fn unregister(&self, user_id: &Uuid) -> bool {
+        match self.connection_counts.entry(*user_id) {
+            Entry::Occupied(mut entry) => {
+                *entry.get_mut() -= 1;
+                if *entry.get() > 0 {
+                    return false;
+                }
+                self.connections.remove(user_id);
+                entry.remove();
+            }
+            Entry::Vacant(_) => return false,
+        }
+
         self.user_statuses.insert(*user_id, UserStatus::Offline);
 
         // Remove from all channel subscriptions
@@ -58,18 +86,37 @@ impl WebSocketServer {
         for mut entry in self.call_subscriptions.iter_mut() {
             entry.value_mut().remove(user_id);
         }
+
+        true
     }
 
-    /// Subscribe user to a channel
-    pub fn subscribe_to_channel(&self, channel_id: Uuid, user_id: Uuid) {
-        self.channel_subscriptions
-            .entry(channel_id)
-            .or_insert_with(HashSet::new)
-            .insert(user_id);
+    /// Subscribe one of the user's connections to a channel. Returns true when no other
+    /// connection of the user was subscribed yet.
+    pub fn subscribe_to_channel(&self, channel_id: Uuid, user_id: Uuid, connection_id: Uuid) -> bool {
+        let mut subscribers = self.channel_subscriptions.entry(channel_id).or_default();
+        let connections = subscribers.entry(user_id).or_default();
+        connections.insert(connection_id) && connections.len() == 1
     }
 
-    /// Unsubscribe user from a channel
-    pub fn unsubscribe_from_channel(&self, channel_id: &Uuid, user_id: &Uuid) {
+    /// Unsubscribe one of the user's connections from a channel. Returns true when it was
+    /// subscribed and no other connection of the user is still subscribed.
+    pub fn unsubscribe_from_channel(&self, channel_id: &Uuid, user_id: &Uuid, connection_id: &Uuid) -> bool {
+        let Some(mut subscribers) = self.channel_subscriptions.get_mut(channel_id) else {
+            return false;
+        };
+        let Some(connections) = subscribers.get_mut(user_id) else {
+            return false;
+        };
+
+        if !connections.remove(connection_id) || !connections.is_empty() {
+            return false;
+        }
+        subscribers.remove(user_id);
+        true
+    }
+
+    /// Unsubscribe all of the user's connections from a channel they no longer belong to
+    pub fn remove_from_channel(&self, channel_id: &Uuid, user_id: &Uuid) {
         if let Some(mut subscribers) = self.channel_subscriptions.get_mut(channel_id) {
             subscribers.remove(user_id);
         }
@@ -105,7 +152,7 @@ impl WebSocketServer {
             let mut no_connection_count = 0;
             let mut send_error_count = 0;
 
-            for user_id in subscribers.iter() {
+            for user_id in subscribers.keys() {
                 if exclude_user.map_or(true, |excluded| excluded != user_id) {
                     if let Some(sender) = self.connections.get(user_id) {
                         match sender.send(json.clone()) {
@@ -157,7 +204,7 @@ impl WebSocketServer {
             .get(channel_id)
             .map(|subscribers| {
                 subscribers
-                    .iter()
+                    .keys()
                     .filter(|user_id| self.connections.contains_key(user_id))
                     .copied()
                     .collect()
@@ -228,6 +275,10 @@ pub async fn ws_handler(
     actix_rt::spawn(async move {
         let mut user_id: Option<Uuid> = None;
         let mut _receiver: Option<broadcast::Receiver<String>> = None;
+        // Identifies this connection in channel subscriptions; the user's other devices have their own
+        let connection_id = Uuid::new_v4();
+        // Channels this connection subscribed to, to unsubscribe from on disconnect
+        let mut subscribed_channels: HashSet<Uuid> = HashSet::new();
 
         while let Some(Ok(msg)) = msg_stream.next().await {
             match msg {
@@ -293,10 +344,20 @@ pub async fn ws_handler(
 
                         Ok(WebSocketMessage::JoinChannel { channel_id }) => {
                             if let Some(uid) = user_id {
+                                // Opening a public channel joins it
+                                if let Err(e) = services.channels.auto_join(&channel_id, &uid).await {
+                                    warn!("Failed to auto-join user {} to channel {}: {}", uid, channel_id, e);
+                                }
+
                                 // Verify user has access to channel
                                 match services.channels.is_member(&channel_id, &uid).await {
                                     Ok(true) => {
-                                        ws_server.subscribe_to_channel(channel_id, uid);
+                                        // The server's subscriptions decide, since removing the user
+                                        // from the channel drops them without this connection knowing
+                                        subscribed_channels.insert(channel_id);
+                                        if !ws_server.subscribe_to_channel(channel_id, uid, connection_id) {
+                                            continue;
+                                        }
                                         info!("User {} subscribed to channel {}", uid, channel_id);
 
                                         // Notify others in channel
@@ -322,7 +383,10 @@ pub async fn ws_handler(
 
                         Ok(WebSocketMessage::LeaveChannel { channel_id }) => {
                             if let Some(uid) = user_id {
-                                ws_server.unsubscribe_from_channel(&channel_id, &uid);
+                                subscribed_channels.remove(&channel_id);
+                                if !ws_server.unsubscribe_from_channel(&channel_id, &uid, &connection_id) {
+                                    continue;
+                                }
 
                                 // Notify others in channel
                                 let msg = WebSocketMessage::UserLeftChannel {
@@ -333,20 +397,75 @@ pub async fn ws_handler(
                             }
                         }
 
-                        Ok(WebSocketMessage::SendMessage { channel_id, content, reply_to_id }) => {
+                        Ok(WebSocketMessage::SendMessage { channel_id, content, reply_to_id, client_nonce }) => {
                             if let Some(uid) = user_id {
                                 // Send message through service
-                                let request = shared::dto::SendMessageRequest {
+                                let request = SendMessageRequest {
                                     content,
                                     message_type: None,
+                                    format: None,
                                     reply_to_id,
                                     attachment_ids: None,
+                                    send_at: None,
+                                    client_nonce: client_nonce.clone(),
+                                    ttl_seconds: None,
                                 };
 
-                                if let Ok(message) = services.messages.send_message(&channel_id, &uid, request).await {
-                                    // Broadcast to channel
-                                    let msg = WebSocketMessage::NewMessage { message };
-                                    ws_server.broadcast_to_channel(&channel_id, &msg, None);
+                                // A retried send gets the message stored the first time
+                                let sent: Result<(MessageResponse, bool), AppError> = async {
+                                    request.validate()?;
+                                    services.messages.send_message_once(&channel_id, &uid, request).await
+                                }
+                                .await;
+
+                                match sent {
+                                    Ok((message, false)) => {
+                                        // Everyone else saw it the first time
+                                        let msg = WebSocketMessage::NewMessage { message, client_nonce };
+                                        if let Ok(json) = serde_json::to_string(&msg) {
+                                            let _ = session.text(json).await;
+                                        }
+                                    }
+                                    Ok((message, true)) => {
+                                        // Broadcast to channel
+                                        crate::handlers::messages::broadcast_new_message(
+                                            &ws_server,
+                                            &channel_id,
+                                            &message,
+                                            client_nonce,
+                                            None,
+                                        );
+
+                                        crate::handlers::drafts::clear_sent_draft(
+                                            &services,
+                                            &ws_server,
+                                            &uid,
+                                            &channel_id,
+                                            reply_to_id.as_ref(),
+                                        )
+                                        .await;
+                                        crate::handlers::messages::spawn_message_notifications(
+                                            services.clone(),
+                                            ws_server.clone(),
+                                            message.clone(),
+                                        );
+                                        crate::handlers::messages::spawn_link_previews(
+                                            services.clone(),
+                                            ws_server.clone(),
+                                            message,
+                                        );
+                                    }
+                                    Err(e) => {
+                                        // Let the sender know why the message was rejected
+                                        warn!("User {} failed to send message to channel {}: {}", uid, channel_id, e);
+                                        let error = WebSocketMessage::Error {
+                                            code: e.error_code().to_string(),
+                                            message: e.to_string(),
+                                        };
+                                        if let Ok(json) = serde_json::to_string(&error) {
+                                            let _ = session.text(json).await;
+                                        }
+                                    }
                                 }
                             }
                         }
@@ -471,33 +590,81 @@ pub async fn ws_handler(
                     let _ = session.pong(&bytes).await;
                 }
 
-                Message::Close(_) => {
-                    if let Some(uid) = user_id {
-                        ws_server.unregister(&uid);
-
-                        // Update user status to offline
-                        let _ = services.users.update_status(&uid, UserStatus::Offline, None).await;
-
-                        // Broadcast status change
-                        let msg = WebSocketMessage::UserStatusChanged {
-                            user_id: uid,
-                            status: UserStatus::Offline,
-                            status_message: None,
-                        };
-                        ws_server.broadcast_to_all(&msg, Some(&uid));
-                    }
-                    break;
-                }
+                Message::Close(_) => break,
 
                 _ => {}
             }
         }
 
-        // Cleanup on disconnect
+        // Cleanup on disconnect; the user stays online while another device is connected
         if let Some(uid) = user_id {
-            ws_server.unregister(&uid);
+            for channel_id in &subscribed_channels {
+                ws_server.unsubscribe_from_channel(channel_id, &uid, &connection_id);
+            }
+
+            if ws_server.unregister(&uid) {
+                // Update user status to offline
+                let _ = services.users.update_status(&uid, UserStatus::Offline, None).await;
+
+                // Broadcast status change
+                let msg = WebSocketMessage::UserStatusChanged {
+                    user_id: uid,
+                    status: UserStatus::Offline,
+                    status_message: None,
+                };
+                ws_server.broadcast_to_all(&msg, Some(&uid));
+            }
         }
     });
 
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_subscriptions_are_counted_per_connection() {
+        let server = WebSocketServer::new();
+        let user_id = Uuid::new_v4();
+        let channel_id = Uuid::new_v4();
+        let (phone, laptop) = (Uuid::new_v4(), Uuid::new_v4());
+        let _phone_rx = server.register(user_id);
+        let _laptop_rx = server.register(user_id);
+
+        assert!(server.subscribe_to_channel(channel_id, user_id, phone));
+        assert!(!server.subscribe_to_channel(channel_id, user_id, laptop));
+        // Joining twice on one connection counts once
+        assert!(!server.subscribe_to_channel(channel_id, user_id, laptop));
+
+        // The laptop leaving keeps the phone subscribed
+        assert!(!server.unsubscribe_from_channel(&channel_id, &user_id, &laptop));
+        assert!(!server.unsubscribe_from_channel(&channel_id, &user_id, &laptop));
+        assert_eq!(server.get_online_users_in_channel(&channel_id), vec![user_id]);
+
+        assert!(server.unsubscribe_from_channel(&channel_id, &user_id, &phone));
+        assert!(server.get_online_users_in_channel(&channel_id).is_empty());
+        assert!(!server.unsubscribe_from_channel(&channel_id, &user_id, &phone));
+    }
+
+    #[test]
+    fn test_removing_a_member_drops_every_subscription() {
+        let server = WebSocketServer::new();
+        let user_id = Uuid::new_v4();
+        let channel_id = Uuid::new_v4();
+        let (phone, laptop) = (Uuid::new_v4(), Uuid::new_v4());
+        let _phone_rx = server.register(user_id);
+        let _laptop_rx = server.register(user_id);
+        server.subscribe_to_channel(channel_id, user_id, phone);
+        server.subscribe_to_channel(channel_id, user_id, laptop);
+
+        server.remove_from_channel(&channel_id, &user_id);
+        assert!(server.get_online_users_in_channel(&channel_id).is_empty());
+        assert!(!server.unsubscribe_from_channel(&channel_id, &user_id, &phone));
+
+        // Rejoining on a connection that was subscribed before subscribes it again
+        assert!(server.subscribe_to_channel(channel_id, user_id, phone));
+        assert_eq!(server.get_online_users_in_channel(&channel_id), vec![user_id]);
+    }
+}
@@ -18,6 +18,7 @@ import type {
   CreateMeetingRequest,
   UpdateMeetingRequest,
   MeetingResponseStatus,
+  Paginated,
 } from '../types';
 
 const API_BASE_URL = '/api/v1';
@@ -216,9 +217,10 @@ class ApiClient {
   }
 
   // Messages
-  async listMessages(channelId: string, limit: number = 50): Promise<Message[]> {
-    const response = await axios.get<Message[]>(
-      `${API_BASE_URL}/channels/${channelId}/messages?limit=${limit}`
+  async listMessages(channelId: string, limit: number = 50, cursor?: string): Promise<Paginated<Message>> {
+    const response = await axios.get<Paginated<Message>>(
+      `${API_BASE_URL}/channels/${channelId}/messages`,
+      { params: { limit, cursor } }
     );
     return response.data;
   }
@@ -300,14 +302,14 @@ class ApiClient {
   }
 
   // Search
-  async searchMessages(query: string): Promise<{ messages: Message[]; total_count: number }> {
+  async searchMessages(query: string): Promise<Paginated<Message>> {
     const response = await axios.get(`${API_BASE_URL}/search/messages?q=${query}`);
     return response.data;
   }
 
   // Notifications
-  async listNotifications(): Promise<Notification[]> {
-    const response = await axios.get<Notification[]>(`${API_BASE_URL}/notifications`);
+  async listNotifications(): Promise<Paginated<Notification>> {
+    const response = await axios.get<Paginated<Notification>>(`${API_BASE_URL}/notifications`);
     return response.data;
   }
 
@@ -53,6 +53,7 @@ export function useChannel(
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
   const [hasMore, setHasMore] = useState(true);
+  const [nextCursor, setNextCursor] = useState<string | null>(null);
 
   // Refs for cleanup and debouncing
   const typingTimeoutRef = useRef<ReturnType<typeof setTimeout>>();
@@ -74,9 +75,10 @@ export function useChannel(
     setError(null);
 
     try {
-      const msgs = await apiClient.listMessages(channelId, initialLimit);
-      setMessages(channelId, msgs);
-      setHasMore(msgs.length === initialLimit);
+      const page = await apiClient.listMessages(channelId, initialLimit);
+      setMessages(channelId, page.items);
+      setHasMore(page.has_more);
+      setNextCursor(page.next_cursor);
     } catch (err: any) {
       setError(err.message || 'Failed to load messages');
     } finally {
@@ -86,23 +88,21 @@ export function useChannel(
 
   // Load older messages (pagination)
   const loadMoreMessages = useCallback(async () => {
-    if (!channelId || !hasMore || loading) return;
-
-    const oldestMessage = messages[0];
-    if (!oldestMessage) return;
+    if (!channelId || !hasMore || !nextCursor || loading) return;
 
     setLoading(true);
     try {
-      const olderMsgs = await apiClient.listMessages(channelId, initialLimit);
+      const page = await apiClient.listMessages(channelId, initialLimit, nextCursor);
       // Prepend older messages
-      setMessages(channelId, [...olderMsgs, ...messages]);
-      setHasMore(olderMsgs.length === initialLimit);
+      setMessages(channelId, [...page.items, ...messages]);
+      setHasMore(page.has_more);
+      setNextCursor(page.next_cursor);
     } catch (err: any) {
       setError(err.message || 'Failed to load more messages');
     } finally {
       setLoading(false);
     }
-  }, [channelId, hasMore, loading, messages, initialLimit, setMessages]);
+  }, [channelId, hasMore, nextCursor, loading, messages, initialLimit, setMessages]);
 
   // Send message with optimistic update
   const sendMessage = useCallback(
@@ -137,6 +137,7 @@ export function useChannel(
         // Send to server
         const message = await apiClient.sendMessage(channelId, {
           content,
+          client_nonce: crypto.randomUUID(),
           ...options,
         });
 
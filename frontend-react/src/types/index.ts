@@ -49,6 +49,19 @@ export interface Message {
   edited: boolean;
   created_at: string;
   updated_at: string;
+  /** Set on self-destructing messages */
+  expires_at?: string | null;
+}
+
+/** A system message shown only to the current user, never stored */
+export interface EphemeralMessage {
+  id: string;
+  channel_id: string;
+  /** Null for notices from the server */
+  sender_id: string | null;
+  content: string;
+  format: string;
+  created_at: string;
 }
 
 export type MessageType = 'text' | 'image' | 'file' | 'system';
@@ -171,6 +184,10 @@ export interface SendMessageRequest {
   message_type?: MessageType;
   reply_to_id?: string;
   attachment_ids?: string[];
+  /** Retrying a send with the same nonce does not post the message twice */
+  client_nonce?: string;
+  /** Purge the message this many seconds after sending it */
+  ttl_seconds?: number;
 }
 
 export interface UpdateUserRequest {
@@ -223,13 +240,20 @@ export interface CalendarQuery {
   end_date: string;
 }
 
+/** A page of a list; pass `next_cursor` back to fetch the following page */
+export interface Paginated<T> {
+  items: T[];
+  has_more: boolean;
+  next_cursor: string | null;
+}
+
 // WebSocket message types
 export type WebSocketMessage =
   // Client -> Server
   | { type: 'Authenticate'; payload: { token: string } }
   | { type: 'JoinChannel'; payload: { channel_id: string } }
   | { type: 'LeaveChannel'; payload: { channel_id: string } }
-  | { type: 'SendMessage'; payload: { channel_id: string; content: string; reply_to_id?: string } }
+  | { type: 'SendMessage'; payload: { channel_id: string; content: string; reply_to_id?: string; client_nonce?: string } }
   | { type: 'StartTyping'; payload: { channel_id: string } }
   | { type: 'StopTyping'; payload: { channel_id: string } }
   | { type: 'UpdateStatus'; payload: { status: UserStatus; status_message?: string } }
@@ -238,9 +262,10 @@ export type WebSocketMessage =
   // Server -> Client
   | { type: 'Authenticated'; payload: { user: User } }
   | { type: 'Error'; payload: { code: string; message: string } }
-  | { type: 'NewMessage'; payload: { message: Message } }
+  | { type: 'NewMessage'; payload: { message: Message; client_nonce?: string | null } }
   | { type: 'MessageUpdated'; payload: { message: Message } }
-  | { type: 'MessageDeleted'; payload: { channel_id: string; message_id: string } }
+  | { type: 'MessageDeleted'; payload: { channel_id: string; message_id: string; moderated: boolean } }
+  | { type: 'EphemeralMessage'; payload: { message: EphemeralMessage } }
   | { type: 'UserTyping'; payload: { channel_id: string; user: User } }
   | { type: 'UserStoppedTyping'; payload: { channel_id: string; user_id: string } }
   | { type: 'UserStatusChanged'; payload: { user_id: string; status: UserStatus; status_message?: string } }
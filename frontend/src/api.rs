@@ -144,6 +144,20 @@ impl ApiClient {
         Self::request("GET", &format!("/teams/{}/members", team_id), None::<()>, true).await
     }
 
+    // Custom emoji
+    pub async fn list_team_emoji(team_id: &str) -> Result<Vec<CustomEmojiResponse>, String> {
+        Self::request("GET", &format!("/teams/{}/emoji", team_id), None::<()>, true).await
+    }
+
+    pub async fn create_team_emoji(team_id: &str, request: CreateCustomEmojiRequest) -> Result<CustomEmojiResponse, String> {
+        Self::request("POST", &format!("/teams/{}/emoji", team_id), Some(request), true).await
+    }
+
+    pub async fn delete_team_emoji(team_id: &str, emoji_id: &str) -> Result<(), String> {
+        Self::request::<serde_json::Value>("DELETE", &format!("/teams/{}/emoji/{}", team_id, emoji_id), None::<()>, true).await?;
+        Ok(())
+    }
+
     // Channels
     pub async fn list_channels() -> Result<Vec<ChannelResponse>, String> {
         Self::request("GET", "/channels", None::<()>, true).await
@@ -171,9 +185,17 @@ impl ApiClient {
     }
 
     // Messages
-    pub async fn list_messages(channel_id: &str, limit: Option<i64>) -> Result<Vec<MessageResponse>, String> {
+    pub async fn list_messages(
+        channel_id: &str,
+        limit: Option<i64>,
+        cursor: Option<&str>,
+    ) -> Result<PaginatedResponse<MessageResponse>, String> {
         let limit = limit.unwrap_or(50);
-        Self::request("GET", &format!("/channels/{}/messages?limit={}", channel_id, limit), None::<()>, true).await
+        let mut path = format!("/channels/{}/messages?limit={}", channel_id, limit);
+        if let Some(cursor) = cursor {
+            path.push_str(&format!("&cursor={}", cursor));
+        }
+        Self::request("GET", &path, None::<()>, true).await
     }
 
     pub async fn send_message(channel_id: &str, request: SendMessageRequest) -> Result<MessageResponse, String> {
@@ -200,6 +222,24 @@ impl ApiClient {
         Ok(())
     }
 
+    pub async fn forward_message(channel_id: &str, message_id: &str, request: ForwardMessageRequest) -> Result<MessageResponse, String> {
+        Self::request("POST", &format!("/channels/{}/messages/{}/forward", channel_id, message_id), Some(request), true).await
+    }
+
+    // Polls
+    pub async fn create_poll(channel_id: &str, request: CreatePollRequest) -> Result<MessageResponse, String> {
+        Self::request("POST", &format!("/channels/{}/polls", channel_id), Some(request), true).await
+    }
+
+    pub async fn vote_poll(channel_id: &str, message_id: &str, option_id: uuid::Uuid) -> Result<PollResponse, String> {
+        let request = PollVoteRequest { option_id };
+        Self::request("POST", &format!("/channels/{}/messages/{}/poll/votes", channel_id, message_id), Some(request), true).await
+    }
+
+    pub async fn retract_poll_vote(channel_id: &str, message_id: &str, option_id: uuid::Uuid) -> Result<PollResponse, String> {
+        Self::request("DELETE", &format!("/channels/{}/messages/{}/poll/votes/{}", channel_id, message_id, option_id), None::<()>, true).await
+    }
+
     // Calls
     pub async fn start_call(request: StartCallRequest) -> Result<CallResponse, String> {
         Self::request("POST", "/calls", Some(request), true).await
@@ -220,12 +260,12 @@ impl ApiClient {
     }
 
     // Search
-    pub async fn search_messages(query: &str) -> Result<SearchResponse, String> {
+    pub async fn search_messages(query: &str) -> Result<PaginatedResponse<MessageResponse>, String> {
         Self::request("GET", &format!("/search/messages?q={}", query), None::<()>, true).await
     }
 
     // Notifications
-    pub async fn list_notifications() -> Result<Vec<NotificationResponse>, String> {
+    pub async fn list_notifications() -> Result<PaginatedResponse<NotificationResponse>, String> {
         Self::request("GET", "/notifications", None::<()>, true).await
     }
 
@@ -3,7 +3,9 @@
 use dioxus::prelude::*;
 
 use crate::components::Avatar;
-use shared::dto::MessageResponse;
+use shared::dto::{ForwardedMessageResponse, LinkPreviewResponse, MessageResponse, PollResponse};
+use shared::markdown::{self, Block, Inline};
+use shared::models::{MentionType, MessageFormat};
 
 #[derive(Props, Clone, PartialEq)]
 pub struct MessageProps {
@@ -16,6 +18,10 @@ pub struct MessageProps {
     pub on_reply: Option<EventHandler<String>>,
     #[props(default)]
     pub on_react: Option<EventHandler<(String, String)>>,
+    /// Called with the message and option ids when a poll option is clicked,
+    /// and whether the user had already voted for it
+    #[props(default)]
+    pub on_vote: Option<EventHandler<(String, String, bool)>>,
 }
 
 #[component]
@@ -56,7 +62,12 @@ pub fn MessageComponent(props: MessageProps) -> Element {
                             class: "text-xs text-gray-500",
                             "{formatted_time}"
                         }
-                        if message.edited {
+                        if message.edited && message.moderated {
+                            span {
+                                class: "text-xs text-gray-400",
+                                "(edited by moderator)"
+                            }
+                        } else if message.edited {
                             span {
                                 class: "text-xs text-gray-400",
                                 "(edited)"
@@ -72,7 +83,34 @@ pub fn MessageComponent(props: MessageProps) -> Element {
                     } else {
                         "inline-block bg-gray-100 rounded-lg px-4 py-2 max-w-md"
                     },
-                    p { class: "whitespace-pre-wrap break-words", "{message.content}" }
+                    if message.deleted_at.is_some() && message.moderated {
+                        p { class: "italic opacity-75", "removed by moderator" }
+                    } else if message.deleted_at.is_some() {
+                        p { class: "italic opacity-75", "message deleted" }
+                    } else if let Some(poll) = &message.poll {
+                        PollView {
+                            poll: poll.clone(),
+                            on_toggle: {
+                                let msg_id = message.id.to_string();
+                                let handler = props.on_vote.clone();
+                                move |(option_id, voted): (uuid::Uuid, bool)| {
+                                    if let Some(h) = &handler {
+                                        h.call((msg_id.clone(), option_id.to_string(), voted));
+                                    }
+                                }
+                            },
+                        }
+                    } else {
+                        MessageBody {
+                            content: message.content.clone(),
+                            format: message.format,
+                        }
+                    }
+                }
+
+                // Forwarded original
+                if let Some(forwarded) = &message.forwarded {
+                    ForwardedEmbed { forwarded: forwarded.clone() }
                 }
 
                 // Attachments
@@ -94,6 +132,11 @@ pub fn MessageComponent(props: MessageProps) -> Element {
                     }
                 }
 
+                // Link previews
+                for preview in message.link_previews.iter() {
+                    LinkPreviewCard { key: "{preview.url}", preview: preview.clone() }
+                }
+
                 // Reactions
                 if !message.reactions.is_empty() {
                     div {
@@ -150,6 +193,217 @@ pub fn MessageComponent(props: MessageProps) -> Element {
     }
 }
 
+/// The original of a forwarded message, or a placeholder for viewers who can't see it
+#[component]
+pub fn ForwardedEmbed(forwarded: ForwardedMessageResponse) -> Element {
+    let sender_name = forwarded.sender.as_ref().map(|s| s.display_name.clone()).unwrap_or_default();
+    let time = forwarded.created_at.map(|t| t.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default();
+
+    rsx! {
+        div {
+            class: "mt-2 max-w-md border-l-4 border-gray-300 bg-white rounded p-2 text-left text-gray-800",
+            p { class: "text-xs text-gray-500 mb-1", "Forwarded" }
+            match (forwarded.accessible, forwarded.content.clone()) {
+                (true, Some(content)) => rsx! {
+                    div {
+                        class: "flex items-baseline space-x-2 text-sm",
+                        span { class: "font-semibold", "{sender_name}" }
+                        span { class: "text-xs text-gray-500", "{time}" }
+                    }
+                    MessageBody { content: content, format: forwarded.format.unwrap_or_default() }
+                },
+                _ => rsx! {
+                    p { class: "italic text-sm text-gray-400", "original message unavailable" }
+                },
+            }
+        }
+    }
+}
+
+/// A poll's question and tallies. Clicking an option toggles the user's vote;
+/// `on_toggle` gets the option and whether it was already selected.
+#[component]
+pub fn PollView(poll: PollResponse, on_toggle: EventHandler<(uuid::Uuid, bool)>) -> Element {
+    let total_votes: i64 = poll.options.iter().map(|o| o.vote_count).sum();
+    let summary = match (poll.closed, poll.allow_multiple) {
+        (true, _) => "Poll closed",
+        (false, true) => "Choose any number",
+        (false, false) => "Choose one",
+    };
+
+    rsx! {
+        div {
+            class: "text-left",
+            p { class: "font-semibold mb-2", "{poll.question}" }
+            for option in poll.options.iter() {
+                {
+                    let percent = if total_votes > 0 { option.vote_count * 100 / total_votes } else { 0 };
+                    let selected = poll.my_votes.contains(&option.id);
+                    let option_id = option.id;
+                    rsx! {
+                        button {
+                            key: "{option.id}",
+                            class: if selected {
+                                "relative block w-full mb-1 px-3 py-1 rounded border border-blue-600 text-left text-gray-800 bg-white"
+                            } else {
+                                "relative block w-full mb-1 px-3 py-1 rounded border border-gray-300 text-left text-gray-800 bg-white"
+                            },
+                            disabled: poll.closed,
+                            onclick: move |_| on_toggle.call((option_id, selected)),
+                            div {
+                                class: "absolute inset-y-0 left-0 bg-blue-100 rounded",
+                                style: "width: {percent}%",
+                            }
+                            span { class: "relative", "{option.text}" }
+                            span { class: "relative float-right text-sm text-gray-500", "{option.vote_count}" }
+                        }
+                    }
+                }
+            }
+            p {
+                class: "text-xs opacity-75 mt-1",
+                "{summary} · {poll.total_voters} voters"
+                if poll.anonymous { " · anonymous" }
+            }
+        }
+    }
+}
+
+/// Title, description and image of a link in a message
+#[component]
+pub fn LinkPreviewCard(preview: LinkPreviewResponse) -> Element {
+    rsx! {
+        a {
+            href: "{preview.url}",
+            target: "_blank",
+            rel: "noopener noreferrer",
+            class: "mt-2 flex max-w-md border-l-4 border-gray-300 bg-white rounded p-2 text-left hover:bg-gray-50",
+            div {
+                class: "flex-1 min-w-0",
+                if let Some(site_name) = &preview.site_name {
+                    p { class: "text-xs text-gray-500", "{site_name}" }
+                }
+                p { class: "font-semibold text-sm text-blue-600 truncate", "{preview.title}" }
+                if let Some(description) = &preview.description {
+                    p { class: "text-sm text-gray-600 line-clamp-3", "{description}" }
+                }
+            }
+            if let Some(image_url) = &preview.image_url {
+                img {
+                    src: "{image_url}",
+                    alt: "",
+                    class: "ml-2 w-16 h-16 object-cover rounded",
+                }
+            }
+        }
+    }
+}
+
+/// Message content, rendered according to its format
+#[component]
+pub fn MessageBody(content: String, format: MessageFormat) -> Element {
+    match format {
+        MessageFormat::Plain => rsx! {
+            p { class: "whitespace-pre-wrap break-words", "{content}" }
+        },
+        MessageFormat::Markdown => {
+            let blocks = markdown::parse(&content);
+            rsx! {
+                div {
+                    class: "space-y-1 break-words",
+                    for block in blocks.iter() {
+                        {render_block(block)}
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn render_block(block: &Block) -> Element {
+    match block {
+        Block::Paragraph { content } => rsx! {
+            p { class: "whitespace-pre-wrap", {render_inlines(content)} }
+        },
+        Block::CodeBlock { language, code } => {
+            let language_class = language
+                .as_ref()
+                .map(|l| format!("language-{}", l))
+                .unwrap_or_default();
+            rsx! {
+                pre {
+                    class: "bg-gray-800 text-gray-100 rounded p-2 overflow-x-auto text-sm",
+                    code { class: "{language_class}", "{code}" }
+                }
+            }
+        }
+        Block::Quote { content } => rsx! {
+            blockquote {
+                class: "border-l-4 border-gray-300 pl-2 text-gray-600",
+                for block in content.iter() {
+                    {render_block(block)}
+                }
+            }
+        },
+        Block::List { ordered: true, items } => rsx! {
+            ol {
+                class: "list-decimal pl-5",
+                for item in items.iter() {
+                    li { {render_inlines(item)} }
+                }
+            }
+        },
+        Block::List { ordered: false, items } => rsx! {
+            ul {
+                class: "list-disc pl-5",
+                for item in items.iter() {
+                    li { {render_inlines(item)} }
+                }
+            }
+        },
+    }
+}
+
+fn render_inlines(nodes: &[Inline]) -> Element {
+    rsx! {
+        for node in nodes.iter() {
+            {render_inline(node)}
+        }
+    }
+}
+
+fn render_inline(node: &Inline) -> Element {
+    match node {
+        Inline::Text { text } => rsx! { "{text}" },
+        Inline::Bold { content } => rsx! { strong { {render_inlines(content)} } },
+        Inline::Italic { content } => rsx! { em { {render_inlines(content)} } },
+        Inline::Code { code } => rsx! {
+            code { class: "bg-gray-200 text-gray-800 rounded px-1 font-mono text-sm", "{code}" }
+        },
+        Inline::Link { url, content } => rsx! {
+            a {
+                href: "{url}",
+                target: "_blank",
+                rel: "noopener noreferrer",
+                class: "underline",
+                {render_inlines(content)}
+            }
+        },
+        Inline::Mention { mention_type, username, .. } => {
+            let label = match mention_type {
+                MentionType::User => format!("@{}", username.as_deref().unwrap_or_default()),
+                MentionType::Channel => "@channel".to_string(),
+                MentionType::Here => "@here".to_string(),
+            };
+            rsx! { span { class: "font-semibold text-blue-600", "{label}" } }
+        }
+        Inline::ChannelRef { name } => rsx! {
+            span { class: "font-semibold text-blue-600", "#{name}" }
+        },
+        Inline::LineBreak => rsx! { br {} },
+    }
+}
+
 #[derive(Props, Clone, PartialEq)]
 pub struct MessageListProps {
     pub messages: Vec<MessageResponse>,
@@ -159,6 +413,8 @@ pub struct MessageListProps {
     pub on_reply: Option<EventHandler<String>>,
     #[props(default)]
     pub on_react: Option<EventHandler<(String, String)>>,
+    #[props(default)]
+    pub on_vote: Option<EventHandler<(String, String, bool)>>,
 }
 
 #[component]
@@ -185,6 +441,7 @@ pub fn MessageList(props: MessageListProps) -> Element {
                             show_avatar: show_avatar,
                             on_reply: props.on_reply.clone(),
                             on_react: props.on_react.clone(),
+                            on_vote: props.on_vote.clone(),
                         }
                     }
                 }
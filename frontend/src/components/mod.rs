@@ -12,7 +12,7 @@ mod user_status;
 pub use avatar::Avatar;
 pub use button::Button;
 pub use input::Input;
-pub use message::{MessageComponent, MessageList};
+pub use message::{ForwardedEmbed, LinkPreviewCard, MessageBody, MessageComponent, MessageList, PollView};
 pub use message_input::MessageInput;
 pub use modal::{AlertModal, ConfirmModal, Modal};
 pub use sidebar::Sidebar;
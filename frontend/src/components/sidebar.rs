@@ -105,7 +105,7 @@ pub fn Sidebar(props: SidebarProps) -> Element {
                                 "+"
                             }
                         }
-                        for channel in props.channels.iter() {
+                        for channel in props.channels.iter().filter(|c| !c.hidden) {
                             button {
                                 key: "{channel.id}",
                                 class: if props.selected_channel_id.as_ref() == Some(&channel.id.to_string()) {
@@ -124,6 +124,12 @@ pub fn Sidebar(props: SidebarProps) -> Element {
                                 },
                                 span { class: "text-gray-400", "#" }
                                 span { class: "truncate", "{channel.name}" }
+                                if channel.mention_count > 0 {
+                                    span {
+                                        class: "ml-auto bg-red-600 text-white text-xs rounded-full px-2",
+                                        "{channel.mention_count}"
+                                    }
+                                }
                             }
                         }
                     }
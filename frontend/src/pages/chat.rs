@@ -2,9 +2,10 @@
 
 use dioxus::prelude::*;
 use shared::dto::SendMessageRequest;
+use shared::models::MessageFormat;
 
 use crate::api::ApiClient;
-use crate::components::{Avatar, Button};
+use crate::components::{Avatar, Button, ForwardedEmbed, LinkPreviewCard, MessageBody, PollView};
 use crate::state::AppState;
 use crate::Route;
 
@@ -36,9 +37,9 @@ pub fn ChatPage(props: ChatPageProps) -> Element {
     use_effect(move || {
         if let Some(cid) = channel_id.clone() {
             spawn(async move {
-                if let Ok(messages) = ApiClient::list_messages(&cid, Some(50)).await {
+                if let Ok(page) = ApiClient::list_messages(&cid, Some(50), None).await {
                     if let Ok(uuid) = uuid::Uuid::parse_str(&cid) {
-                        state.write().set_messages(uuid, messages);
+                        state.write().set_messages(uuid, page.items);
                     }
                 }
             });
@@ -58,8 +59,12 @@ pub fn ChatPage(props: ChatPageProps) -> Element {
                 let request = SendMessageRequest {
                     content,
                     message_type: None,
+                    format: Some(MessageFormat::Markdown),
                     reply_to_id: None,
                     attachment_ids: None,
+                    send_at: None,
+                    client_nonce: Some(uuid::Uuid::new_v4().to_string()),
+                    ttl_seconds: None,
                 };
 
                 if let Ok(message) = ApiClient::send_message(&cid, request).await {
@@ -73,6 +78,23 @@ pub fn ChatPage(props: ChatPageProps) -> Element {
         }
     };
 
+    let channel_id_for_vote = props.channel_id.clone();
+    let toggle_vote = move |(message_id, option_id, voted): (uuid::Uuid, uuid::Uuid, bool)| {
+        if let Some(cid) = channel_id_for_vote.clone() {
+            spawn(async move {
+                let mid = message_id.to_string();
+                let result = if voted {
+                    ApiClient::retract_poll_vote(&cid, &mid, option_id).await
+                } else {
+                    ApiClient::vote_poll(&cid, &mid, option_id).await
+                };
+                if let (Ok(poll), Ok(uuid)) = (result, uuid::Uuid::parse_str(&cid)) {
+                    state.write().set_poll(uuid, message_id, poll);
+                }
+            });
+        }
+    };
+
     let current_user = state.read().current_user.clone();
     let channels = state.read().channels.clone();
     let selected_channel = props.channel_id.as_ref().and_then(|cid| {
@@ -89,6 +111,13 @@ pub fn ChatPage(props: ChatPageProps) -> Element {
             let sender_name = msg.sender.display_name.clone();
             let sender_avatar = msg.sender.avatar_url.clone().unwrap_or_default();
             let content = msg.content.clone();
+            let format = msg.format;
+            let link_previews = msg.link_previews.clone();
+            let poll = msg.poll.clone();
+            let forwarded = msg.forwarded.clone();
+            let message_id = msg.id;
+            let deleted = msg.deleted_at.is_some();
+            let tombstone = if msg.moderated { "removed by moderator" } else { "message deleted" };
             let time = msg.created_at.format("%H:%M").to_string();
             rsx! {
                 div {
@@ -104,7 +133,28 @@ pub fn ChatPage(props: ChatPageProps) -> Element {
                             span { class: "font-semibold", "{sender_name}" }
                             span { class: "text-xs text-gray-500", "{time}" }
                         }
-                        p { class: "text-gray-800", "{content}" }
+                        div {
+                            class: "text-gray-800",
+                            if deleted {
+                                p { class: "italic text-gray-400", "{tombstone}" }
+                            } else if let Some(poll) = poll {
+                                PollView {
+                                    poll: poll,
+                                    on_toggle: {
+                                        let toggle_vote = toggle_vote.clone();
+                                        move |(option_id, voted)| toggle_vote((message_id, option_id, voted))
+                                    },
+                                }
+                            } else {
+                                MessageBody { content: content, format: format }
+                                if let Some(forwarded) = forwarded {
+                                    ForwardedEmbed { forwarded: forwarded }
+                                }
+                                for preview in link_previews {
+                                    LinkPreviewCard { key: "{preview.url}", preview: preview }
+                                }
+                            }
+                        }
                     }
                 }
             }
@@ -112,7 +162,7 @@ pub fn ChatPage(props: ChatPageProps) -> Element {
     });
 
     // Pre-render channel links
-    let channel_links: Vec<_> = channels.iter().map(|channel| {
+    let channel_links: Vec<_> = channels.iter().filter(|channel| !channel.hidden).map(|channel| {
         let channel_id = channel.id.to_string();
         let channel_name = channel.name.clone();
         rsx! {
@@ -207,8 +257,14 @@ pub fn ChatPage(props: ChatPageProps) -> Element {
                 }
 
                 // Message input
-                match selected_channel.is_some() {
-                    true => rsx! {
+                match &selected_channel {
+                    Some(channel) if channel.is_archived => rsx! {
+                        div {
+                            class: "p-4 bg-gray-50 border-t text-center text-gray-500",
+                            "This channel is archived and read-only"
+                        }
+                    },
+                    Some(_) => rsx! {
                         div {
                             class: "p-4 bg-white border-t",
                             form {
@@ -231,7 +287,7 @@ pub fn ChatPage(props: ChatPageProps) -> Element {
                             }
                         }
                     },
-                    false => rsx! { div {} }
+                    None => rsx! { div {} }
                 }
             }
         }
@@ -1,7 +1,7 @@
 //! Application state management
 
 use gloo_storage::{LocalStorage, Storage};
-use shared::dto::{ChannelResponse, MessageResponse, TeamResponse, UserResponse};
+use shared::dto::{ChannelResponse, MessageResponse, PollResponse, TeamResponse, UserResponse};
 use shared::models::UserStatus;
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -105,6 +105,14 @@ impl AppState {
         }
     }
 
+    pub fn set_poll(&mut self, channel_id: Uuid, message_id: Uuid, poll: PollResponse) {
+        if let Some(messages) = self.messages.get_mut(&channel_id) {
+            if let Some(message) = messages.iter_mut().find(|m| m.id == message_id) {
+                message.poll = Some(poll);
+            }
+        }
+    }
+
     pub fn delete_message(&mut self, channel_id: Uuid, message_id: Uuid) {
         if let Some(messages) = self.messages.get_mut(&channel_id) {
             messages.retain(|m| m.id != message_id);
@@ -149,11 +149,13 @@ impl WebSocketClient {
         channel_id: uuid::Uuid,
         content: String,
         reply_to_id: Option<uuid::Uuid>,
+        client_nonce: String,
     ) -> Result<(), String> {
         self.send(&WebSocketMessage::SendMessage {
             channel_id,
             content,
             reply_to_id,
+            client_nonce: Some(client_nonce),
         })
     }
 
@@ -3,13 +3,14 @@
 //! These structures are used for serializing/deserializing API payloads.
 
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::models::{
-    CallStatus, CallType, ChannelType, MessageType, MeetingResponseStatus, MeetingStatus,
-    RecurrenceType, TeamRole, UserStatus,
+    CallStatus, CallType, ChannelType, ContentRuleAction, ContentRuleKind, HistoryVisibility,
+    MentionType, MessageFormat, MessageType, MeetingResponseStatus, MeetingStatus, ModerationAction,
+    NotificationLevel, RecurrenceType, ScheduledMessageStatus, TeamRole, UserStatus,
 };
 
 // ============================================================================
@@ -83,6 +84,17 @@ pub struct ChangePasswordRequest {
     pub new_password: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PrivacySettingsResponse {
+    /// Whether other members can see when this user has read their messages
+    pub read_receipts_enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UpdatePrivacySettingsRequest {
+    pub read_receipts_enabled: Option<bool>,
+}
+
 // ============================================================================
 // Team DTOs
 // ============================================================================
@@ -158,8 +170,28 @@ pub struct ChannelResponse {
     pub name: String,
     pub description: Option<String>,
     pub channel_type: ChannelType,
+    pub is_archived: bool,
+    pub archived_at: Option<DateTime<Utc>>,
+    /// Only team admins and designated posters may send messages
+    pub is_announcement: bool,
+    /// Minimum seconds between messages from the same member, 0 when disabled
+    pub slow_mode_seconds: i32,
+    /// Maximum messages per member per minute, 0 when unlimited
+    pub burst_limit: i32,
+    pub history_visibility: HistoryVisibility,
+    /// Whether the requesting user is a member of the channel
+    pub is_member: bool,
+    /// Shared with other teams besides the host team in `team_id`
+    pub is_shared: bool,
+    /// The requesting member's notification preferences for this channel
+    pub notify_level: NotificationLevel,
+    pub muted_until: Option<DateTime<Utc>>,
+    pub hidden: bool,
     pub member_count: i64,
     pub unread_count: i64,
+    /// Unread messages that mention the user directly or via @channel/@here
+    pub mention_count: i64,
+    pub first_unread_message_id: Option<Uuid>,
     pub last_message: Option<MessageResponse>,
     pub created_at: DateTime<Utc>,
 }
@@ -170,6 +202,52 @@ pub struct UpdateChannelRequest {
     pub name: Option<String>,
     #[validate(length(max = 500, message = "Description must be at most 500 characters"))]
     pub description: Option<String>,
+    pub is_announcement: Option<bool>,
+    #[validate(range(min = 0, max = 21600, message = "Slow mode must be between 0 and 21600 seconds"))]
+    pub slow_mode_seconds: Option<i32>,
+    #[validate(range(min = 0, max = 1000, message = "Burst limit must be between 0 and 1000 messages per minute"))]
+    pub burst_limit: Option<i32>,
+    /// Convert between public and private; team owners and admins only
+    pub channel_type: Option<ChannelType>,
+    pub history_visibility: Option<HistoryVisibility>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShareChannelRequest {
+    pub team_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChannelTeamResponse {
+    pub team_id: Uuid,
+    pub team_name: String,
+    pub invited_by: Uuid,
+    pub invited_at: DateTime<Utc>,
+    /// `None` while the invitation is pending
+    pub accepted_at: Option<DateTime<Utc>>,
+}
+
+/// Update the requesting member's own settings for a channel
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UpdateChannelMemberSettingsRequest {
+    pub notify_level: Option<NotificationLevel>,
+    /// `null` unmutes the channel; omit to leave unchanged
+    #[serde(
+        default,
+        deserialize_with = "deserialize_nullable",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub muted_until: Option<Option<DateTime<Utc>>>,
+    pub hidden: Option<bool>,
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from a missing field (`None`)
+fn deserialize_nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -178,6 +256,29 @@ pub struct ChannelMemberResponse {
     pub user: UserResponse,
     pub joined_at: DateTime<Utc>,
     pub last_read_at: Option<DateTime<Utc>>,
+    /// Designated poster in an announcement channel
+    pub can_post: bool,
+}
+
+// ============================================================================
+// Retention DTOs
+// ============================================================================
+
+/// Retention is set per team and overridden per channel. Channels without a policy
+/// follow their team, and teams without one follow the server default.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RetentionPolicyResponse {
+    /// Days to keep messages, 0 for forever, or null to inherit
+    pub retention_days: Option<i32>,
+    /// Days messages are kept after inheritance, or null when they are kept forever
+    pub effective_retention_days: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq, Default)]
+pub struct UpdateRetentionPolicyRequest {
+    /// Days to keep messages, 0 for forever, or null to inherit
+    #[validate(range(min = 0, max = 36500, message = "Retention must be between 0 and 36500 days"))]
+    pub retention_days: Option<i32>,
 }
 
 // ============================================================================
@@ -189,8 +290,96 @@ pub struct SendMessageRequest {
     #[validate(length(min = 1, max = 10000, message = "Message must be 1-10000 characters"))]
     pub content: String,
     pub message_type: Option<MessageType>,
+    pub format: Option<MessageFormat>,
     pub reply_to_id: Option<Uuid>,
     pub attachment_ids: Option<Vec<Uuid>>,
+    /// Schedule the message to be posted later instead of now
+    pub send_at: Option<DateTime<Utc>>,
+    /// Client generated id of the send. Retrying with the same nonce returns the
+    /// message stored the first time instead of posting it again.
+    #[validate(length(min = 1, max = 64, message = "Client nonce must be 1-64 characters"))]
+    pub client_nonce: Option<String>,
+    /// Make the message self-destruct: its content and attachments are purged
+    /// this many seconds after it is sent
+    #[validate(range(min = 10, max = 604800, message = "Time to live must be between 10 seconds and 7 days"))]
+    pub ttl_seconds: Option<i64>,
+}
+
+/// An "only visible to you" message for one member of the channel, such as the
+/// reply to a slash command
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq)]
+pub struct SendEphemeralMessageRequest {
+    pub user_id: Uuid,
+    #[validate(length(min = 1, max = 10000, message = "Message must be 1-10000 characters"))]
+    pub content: String,
+    pub format: Option<MessageFormat>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq)]
+pub struct ForwardMessageRequest {
+    pub target_channel_id: Uuid,
+    /// Optional text posted along with the forwarded message
+    #[validate(length(max = 10000, message = "Comment must be at most 10000 characters"))]
+    pub comment: Option<String>,
+    pub format: Option<MessageFormat>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq, Default)]
+pub struct CreatePollRequest {
+    #[validate(length(min = 1, max = 500, message = "Question must be 1-500 characters"))]
+    pub question: String,
+    #[validate(length(min = 2, max = 10, message = "A poll needs 2-10 options"))]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub allow_multiple: bool,
+    #[serde(default)]
+    pub anonymous: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PollVoteRequest {
+    pub option_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DraftResponse {
+    pub channel_id: Uuid,
+    pub thread_root_id: Option<Uuid>,
+    /// Empty once the draft has been cleared
+    pub content: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq, Default)]
+pub struct SaveDraftRequest {
+    /// Saving empty content clears the draft
+    #[validate(length(max = 10000, message = "Draft must be at most 10000 characters"))]
+    pub content: String,
+    pub thread_root_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduledMessageResponse {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub content: String,
+    pub message_type: MessageType,
+    pub format: MessageFormat,
+    pub reply_to_id: Option<Uuid>,
+    pub attachment_ids: Vec<Uuid>,
+    pub send_at: DateTime<Utc>,
+    pub status: ScheduledMessageStatus,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq, Default)]
+pub struct UpdateScheduledMessageRequest {
+    #[validate(length(min = 1, max = 10000, message = "Message must be 1-10000 characters"))]
+    pub content: Option<String>,
+    pub send_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -200,18 +389,211 @@ pub struct MessageResponse {
     pub sender: UserResponse,
     pub content: String,
     pub message_type: MessageType,
+    pub format: MessageFormat,
     pub reply_to: Option<Box<MessageResponse>>,
     pub reactions: Vec<ReactionResponse>,
     pub attachments: Vec<FileAttachmentResponse>,
+    pub mentions: Vec<MentionResponse>,
+    pub link_previews: Vec<LinkPreviewResponse>,
+    /// Set on poll messages
+    pub poll: Option<PollResponse>,
+    /// Set on messages forwarded from another message
+    pub forwarded: Option<ForwardedMessageResponse>,
     pub edited: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Set on deleted messages, which are returned as tombstones without content
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Whether a moderator edited or removed the message
+    pub moderated: bool,
+    /// Set on self-destructing messages: when they will be purged
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl MessageResponse {
+    /// The message as any member of its channel may see it, for broadcasts. A
+    /// forwarded original is collapsed, since not every member can access its
+    /// channel, and the reply parent's content is left out, since it may predate
+    /// a member's visible history; clients refetch the message to expand them.
+    /// Fields that describe the acting user, such as their votes and reactions,
+    /// are cleared.
+    pub fn for_broadcast(mut self) -> Self {
+        if let Some(forwarded) = self.forwarded.as_mut() {
+            *forwarded = ForwardedMessageResponse::collapsed();
+        }
+        if let Some(parent) = self.reply_to.as_mut() {
+            parent.content.clear();
+        }
+        if let Some(poll) = self.poll.as_mut() {
+            poll.my_votes.clear();
+        }
+        for reaction in &mut self.reactions {
+            reaction.reacted_by_me = false;
+        }
+        self
+    }
+}
+
+/// A system message shown to a single user in a channel. It is not stored, so it
+/// is gone once the client reloads the channel.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EphemeralMessageResponse {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    /// The user who sent it, or none for notices from the server
+    pub sender_id: Option<Uuid>,
+    pub content: String,
+    pub format: MessageFormat,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The original of a forwarded message. Only filled in for viewers who can
+/// access the source channel.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ForwardedMessageResponse {
+    pub accessible: bool,
+    pub message_id: Option<Uuid>,
+    pub channel_id: Option<Uuid>,
+    pub sender: Option<UserResponse>,
+    pub content: Option<String>,
+    pub format: Option<MessageFormat>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl ForwardedMessageResponse {
+    /// A forward whose original the viewer cannot see
+    pub fn collapsed() -> Self {
+        Self {
+            accessible: false,
+            message_id: None,
+            channel_id: None,
+            sender: None,
+            content: None,
+            format: None,
+            created_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PollResponse {
+    pub question: String,
+    pub options: Vec<PollOptionResponse>,
+    pub allow_multiple: bool,
+    pub anonymous: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+    pub closed: bool,
+    pub total_voters: i64,
+    /// Options the requesting user voted for
+    pub my_votes: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PollOptionResponse {
+    pub id: Uuid,
+    pub text: String,
+    pub vote_count: i64,
+    /// Empty for anonymous polls
+    pub voters: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkPreviewResponse {
+    pub url: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// A previous version of an edited message
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MessageRevisionResponse {
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub replaced_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq)]
 pub struct UpdateMessageRequest {
     #[validate(length(min = 1, max = 10000, message = "Message must be 1-10000 characters"))]
     pub content: String,
+    /// Required when a moderator edits someone else's message
+    #[validate(length(min = 1, max = 500, message = "Reason must be 1-500 characters"))]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModerationRecordResponse {
+    pub id: Uuid,
+    pub message_id: Option<Uuid>,
+    pub channel_id: Uuid,
+    pub moderator: UserResponse,
+    pub action: ModerationAction,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContentRuleResponse {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub name: String,
+    pub kind: ContentRuleKind,
+    pub pattern: Option<String>,
+    pub words: Vec<String>,
+    pub action: ContentRuleAction,
+    pub enabled: bool,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq, Default)]
+pub struct CreateContentRuleRequest {
+    #[validate(length(min = 1, max = 100, message = "Rule name must be 1-100 characters"))]
+    pub name: String,
+    pub kind: ContentRuleKind,
+    /// Required for regex rules
+    #[validate(length(min = 1, max = 1000, message = "Pattern must be 1-1000 characters"))]
+    pub pattern: Option<String>,
+    /// Required for word list rules
+    #[validate(length(max = 1000, message = "A word list can have at most 1000 words"))]
+    pub words: Option<Vec<String>>,
+    pub action: ContentRuleAction,
+    pub enabled: Option<bool>,
+}
+
+/// The kind of a rule can't be changed
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq, Default)]
+pub struct UpdateContentRuleRequest {
+    #[validate(length(min = 1, max = 100, message = "Rule name must be 1-100 characters"))]
+    pub name: Option<String>,
+    #[validate(length(min = 1, max = 1000, message = "Pattern must be 1-1000 characters"))]
+    pub pattern: Option<String>,
+    #[validate(length(max = 1000, message = "A word list can have at most 1000 words"))]
+    pub words: Option<Vec<String>>,
+    pub action: Option<ContentRuleAction>,
+    pub enabled: Option<bool>,
+}
+
+/// A message waiting in the moderation queue
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlaggedMessageResponse {
+    pub id: Uuid,
+    pub message: MessageResponse,
+    pub rule_id: Option<Uuid>,
+    pub rule_name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A resolved mention, located by byte offsets into the message content
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MentionResponse {
+    pub mention_type: MentionType,
+    pub user_id: Option<Uuid>,
+    pub start: usize,
+    pub end: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -222,11 +604,37 @@ pub struct ReactionResponse {
     pub reacted_by_me: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq)]
 pub struct AddReactionRequest {
+    /// A single Unicode emoji, or a custom emoji as `:name:`
+    #[validate(length(min = 1, max = 64, message = "Emoji must be 1-64 characters"))]
     pub emoji: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq)]
+pub struct CreateCustomEmojiRequest {
+    #[validate(length(min = 2, max = 32, message = "Emoji name must be 2-32 characters"))]
+    pub name: String,
+    /// An uploaded image
+    pub file_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, PartialEq)]
+pub struct UpdateCustomEmojiRequest {
+    #[validate(length(min = 2, max = 32, message = "Emoji name must be 2-32 characters"))]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomEmojiResponse {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub name: String,
+    pub image_url: String,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
 // ============================================================================
 // File DTOs
 // ============================================================================
@@ -299,7 +707,12 @@ pub enum WebSocketMessage {
     Authenticate { token: String },
     JoinChannel { channel_id: Uuid },
     LeaveChannel { channel_id: Uuid },
-    SendMessage { channel_id: Uuid, content: String, reply_to_id: Option<Uuid> },
+    SendMessage {
+        channel_id: Uuid,
+        content: String,
+        reply_to_id: Option<Uuid>,
+        client_nonce: Option<String>,
+    },
     StartTyping { channel_id: Uuid },
     StopTyping { channel_id: Uuid },
     UpdateStatus { status: UserStatus, status_message: Option<String> },
@@ -309,14 +722,27 @@ pub enum WebSocketMessage {
     // Server -> Client
     Authenticated { user: UserResponse },
     Error { code: String, message: String },
-    NewMessage { message: MessageResponse },
+    /// `client_nonce` is the sender's nonce for the send, if it gave one
+    NewMessage {
+        message: MessageResponse,
+        client_nonce: Option<String>,
+    },
     MessageUpdated { message: MessageResponse },
-    MessageDeleted { channel_id: Uuid, message_id: Uuid },
+    /// `moderated` is set when a moderator removed the message, so clients can show
+    /// its tombstone as removed by a moderator
+    MessageDeleted { channel_id: Uuid, message_id: Uuid, moderated: bool },
+    /// Only sent to the user it is meant for
+    EphemeralMessage { message: EphemeralMessageResponse },
+    ReactionAdded { channel_id: Uuid, message_id: Uuid, emoji: String, user_id: Uuid },
+    ReactionRemoved { channel_id: Uuid, message_id: Uuid, emoji: String, user_id: Uuid },
     UserTyping { channel_id: Uuid, user: UserResponse },
     UserStoppedTyping { channel_id: Uuid, user_id: Uuid },
     UserStatusChanged { user_id: Uuid, status: UserStatus, status_message: Option<String> },
     UserJoinedChannel { channel_id: Uuid, user: UserResponse },
     UserLeftChannel { channel_id: Uuid, user_id: Uuid },
+    ReadReceipt { channel_id: Uuid, user_id: Uuid, last_read_at: DateTime<Utc> },
+    DraftUpdated { draft: DraftResponse },
+    PollUpdated { channel_id: Uuid, message_id: Uuid, options: Vec<PollOptionResponse>, total_voters: i64 },
     CallStarted { call: CallResponse },
     CallEnded { call_id: Uuid },
     ParticipantJoined { call_id: Uuid, participant: CallParticipantResponse },
@@ -364,32 +790,36 @@ pub struct SearchRequest {
     pub from_date: Option<DateTime<Utc>>,
     pub to_date: Option<DateTime<Utc>>,
     pub limit: Option<i64>,
-    pub offset: Option<i64>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct SearchResponse {
-    pub messages: Vec<MessageResponse>,
-    pub total_count: i64,
+    pub cursor: Option<String>,
 }
 
 // ============================================================================
 // Pagination DTOs
 // ============================================================================
 
+/// Query parameters of a paginated list. `cursor` is the `next_cursor` of the
+/// previous page; without it the newest items are returned.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PaginationParams {
     pub limit: Option<i64>,
-    pub offset: Option<i64>,
-    pub before: Option<DateTime<Utc>>,
-    pub after: Option<DateTime<Utc>>,
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PaginatedResponse<T: PartialEq> {
     pub items: Vec<T>,
-    pub total_count: i64,
     pub has_more: bool,
+    pub next_cursor: Option<String>,
+}
+
+impl<T: PartialEq> PaginatedResponse<T> {
+    pub fn map<U: PartialEq>(self, f: impl FnMut(T) -> U) -> PaginatedResponse<U> {
+        PaginatedResponse {
+            items: self.items.into_iter().map(f).collect(),
+            has_more: self.has_more,
+            next_cursor: self.next_cursor,
+        }
+    }
 }
 
 // ============================================================================
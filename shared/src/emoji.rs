@@ -0,0 +1,118 @@
+//! Emoji validation for reactions
+//!
+//! A reaction is either a single Unicode emoji, including skin tone, ZWJ, flag,
+//! keycap and tag sequences, or a team's custom emoji written as `:name:`.
+
+/// Longest emoji sequence accepted, in characters. The longest standard
+/// sequences, such as families with skin tones, are around a dozen.
+pub const MAX_EMOJI_CHARS: usize = 16;
+/// Longest custom emoji name
+pub const MAX_CUSTOM_EMOJI_NAME_LEN: usize = 32;
+/// Shortest custom emoji name
+const MIN_CUSTOM_EMOJI_NAME_LEN: usize = 2;
+
+const ZWJ: char = '\u{200D}';
+const VS16: char = '\u{FE0F}';
+const KEYCAP: char = '\u{20E3}';
+const BLACK_FLAG: char = '\u{1F3F4}';
+const CANCEL_TAG: char = '\u{E007F}';
+
+/// Whether the string is exactly one Unicode emoji
+pub fn is_emoji(s: &str) -> bool {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.is_empty() || chars.len() > MAX_EMOJI_CHARS {
+        return false;
+    }
+
+    match chars.as_slice() {
+        // Flags are pairs of regional indicators
+        [a, b] if is_regional_indicator(*a) && is_regional_indicator(*b) => true,
+        // Keycaps: a digit, `#` or `*`, then the enclosing keycap
+        ['0'..='9' | '#' | '*', rest @ ..] => {
+            matches!(rest, [KEYCAP] | [VS16, KEYCAP])
+        }
+        // Subdivision flags: a black flag followed by tag characters
+        [BLACK_FLAG, tags @ .., CANCEL_TAG] if !tags.is_empty() => {
+            tags.iter().all(|c| ('\u{E0020}'..='\u{E007E}').contains(c))
+        }
+        _ => chars.split(|c| *c == ZWJ).all(is_emoji_element),
+    }
+}
+
+/// Name of a custom emoji reference such as `:party_parrot:`
+pub fn custom_emoji_name(s: &str) -> Option<&str> {
+    let name = s.strip_prefix(':')?.strip_suffix(':')?;
+    is_valid_custom_emoji_name(name).then_some(name)
+}
+
+/// Custom emoji names are lowercase letters, digits, `_`, `-` and `+`
+pub fn is_valid_custom_emoji_name(name: &str) -> bool {
+    (MIN_CUSTOM_EMOJI_NAME_LEN..=MAX_CUSTOM_EMOJI_NAME_LEN).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'_' | b'-' | b'+'))
+}
+
+/// A pictograph, optionally followed by the emoji presentation selector or a skin tone
+fn is_emoji_element(element: &[char]) -> bool {
+    match element {
+        [base] | [base, VS16] => is_pictographic(*base),
+        [base, modifier] => is_pictographic(*base) && is_skin_tone(*modifier),
+        _ => false,
+    }
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+fn is_skin_tone(c: char) -> bool {
+    ('\u{1F3FB}'..='\u{1F3FF}').contains(&c)
+}
+
+/// Code points that can be emoji on their own, following the
+/// Extended_Pictographic property
+fn is_pictographic(c: char) -> bool {
+    matches!(c as u32,
+        0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139
+        | 0x2194..=0x2199 | 0x21A9..=0x21AA
+        | 0x231A..=0x231B | 0x2328 | 0x23CF | 0x23E9..=0x23F3 | 0x23F8..=0x23FA
+        | 0x24C2 | 0x25AA..=0x25AB | 0x25B6 | 0x25C0 | 0x25FB..=0x25FE
+        | 0x2600..=0x27BF
+        | 0x2934..=0x2935 | 0x2B05..=0x2B07 | 0x2B1B..=0x2B1C | 0x2B50 | 0x2B55
+        | 0x3030 | 0x303D | 0x3297 | 0x3299
+        | 0x1F000..=0x1F0FF
+        | 0x1F10D..=0x1F10F | 0x1F12F | 0x1F16C..=0x1F171 | 0x1F17E..=0x1F17F
+        | 0x1F18E | 0x1F191..=0x1F19A | 0x1F201..=0x1F251
+        | 0x1F300..=0x1F3FA | 0x1F400..=0x1FAFF
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode_emoji() {
+        for emoji in ["👍", "👍🏽", "❤️", "☕", "👨‍👩‍👧", "🏳️‍🌈", "🇫🇷", "1️⃣", "🏴󠁧󠁢󠁳󠁣󠁴󠁿"] {
+            assert!(is_emoji(emoji), "{} should be accepted", emoji);
+        }
+    }
+
+    #[test]
+    fn test_rejects_non_emoji() {
+        let long = "👍".repeat(40);
+        for input in ["", "a", "1", "👍👍", "👍 ", "🇫", "👍\u{200D}", "<script>", ":smile:", long.as_str()] {
+            assert!(!is_emoji(input), "{:?} should be rejected", input);
+        }
+    }
+
+    #[test]
+    fn test_custom_emoji_name() {
+        assert_eq!(custom_emoji_name(":party_parrot:"), Some("party_parrot"));
+        assert_eq!(custom_emoji_name(":+1:"), Some("+1"));
+        assert_eq!(custom_emoji_name(":Party:"), None);
+        assert_eq!(custom_emoji_name("::"), None);
+        assert_eq!(custom_emoji_name("party"), None);
+    }
+}
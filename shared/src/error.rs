@@ -32,8 +32,8 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
-    #[error("Rate limit exceeded")]
-    RateLimitExceeded,
+    #[error("Rate limit exceeded, retry after {retry_after} seconds")]
+    RateLimitExceeded { retry_after: u64 },
 
     #[error("File upload error: {0}")]
     FileUploadError(String),
@@ -54,6 +54,9 @@ pub struct ErrorDetail {
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<Vec<FieldError>>,
+    /// Seconds to wait before retrying a rate-limited request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,18 +76,26 @@ impl AppError {
             AppError::DatabaseError(_) => "DATABASE_ERROR",
             AppError::InternalError(_) => "INTERNAL_ERROR",
             AppError::BadRequest(_) => "BAD_REQUEST",
-            AppError::RateLimitExceeded => "RATE_LIMIT_EXCEEDED",
+            AppError::RateLimitExceeded { .. } => "RATE_LIMIT_EXCEEDED",
             AppError::FileUploadError(_) => "FILE_UPLOAD_ERROR",
             AppError::WebSocketError(_) => "WEBSOCKET_ERROR",
         }
     }
 
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            AppError::RateLimitExceeded { retry_after } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
     pub fn to_error_response(&self) -> ErrorResponse {
         ErrorResponse {
             error: ErrorDetail {
                 code: self.error_code().to_string(),
                 message: self.to_string(),
                 details: None,
+                retry_after: self.retry_after(),
             },
         }
     }
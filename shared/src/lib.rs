@@ -7,6 +7,11 @@ pub mod models;
 pub mod dto;
 pub mod error;
 pub mod validation;
+pub mod mentions;
+pub mod markdown;
+pub mod emoji;
+pub mod filters;
+pub mod pagination;
 
 pub use models::*;
 pub use dto::*;
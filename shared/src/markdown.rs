@@ -0,0 +1,583 @@
+//! Markdown dialect for message content
+//!
+//! Supports bold, italics, code spans, fenced code blocks, lists, links, quotes,
+//! mentions and `#channel` references. The AST never carries raw HTML and links
+//! are only produced for safe URL schemes, so clients can render it directly.
+//! Mention offsets are byte offsets into the original content, matching
+//! [`crate::mentions::parse_mentions`].
+
+use crate::mentions::{is_username_byte, mention_at, ParsedMention};
+use crate::models::MentionType;
+
+/// Deepest nesting of quotes; deeper `>` markers are kept as text
+const MAX_QUOTE_DEPTH: usize = 3;
+/// Deepest nesting of inline formatting; deeper delimiters are kept as text
+const MAX_INLINE_DEPTH: usize = 8;
+/// Longest channel name accepted by channel validation
+const MAX_CHANNEL_NAME_LEN: usize = 100;
+/// Longest language tag kept on a fenced code block
+const MAX_LANGUAGE_LEN: usize = 32;
+
+/// URL schemes that may be rendered as links
+const SAFE_URL_SCHEMES: &[&str] = &["http://", "https://", "mailto:"];
+
+/// A block-level element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Paragraph { content: Vec<Inline> },
+    CodeBlock { language: Option<String>, code: String },
+    Quote { content: Vec<Block> },
+    List { ordered: bool, items: Vec<Vec<Inline>> },
+}
+
+/// An inline element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline {
+    Text { text: String },
+    Bold { content: Vec<Inline> },
+    Italic { content: Vec<Inline> },
+    Code { code: String },
+    Link { url: String, content: Vec<Inline> },
+    Mention {
+        mention_type: MentionType,
+        username: Option<String>,
+        start: usize,
+        end: usize,
+    },
+    ChannelRef { name: String },
+    LineBreak,
+}
+
+/// A line of input and its byte offset in the original content
+#[derive(Debug, Clone, Copy)]
+struct Line<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+/// Parse message content into blocks.
+pub fn parse(content: &str) -> Vec<Block> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for text in content.split('\n') {
+        lines.push(Line {
+            text: text.strip_suffix('\r').unwrap_or(text),
+            offset,
+        });
+        offset += text.len() + 1;
+    }
+
+    parse_blocks(&lines, 0)
+}
+
+/// Collect the mentions in a parsed message, skipping anything inside code.
+pub fn collect_mentions(blocks: &[Block]) -> Vec<ParsedMention> {
+    let mut mentions = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Paragraph { content } => collect_inline_mentions(content, &mut mentions),
+            Block::Quote { content } => mentions.extend(collect_mentions(content)),
+            Block::List { items, .. } => {
+                for item in items {
+                    collect_inline_mentions(item, &mut mentions);
+                }
+            }
+            Block::CodeBlock { .. } => {}
+        }
+    }
+    mentions
+}
+
+/// Collect the distinct link targets in a parsed message, in order of appearance
+pub fn collect_links(blocks: &[Block]) -> Vec<String> {
+    let mut links = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Paragraph { content } => collect_inline_links(content, &mut links),
+            Block::Quote { content } => {
+                for link in collect_links(content) {
+                    if !links.contains(&link) {
+                        links.push(link);
+                    }
+                }
+            }
+            Block::List { items, .. } => {
+                for item in items {
+                    collect_inline_links(item, &mut links);
+                }
+            }
+            Block::CodeBlock { .. } => {}
+        }
+    }
+    links
+}
+
+/// Whether a URL uses a scheme that is safe to render as a link
+pub fn is_safe_url(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    SAFE_URL_SCHEMES
+        .iter()
+        .any(|scheme| lower.starts_with(scheme) && lower.len() > scheme.len())
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+fn collect_inline_mentions(nodes: &[Inline], mentions: &mut Vec<ParsedMention>) {
+    for node in nodes {
+        match node {
+            Inline::Mention {
+                mention_type,
+                username,
+                start,
+                end,
+            } => mentions.push(ParsedMention {
+                mention_type: *mention_type,
+                username: username.clone(),
+                start: *start,
+                end: *end,
+            }),
+            Inline::Bold { content } | Inline::Italic { content } | Inline::Link { content, .. } => {
+                collect_inline_mentions(content, mentions)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_inline_links(nodes: &[Inline], links: &mut Vec<String>) {
+    for node in nodes {
+        match node {
+            Inline::Link { url, .. } if !links.contains(url) => links.push(url.clone()),
+            Inline::Bold { content } | Inline::Italic { content } => {
+                collect_inline_links(content, links)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_blocks(lines: &[Line], depth: usize) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.text.trim_start();
+
+        if trimmed.is_empty() {
+            i += 1;
+        } else if let Some(info) = trimmed.strip_prefix("```") {
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && !lines[i].text.trim_start().starts_with("```") {
+                code.push(lines[i].text);
+                i += 1;
+            }
+            // Skip the closing fence; an unclosed block runs to the end
+            i += 1;
+            blocks.push(Block::CodeBlock {
+                language: parse_language(info),
+                code: code.join("\n"),
+            });
+        } else if depth < MAX_QUOTE_DEPTH && quote_line(line).is_some() {
+            let mut quoted = Vec::new();
+            while let Some(inner) = lines.get(i).and_then(|l| quote_line(*l)) {
+                quoted.push(inner);
+                i += 1;
+            }
+            blocks.push(Block::Quote {
+                content: parse_blocks(&quoted, depth + 1),
+            });
+        } else if let Some((ordered, _)) = list_item(line) {
+            let mut items = Vec::new();
+            while let Some((item_ordered, item)) = lines.get(i).and_then(|l| list_item(*l)) {
+                if item_ordered != ordered {
+                    break;
+                }
+                items.push(parse_inline(item.text, item.offset, 0));
+                i += 1;
+            }
+            blocks.push(Block::List { ordered, items });
+        } else {
+            let mut content = Vec::new();
+            while let Some(line) = lines.get(i) {
+                if line.text.trim().is_empty() || (!content.is_empty() && starts_block(*line, depth)) {
+                    break;
+                }
+                if !content.is_empty() {
+                    content.push(Inline::LineBreak);
+                }
+                content.extend(parse_inline(line.text, line.offset, 0));
+                i += 1;
+            }
+            blocks.push(Block::Paragraph { content });
+        }
+    }
+
+    blocks
+}
+
+fn starts_block(line: Line, depth: usize) -> bool {
+    line.text.trim_start().starts_with("```")
+        || (depth < MAX_QUOTE_DEPTH && quote_line(line).is_some())
+        || list_item(line).is_some()
+}
+
+fn parse_language(info: &str) -> Option<String> {
+    let language = info.split_whitespace().next()?;
+    let valid = language.len() <= MAX_LANGUAGE_LEN
+        && language
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-#._".contains(c));
+    valid.then(|| language.to_string())
+}
+
+/// The content of a `>` quote line
+fn quote_line(line: Line) -> Option<Line> {
+    let indent = line.text.len() - line.text.trim_start().len();
+    let rest = line.text[indent..].strip_prefix('>')?;
+    let text = rest.strip_prefix(' ').unwrap_or(rest);
+    Some(Line {
+        text,
+        offset: line.offset + line.text.len() - text.len(),
+    })
+}
+
+/// Whether a line is a list item, and if so whether it's ordered and its content
+fn list_item(line: Line) -> Option<(bool, Line)> {
+    let trimmed = line.text.trim_start();
+
+    let (ordered, text) = if let Some(text) = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| trimmed.strip_prefix(marker))
+    {
+        (false, text)
+    } else {
+        let digits = trimmed.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 || digits > 9 {
+            return None;
+        }
+        let rest = &trimmed[digits..];
+        let text = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") "))?;
+        (true, text)
+    };
+
+    Some((
+        ordered,
+        Line {
+            text,
+            offset: line.offset + line.text.len() - text.len(),
+        },
+    ))
+}
+
+fn parse_inline(text: &str, offset: usize, depth: usize) -> Vec<Inline> {
+    let bytes = text.as_bytes();
+    let mut nodes = Vec::new();
+    let mut pending = String::new();
+    let mut i = 0;
+
+    while i < text.len() {
+        if bytes[i] == b'\\' && bytes.get(i + 1).is_some_and(u8::is_ascii_punctuation) {
+            pending.push(bytes[i + 1] as char);
+            i += 2;
+            continue;
+        }
+
+        let parsed = match bytes[i] {
+            b'`' => code_span(&text[i..]),
+            b'*' | b'_' if depth < MAX_INLINE_DEPTH => emphasis(text, i, offset, depth),
+            b'[' if depth < MAX_INLINE_DEPTH => link(&text[i..], offset + i, depth),
+            b'@' => mention_at(text, i).map(|m| {
+                let len = m.end - m.start;
+                let node = Inline::Mention {
+                    mention_type: m.mention_type,
+                    username: m.username,
+                    start: offset + m.start,
+                    end: offset + m.end,
+                };
+                (node, len)
+            }),
+            b'#' => channel_ref(text, i),
+            b'h' | b'H' => autolink(text, i),
+            _ => None,
+        };
+
+        match parsed {
+            Some((node, len)) => {
+                if !pending.is_empty() {
+                    nodes.push(Inline::Text {
+                        text: std::mem::take(&mut pending),
+                    });
+                }
+                nodes.push(node);
+                i += len;
+            }
+            None => {
+                let ch = text[i..].chars().next().unwrap_or_default();
+                pending.push(ch);
+                i += ch.len_utf8();
+            }
+        }
+    }
+
+    if !pending.is_empty() {
+        nodes.push(Inline::Text { text: pending });
+    }
+
+    nodes
+}
+
+fn code_span(rest: &str) -> Option<(Inline, usize)> {
+    let close = rest[1..].find('`')?;
+    if close == 0 {
+        return None;
+    }
+    let code = rest[1..1 + close].to_string();
+    Some((Inline::Code { code }, close + 2))
+}
+
+/// `**bold**`, `__bold__`, `*italic*` or `_italic_` starting at byte `i`
+fn emphasis(text: &str, i: usize, offset: usize, depth: usize) -> Option<(Inline, usize)> {
+    let rest = &text[i..];
+    let bytes = rest.as_bytes();
+    let marker = bytes[0];
+
+    // Underscores inside words are literal, as in snake_case
+    if marker == b'_' && i > 0 && is_username_byte(text.as_bytes()[i - 1]) {
+        return None;
+    }
+
+    let width = if bytes.get(1) == Some(&marker) { 2 } else { 1 };
+    let delimiter = &rest[..width];
+    let first = rest[width..].chars().next().filter(|c| !c.is_whitespace())?;
+
+    let mut from = width + first.len_utf8();
+    while from <= rest.len() {
+        let mut close = from + rest[from..].find(delimiter)?;
+
+        // A doubled marker inside single emphasis belongs to nested bold
+        if width == 1 && bytes.get(close + 1) == Some(&marker) {
+            from = close + 2;
+            continue;
+        }
+        // Prefer the last delimiter of a run, so `***x***` nests cleanly
+        while bytes.get(close + width) == Some(&marker) {
+            close += 1;
+        }
+
+        let closes = !rest[..close].ends_with(char::is_whitespace)
+            && (marker != b'_' || !bytes.get(close + width).is_some_and(|b| is_username_byte(*b)));
+        if closes {
+            let content = parse_inline(&rest[width..close], offset + i + width, depth + 1);
+            let node = if width == 2 {
+                Inline::Bold { content }
+            } else {
+                Inline::Italic { content }
+            };
+            return Some((node, close + width));
+        }
+        from = close + width;
+    }
+
+    None
+}
+
+/// `[label](url)` starting at the given offset
+fn link(rest: &str, offset: usize, depth: usize) -> Option<(Inline, usize)> {
+    let label_end = rest.find(']')?;
+    let target = rest[label_end + 1..].strip_prefix('(')?;
+    let url_end = target.find(')')?;
+    let label = &rest[1..label_end];
+    let url = target[..url_end].trim();
+    let len = label_end + 2 + url_end + 1;
+
+    if label.is_empty() {
+        return None;
+    }
+
+    if !is_safe_url(url) {
+        // Unsafe targets are dropped, keeping only the label
+        let node = Inline::Text {
+            text: label.to_string(),
+        };
+        return Some((node, len));
+    }
+
+    let node = Inline::Link {
+        url: url.to_string(),
+        content: parse_inline(label, offset + 1, depth + 1),
+    };
+    Some((node, len))
+}
+
+/// `#channel-name` starting at byte `i`
+fn channel_ref(text: &str, i: usize) -> Option<(Inline, usize)> {
+    let bytes = text.as_bytes();
+    if i > 0 && is_username_byte(bytes[i - 1]) {
+        return None;
+    }
+    if !bytes.get(i + 1).is_some_and(u8::is_ascii_alphabetic) {
+        return None;
+    }
+
+    let len = bytes[i + 1..]
+        .iter()
+        .take_while(|b| is_username_byte(**b))
+        .count();
+    if len > MAX_CHANNEL_NAME_LEN {
+        return None;
+    }
+
+    let name = text[i + 1..i + 1 + len].to_string();
+    Some((Inline::ChannelRef { name }, len + 1))
+}
+
+/// A bare `http://` or `https://` URL starting at byte `i`
+fn autolink(text: &str, i: usize) -> Option<(Inline, usize)> {
+    if i > 0 && text.as_bytes()[i - 1].is_ascii_alphanumeric() {
+        return None;
+    }
+
+    let rest = &text[i..];
+    let scheme = rest.get(..8).unwrap_or(rest).to_ascii_lowercase();
+    if !(scheme.starts_with("http://") || scheme.starts_with("https://")) {
+        return None;
+    }
+
+    let end = rest
+        .find(|c: char| c.is_whitespace() || c == '<' || c == '>')
+        .unwrap_or(rest.len());
+    let mut url = &rest[..end];
+
+    // Trailing punctuation usually ends the sentence rather than the URL
+    url = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+    if url.ends_with(')') && !url.contains('(') {
+        url = &url[..url.len() - 1];
+    }
+
+    if !is_safe_url(url) {
+        return None;
+    }
+
+    let node = Inline::Link {
+        url: url.to_string(),
+        content: vec![Inline::Text {
+            text: url.to_string(),
+        }],
+    };
+    Some((node, url.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Inline {
+        Inline::Text { text: s.to_string() }
+    }
+
+    #[test]
+    fn test_parse_inline_formatting() {
+        let blocks = parse("**bold** and *it **both***, `co*de*` in snake_case_name");
+        let Block::Paragraph { content } = &blocks[0] else {
+            panic!("expected paragraph");
+        };
+        assert_eq!(content[0], Inline::Bold { content: vec![text("bold")] });
+        assert_eq!(
+            content[2],
+            Inline::Italic {
+                content: vec![text("it "), Inline::Bold { content: vec![text("both")] }],
+            }
+        );
+        assert_eq!(content[4], Inline::Code { code: "co*de*".to_string() });
+        assert_eq!(content[5], text(" in snake_case_name"));
+    }
+
+    #[test]
+    fn test_parse_blocks() {
+        let blocks = parse("intro\n```rust\nlet x = **1**;\n```\n> quoted\n> more\n- one\n- two\n1. first");
+        assert_eq!(blocks.len(), 5);
+        assert_eq!(
+            blocks[1],
+            Block::CodeBlock {
+                language: Some("rust".to_string()),
+                code: "let x = **1**;".to_string(),
+            }
+        );
+        assert_eq!(
+            blocks[2],
+            Block::Quote {
+                content: vec![Block::Paragraph {
+                    content: vec![text("quoted"), Inline::LineBreak, text("more")],
+                }],
+            }
+        );
+        assert_eq!(
+            blocks[3],
+            Block::List {
+                ordered: false,
+                items: vec![vec![text("one")], vec![text("two")]],
+            }
+        );
+        assert!(matches!(blocks[4], Block::List { ordered: true, .. }));
+    }
+
+    #[test]
+    fn test_links_are_sanitized() {
+        let blocks = parse("[docs](https://example.com) [bad](javascript:alert(1)) see http://a.io/x.");
+        let Block::Paragraph { content } = &blocks[0] else {
+            panic!("expected paragraph");
+        };
+        assert_eq!(
+            content[0],
+            Inline::Link {
+                url: "https://example.com".to_string(),
+                content: vec![text("docs")],
+            }
+        );
+        assert!(content.iter().all(|node| match node {
+            Inline::Link { url, .. } => !url.starts_with("javascript"),
+            _ => true,
+        }));
+        assert!(content.contains(&Inline::Link {
+            url: "http://a.io/x".to_string(),
+            content: vec![text("http://a.io/x")],
+        }));
+        assert!(!is_safe_url("data:text/html,hi"));
+    }
+
+    #[test]
+    fn test_mentions_and_channel_refs() {
+        let content = "> hi @john_doe see #dev-team\n`@ignored` @here";
+        let blocks = parse(content);
+        let mentions = collect_mentions(&blocks);
+        assert_eq!(mentions.len(), 2);
+        assert_eq!(&content[mentions[0].start..mentions[0].end], "@john_doe");
+        assert_eq!(mentions[1].mention_type, MentionType::Here);
+
+        let Block::Quote { content: quoted } = &blocks[0] else {
+            panic!("expected quote");
+        };
+        let Block::Paragraph { content } = &quoted[0] else {
+            panic!("expected paragraph");
+        };
+        assert!(content.contains(&Inline::ChannelRef { name: "dev-team".to_string() }));
+    }
+
+    #[test]
+    fn test_collect_links() {
+        let blocks = parse("see https://a.io and [docs](https://b.io)\n> https://a.io again\n`https://c.io`");
+        assert_eq!(collect_links(&blocks), vec!["https://a.io", "https://b.io"]);
+    }
+
+    #[test]
+    fn test_unmatched_delimiters_are_text() {
+        let blocks = parse("2 * 3 = 6, a `tick, [not a link] and \\*escaped*");
+        assert_eq!(
+            blocks,
+            vec![Block::Paragraph {
+                content: vec![text("2 * 3 = 6, a `tick, [not a link] and *escaped*")],
+            }]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Mention parsing for message content
+//!
+//! Recognizes `@username`, `@channel` and `@here` tokens. Offsets are byte
+//! offsets into the message content and cover the leading `@`.
+
+use crate::models::MentionType;
+
+/// Longest username accepted at registration
+const MAX_USERNAME_LEN: usize = 30;
+/// Shortest username accepted at registration
+const MIN_USERNAME_LEN: usize = 3;
+
+/// A mention token found in message content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedMention {
+    pub mention_type: MentionType,
+    /// The mentioned username, for user mentions
+    pub username: Option<String>,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Find all mentions in a message.
+///
+/// A mention starts with `@` at the beginning of the text or after a character that
+/// can't be part of a username, so e-mail addresses are not treated as mentions.
+pub fn parse_mentions(content: &str) -> Vec<ParsedMention> {
+    let mut mentions = Vec::new();
+    let mut i = 0;
+
+    while i < content.len() {
+        match mention_at(content, i) {
+            Some(mention) => {
+                i = mention.end;
+                mentions.push(mention);
+            }
+            None => i += 1,
+        }
+    }
+
+    mentions
+}
+
+/// Parse a mention starting exactly at byte offset `start`, if there is one.
+pub(crate) fn mention_at(content: &str, start: usize) -> Option<ParsedMention> {
+    let bytes = content.as_bytes();
+    if bytes.get(start) != Some(&b'@') || (start > 0 && is_username_byte(bytes[start - 1])) {
+        return None;
+    }
+
+    let mut end = start + 1;
+    while end < bytes.len() && is_username_byte(bytes[end]) {
+        end += 1;
+    }
+
+    let name = &content[start + 1..end];
+    let (mention_type, username) = if name.eq_ignore_ascii_case("channel") {
+        (MentionType::Channel, None)
+    } else if name.eq_ignore_ascii_case("here") {
+        (MentionType::Here, None)
+    } else if (MIN_USERNAME_LEN..=MAX_USERNAME_LEN).contains(&name.len()) {
+        (MentionType::User, Some(name.to_string()))
+    } else {
+        return None;
+    };
+
+    Some(ParsedMention {
+        mention_type,
+        username,
+        start,
+        end,
+    })
+}
+
+pub(crate) fn is_username_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'-'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_user_mentions() {
+        let mentions = parse_mentions("hey @john_doe and @test-user!");
+        assert_eq!(mentions.len(), 2);
+        assert_eq!(mentions[0].username.as_deref(), Some("john_doe"));
+        assert_eq!((mentions[0].start, mentions[0].end), (4, 13));
+        assert_eq!(mentions[1].username.as_deref(), Some("test-user"));
+        assert_eq!(mentions[1].mention_type, MentionType::User);
+    }
+
+    #[test]
+    fn test_parse_channel_and_here() {
+        let mentions = parse_mentions("@channel deploy at 5, @HERE please review");
+        assert_eq!(mentions.len(), 2);
+        assert_eq!(mentions[0].mention_type, MentionType::Channel);
+        assert_eq!(mentions[1].mention_type, MentionType::Here);
+        assert_eq!(mentions[1].username, None);
+    }
+
+    #[test]
+    fn test_ignore_non_mentions() {
+        assert!(parse_mentions("mail me at john@example.com").is_empty());
+        assert!(parse_mentions("@ab is too short").is_empty());
+        assert!(parse_mentions("a lone @ sign").is_empty());
+        assert!(parse_mentions("no mentions here").is_empty());
+    }
+
+    #[test]
+    fn test_offsets_with_multibyte_text() {
+        let content = "héllo @user123.";
+        let mentions = parse_mentions(content);
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(&content[mentions[0].start..mentions[0].end], "@user123");
+    }
+}
@@ -45,6 +45,7 @@ pub struct User {
 pub enum TeamRole {
     Owner,
     Admin,
+    Moderator,
     Member,
 }
 
@@ -54,6 +55,13 @@ impl Default for TeamRole {
     }
 }
 
+impl TeamRole {
+    /// Owners, admins and moderators can moderate the team's channels
+    pub fn can_moderate(&self) -> bool {
+        matches!(self, Self::Owner | Self::Admin | Self::Moderator)
+    }
+}
+
 /// Channel type
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -69,6 +77,48 @@ impl Default for ChannelType {
     }
 }
 
+/// How much of a channel's history members can see
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryVisibility {
+    /// Members see all messages, including those sent before they joined
+    Full,
+    /// Members only see messages sent after they joined
+    Joined,
+}
+
+impl Default for HistoryVisibility {
+    fn default() -> Self {
+        Self::Full
+    }
+}
+
+/// Which messages in a channel raise notifications for a member
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationLevel {
+    All,
+    Mentions,
+    Nothing,
+}
+
+impl Default for NotificationLevel {
+    fn default() -> Self {
+        Self::All
+    }
+}
+
+impl NotificationLevel {
+    /// The level members start with: every message in direct messages, only
+    /// mentions elsewhere, so busy channels don't notify everyone on every message
+    pub fn default_for(channel_type: ChannelType) -> Self {
+        match channel_type {
+            ChannelType::DirectMessage => Self::All,
+            _ => Self::Mentions,
+        }
+    }
+}
+
 /// Message type
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -77,6 +127,7 @@ pub enum MessageType {
     Image,
     File,
     System,
+    Poll,
 }
 
 impl Default for MessageType {
@@ -85,6 +136,39 @@ impl Default for MessageType {
     }
 }
 
+/// How message content is rendered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    Plain,
+    /// Rendered with the [`crate::markdown`] dialect
+    Markdown,
+}
+
+impl Default for MessageFormat {
+    fn default() -> Self {
+        Self::Plain
+    }
+}
+
+/// What a mention in a message refers to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MentionType {
+    /// A single user, `@username`
+    User,
+    /// Every channel member, `@channel`
+    Channel,
+    /// Channel members who are currently online, `@here`
+    Here,
+}
+
+impl Default for MentionType {
+    fn default() -> Self {
+        Self::User
+    }
+}
+
 /// Call type
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -174,6 +258,12 @@ pub struct Channel {
     pub description: Option<String>,
     pub channel_type: ChannelType,
     pub created_by: Uuid,
+    pub is_archived: bool,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub is_announcement: bool,
+    pub slow_mode_seconds: i32,
+    pub burst_limit: i32,
+    pub history_visibility: HistoryVisibility,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -186,6 +276,27 @@ pub struct ChannelMember {
     pub user_id: Uuid,
     pub joined_at: DateTime<Utc>,
     pub last_read_at: Option<DateTime<Utc>>,
+    pub can_post: bool,
+    pub notify_level: NotificationLevel,
+    pub muted_until: Option<DateTime<Utc>>,
+    pub hidden: bool,
+}
+
+impl ChannelMember {
+    /// Whether the member has muted the channel at the given time
+    pub fn is_muted_at(&self, now: DateTime<Utc>) -> bool {
+        self.muted_until.is_some_and(|until| until > now)
+    }
+}
+
+/// A team a channel is shared with, in addition to its host team
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChannelTeam {
+    pub channel_id: Uuid,
+    pub team_id: Uuid,
+    pub invited_by: Uuid,
+    pub invited_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
 }
 
 /// Message model
@@ -196,10 +307,239 @@ pub struct Message {
     pub sender_id: Uuid,
     pub content: String,
     pub message_type: MessageType,
+    pub format: MessageFormat,
     pub reply_to_id: Option<Uuid>,
     pub edited: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Set when the message has been deleted; the row is kept as a tombstone
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<Uuid>,
+    /// Set when a moderator edited or removed someone else's message
+    pub moderated_by: Option<Uuid>,
+    /// Set on self-destructing messages; cleared once they have been purged
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A previous version of a message, recorded on every edit and on deletion
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MessageRevision {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub content: String,
+    /// The user whose edit or deletion replaced this version
+    pub replaced_by: Uuid,
+    /// When this version was written
+    pub created_at: DateTime<Utc>,
+    pub replaced_at: DateTime<Utc>,
+}
+
+/// Where a scheduled message is in being posted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduledMessageStatus {
+    /// Waiting for `send_at`
+    Pending,
+    /// Taken by the dispatcher
+    Sending,
+    /// Could not be posted; kept until the author retries or cancels it
+    Failed,
+}
+
+impl Default for ScheduledMessageStatus {
+    fn default() -> Self {
+        Self::Pending
+    }
+}
+
+/// A message waiting to be posted at `send_at`, visible only to its author
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduledMessage {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub sender_id: Uuid,
+    pub content: String,
+    pub message_type: MessageType,
+    pub format: MessageFormat,
+    pub reply_to_id: Option<Uuid>,
+    pub attachment_ids: Vec<Uuid>,
+    pub send_at: DateTime<Utc>,
+    pub status: ScheduledMessageStatus,
+    /// Why the last attempt to post it failed
+    pub error: Option<String>,
+    /// Nonce of the request that scheduled it, also used when it is posted
+    pub client_nonce: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An unsent message, one per user per channel or thread
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Draft {
+    pub user_id: Uuid,
+    pub channel_id: Uuid,
+    /// The message being replied to, for drafts in a thread
+    pub thread_root_id: Option<Uuid>,
+    pub content: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Snapshot of the message a forwarded message was shared from. The source
+/// references are cleared if the original message, channel or sender is removed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MessageForward {
+    pub message_id: Uuid,
+    pub source_message_id: Option<Uuid>,
+    pub source_channel_id: Option<Uuid>,
+    pub source_sender_id: Option<Uuid>,
+    pub content: String,
+    pub format: MessageFormat,
+    pub source_created_at: DateTime<Utc>,
+}
+
+/// A poll posted as a message. The message content holds the question.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Poll {
+    pub message_id: Uuid,
+    pub question: String,
+    pub allow_multiple: bool,
+    /// Votes are counted but voters are not shown
+    pub anonymous: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Poll {
+    pub fn is_closed(&self) -> bool {
+        self.closes_at.is_some_and(|closes_at| closes_at <= Utc::now())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PollOption {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub position: i32,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PollVote {
+    pub message_id: Uuid,
+    pub option_id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Cached page metadata for a link. All fields are empty when the page had
+/// no usable metadata or could not be fetched.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// What a moderator did to a message
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    Edit,
+    Delete,
+}
+
+impl Default for ModerationAction {
+    fn default() -> Self {
+        Self::Delete
+    }
+}
+
+/// Audit record of a moderator acting on another user's message
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModerationRecord {
+    pub id: Uuid,
+    /// Cleared if the message is later purged
+    pub message_id: Option<Uuid>,
+    pub channel_id: Uuid,
+    pub team_id: Uuid,
+    pub moderator_id: Uuid,
+    pub action: ModerationAction,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What a content rule looks for
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentRuleKind {
+    /// A custom regular expression
+    Regex,
+    /// Built-in detector for API keys, tokens and private keys
+    Secrets,
+    /// Built-in detector for payment card numbers
+    Cards,
+    /// A list of words, matched whole and ignoring case
+    Words,
+}
+
+impl Default for ContentRuleKind {
+    fn default() -> Self {
+        Self::Regex
+    }
+}
+
+/// What happens to a message a content rule matches
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentRuleAction {
+    /// The message is rejected
+    Block,
+    /// The matched text is replaced before the message is stored
+    Redact,
+    /// The message is posted and queued for moderators to review
+    Flag,
+}
+
+impl Default for ContentRuleAction {
+    fn default() -> Self {
+        Self::Flag
+    }
+}
+
+/// A team's rule for content posted in its channels
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContentRule {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub name: String,
+    pub kind: ContentRuleKind,
+    /// The expression of regex rules
+    pub pattern: Option<String>,
+    /// The words of word list rules
+    pub words: Vec<String>,
+    pub action: ContentRuleAction,
+    pub enabled: bool,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A message a content rule flagged for moderators
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlaggedMessage {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub channel_id: Uuid,
+    pub team_id: Uuid,
+    /// Cleared if the rule is deleted
+    pub rule_id: Option<Uuid>,
+    pub rule_name: String,
+    pub created_at: DateTime<Utc>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
 }
 
 /// Reaction model
@@ -212,6 +552,17 @@ pub struct Reaction {
     pub created_at: DateTime<Utc>,
 }
 
+/// Mention model
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MessageMention {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub mention_type: MentionType,
+    pub user_id: Option<Uuid>,
+    pub start_offset: i32,
+    pub end_offset: i32,
+}
+
 /// File attachment model
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FileAttachment {
@@ -226,6 +577,17 @@ pub struct FileAttachment {
     pub created_at: DateTime<Utc>,
 }
 
+/// A team's custom emoji. The image is a file uploaded through the file subsystem.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomEmoji {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub name: String,
+    pub file_id: Uuid,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
 /// Call model
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Call {
@@ -0,0 +1,125 @@
+//! Keyset pagination
+//!
+//! Lists are ordered newest first by `(created_at, id)`. A cursor holds that key for
+//! the last item of a page, and the next page starts strictly after it, so items that
+//! share a timestamp are neither skipped nor repeated. Cursors are opaque to clients.
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::dto::PaginatedResponse;
+use crate::error::AppError;
+
+/// Largest page a client may ask for
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn new(created_at: DateTime<Utc>, id: Uuid) -> Self {
+        Self { created_at, id }
+    }
+
+    /// Hex encoding of the timestamp in microseconds followed by the id
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(24);
+        bytes.extend_from_slice(&self.created_at.timestamp_micros().to_be_bytes());
+        bytes.extend_from_slice(self.id.as_bytes());
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        if value.len() != 48 || !value.is_ascii() {
+            return None;
+        }
+        let bytes = (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+
+        let micros = i64::from_be_bytes(bytes[..8].try_into().ok()?);
+        let created_at = DateTime::from_timestamp_micros(micros)?;
+        let id = Uuid::from_slice(&bytes[8..]).ok()?;
+        Some(Self { created_at, id })
+    }
+
+    /// Decode a cursor given by a client. A missing cursor means the first page.
+    pub fn parse(value: Option<&str>) -> Result<Option<Self>, AppError> {
+        value
+            .map(|v| Self::decode(v).ok_or_else(|| AppError::ValidationError("Invalid cursor".to_string())))
+            .transpose()
+    }
+}
+
+/// The page size to use, given the size asked for
+pub fn page_size(limit: Option<i64>, default: i64) -> i64 {
+    limit.unwrap_or(default).clamp(1, MAX_PAGE_SIZE)
+}
+
+/// Turn up to `limit + 1` items, fetched newest first, into a page. The extra item
+/// only tells whether there are more.
+pub fn paginate<T: PartialEq>(
+    mut items: Vec<T>,
+    limit: i64,
+    key: impl Fn(&T) -> Cursor,
+) -> PaginatedResponse<T> {
+    let has_more = items.len() as i64 > limit;
+    items.truncate(limit.max(0) as usize);
+    let next_cursor = if has_more {
+        items.last().map(|item| key(item).encode())
+    } else {
+        None
+    };
+
+    PaginatedResponse {
+        items,
+        has_more,
+        next_cursor,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor::new(
+            DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap(),
+            Uuid::new_v4(),
+        );
+        let encoded = cursor.encode();
+        assert_eq!(encoded.len(), 48);
+        assert_eq!(Cursor::decode(&encoded), Some(cursor));
+    }
+
+    #[test]
+    fn test_invalid_cursor() {
+        let long = "0".repeat(50);
+        for value in ["", "abc", "zz".repeat(24).as_str(), long.as_str(), "é".repeat(24).as_str()] {
+            assert_eq!(Cursor::decode(value), None, "{:?} should be rejected", value);
+        }
+        assert!(Cursor::parse(Some("abc")).is_err());
+        assert_eq!(Cursor::parse(None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_paginate() {
+        let now = Utc::now();
+        let items: Vec<(DateTime<Utc>, Uuid)> = (0..3).map(|_| (now, Uuid::new_v4())).collect();
+
+        let page = paginate(items.clone(), 2, |(created_at, id)| Cursor::new(*created_at, *id));
+        assert_eq!(page.items.len(), 2);
+        assert!(page.has_more);
+        let cursor = Cursor::decode(page.next_cursor.as_deref().unwrap()).unwrap();
+        assert_eq!(cursor.id, items[1].1);
+
+        let page = paginate(items[..2].to_vec(), 2, |(created_at, id)| Cursor::new(*created_at, *id));
+        assert!(!page.has_more);
+        assert_eq!(page.next_cursor, None);
+    }
+}
@@ -35,6 +35,7 @@ pub fn validate_channel_name(name: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dto::UpdateRetentionPolicyRequest;
 
     #[test]
     fn test_validate_username() {
@@ -53,4 +54,14 @@ mod tests {
         assert!(!validate_channel_name("")); // empty
         assert!(!validate_channel_name("channel name")); // space not allowed
     }
+
+    #[test]
+    fn test_validate_retention_window() {
+        let request = |days| UpdateRetentionPolicyRequest { retention_days: days };
+        assert!(validate_request(&request(None)).is_ok()); // inherit
+        assert!(validate_request(&request(Some(0))).is_ok()); // forever
+        assert!(validate_request(&request(Some(36500))).is_ok());
+        assert!(validate_request(&request(Some(-1))).is_err());
+        assert!(validate_request(&request(Some(36501))).is_err());
+    }
 }